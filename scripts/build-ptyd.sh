@@ -0,0 +1,14 @@
+#!/bin/bash
+# Build stagehand-ptyd and put it where `bundle.externalBin` expects it, so
+# `tauri build` ships it next to the app. Runs as part of beforeBuildCommand.
+# Usage: ./scripts/build-ptyd.sh
+
+set -euo pipefail
+cd "$(dirname "$0")/../src-tauri"
+
+TRIPLE="${TAURI_ENV_TARGET_TRIPLE:-$(rustc -vV | sed -n 's/^host: //p')}"
+
+cargo build --release --bin stagehand-ptyd --target "$TRIPLE"
+mkdir -p binaries
+cp "target/$TRIPLE/release/stagehand-ptyd" "binaries/stagehand-ptyd-$TRIPLE"
+echo "Bundling binaries/stagehand-ptyd-$TRIPLE"
@@ -0,0 +1,16 @@
+// Input latency across 20 concurrent PTY sessions while one of them is
+// stalled. Run with `cargo bench --features bench --bench pty_sessions`.
+
+const SESSIONS: usize = 20;
+const ROUNDS: usize = 50;
+
+fn main() {
+    let runtime = tokio::runtime::Runtime::new().expect("failed to start runtime");
+    let stats = runtime
+        .block_on(app_lib::bench::pty_round_trips(SESSIONS, ROUNDS))
+        .expect("benchmark failed");
+    println!(
+        "{} shells, one stalled: {} round trips, p50 {:?}, p99 {:?}, max {:?}",
+        SESSIONS, stats.samples, stats.p50, stats.p99, stats.max
+    );
+}
@@ -1,3 +1,17 @@
+use std::path::Path;
+
 fn main() {
-  tauri_build::build()
+    // tauri-build wants the `stagehand-ptyd` sidecar to exist, but this
+    // crate is what builds it. An empty placeholder keeps plain cargo
+    // builds working; scripts/build-ptyd.sh puts the real binary there
+    // before `tauri build`, and the app treats an empty one as missing.
+    let target = std::env::var("TARGET").expect("cargo sets TARGET");
+    let suffix = if target.contains("windows") { ".exe" } else { "" };
+    let sidecar = Path::new("binaries").join(format!("stagehand-ptyd-{}{}", target, suffix));
+    if !sidecar.exists() {
+        std::fs::create_dir_all("binaries").expect("failed to create binaries/");
+        std::fs::write(&sidecar, b"").expect("failed to create sidecar placeholder");
+    }
+
+    tauri_build::build()
 }
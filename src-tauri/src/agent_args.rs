@@ -0,0 +1,454 @@
+use crate::agents::Agent;
+use crate::temp_context::TempContext;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+/// Patch a JSON Schema so it conforms to OpenAI's strict structured-output rules:
+///   1. Every object must have `"additionalProperties": false`.
+///   2. Every property defined in `properties` must appear in `required`.
+///      Properties that were previously optional are made nullable instead
+///      (type becomes `["<original>", "null"]`).
+///
+/// This lets the canonical stage templates stay agent-agnostic while Codex
+/// gets a compliant schema at the boundary.
+fn patch_schema_for_openai(value: &mut serde_json::Value) {
+    if let Some(obj) = value.as_object_mut() {
+        if obj.get("type").and_then(|v| v.as_str()) == Some("object") {
+            // 1. additionalProperties: false
+            obj.entry("additionalProperties")
+                .or_insert(serde_json::Value::Bool(false));
+
+            // 2. Ensure every key in `properties` is in `required`.
+            //    For newly-required keys, make the property nullable so the
+            //    model can still omit the value by sending null.
+            if let Some(props) = obj.get("properties").and_then(|p| p.as_object()).cloned() {
+                let all_keys: Vec<String> = props.keys().cloned().collect();
+
+                let existing_required: std::collections::HashSet<String> = obj
+                    .get("required")
+                    .and_then(|r| r.as_array())
+                    .map(|arr| {
+                        arr.iter()
+                            .filter_map(|v| v.as_str().map(String::from))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                // Make previously-optional properties nullable
+                if let Some(props_mut) = obj.get_mut("properties").and_then(|p| p.as_object_mut()) {
+                    for key in &all_keys {
+                        if !existing_required.contains(key) {
+                            if let Some(prop) = props_mut.get_mut(key).and_then(|p| p.as_object_mut()) {
+                                if let Some(ty) = prop.get("type").cloned() {
+                                    if ty.is_string() {
+                                        prop.insert(
+                                            "type".to_string(),
+                                            serde_json::json!([ty.as_str().unwrap(), "null"]),
+                                        );
+                                    }
+                                    // If already an array type, append "null" if missing
+                                    else if let Some(arr) = ty.as_array() {
+                                        if !arr.iter().any(|v| v.as_str() == Some("null")) {
+                                            let mut new_arr = arr.clone();
+                                            new_arr.push(serde_json::json!("null"));
+                                            prop.insert("type".to_string(), serde_json::Value::Array(new_arr));
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+
+                // Set required to ALL keys
+                let required_arr: Vec<serde_json::Value> = all_keys
+                    .into_iter()
+                    .map(serde_json::Value::String)
+                    .collect();
+                obj.insert("required".to_string(), serde_json::Value::Array(required_arr));
+            }
+        }
+
+        // Recurse into all sub-values
+        for (_, v) in obj.iter_mut() {
+            patch_schema_for_openai(v);
+        }
+    } else if let Some(arr) = value.as_array_mut() {
+        for v in arr.iter_mut() {
+            patch_schema_for_openai(v);
+        }
+    }
+}
+
+/// Convert Claude-format MCP config JSON to Codex `.codex/config.toml` format.
+///
+/// Input (Claude format):
+/// ```json
+/// {"mcpServers":{"name":{"command":"node","args":["path"],"env":{"K":"V"}}}}
+/// ```
+///
+/// Output (Codex TOML):
+/// ```toml
+/// [mcp_servers.name]
+/// command = "node"
+/// args = ["path"]
+/// env = { K = "V" }
+/// ```
+fn convert_mcp_json_to_codex_toml(mcp_json: &str) -> Result<String, String> {
+    let parsed: serde_json::Value =
+        serde_json::from_str(mcp_json).map_err(|e| format!("Invalid MCP JSON: {}", e))?;
+
+    let servers = parsed
+        .get("mcpServers")
+        .and_then(|v| v.as_object())
+        .ok_or("MCP JSON missing mcpServers object")?;
+
+    let mut toml = String::new();
+    for (name, config) in servers {
+        // Codex expects [mcp_servers.<name>] tables, NOT [[mcp_servers]] arrays.
+        toml.push_str(&format!("[mcp_servers.{:?}]\n", name));
+
+        if let Some(command) = config.get("command").and_then(|v| v.as_str()) {
+            toml.push_str(&format!("command = {:?}\n", command));
+        }
+
+        if let Some(args) = config.get("args").and_then(|v| v.as_array()) {
+            let args_str: Vec<String> = args
+                .iter()
+                .filter_map(|a| a.as_str())
+                .map(|a| format!("{:?}", a))
+                .collect();
+            toml.push_str(&format!("args = [{}]\n", args_str.join(", ")));
+        }
+
+        if let Some(env) = config.get("env").and_then(|v| v.as_object()) {
+            if !env.is_empty() {
+                let pairs: Vec<String> = env
+                    .iter()
+                    .map(|(k, v)| {
+                        let val = v.as_str().unwrap_or("");
+                        format!("{} = {:?}", k, val)
+                    })
+                    .collect();
+                toml.push_str(&format!("env = {{ {} }}\n", pairs.join(", ")));
+            }
+        }
+
+        toml.push('\n');
+    }
+
+    Ok(toml)
+}
+
+/// Convert Claude-format MCP config JSON to Gemini `.gemini/settings.json` format.
+///
+/// Input (Claude format):
+/// ```json
+/// {"mcpServers":{"name":{"command":"node","args":["path"],"env":{"K":"V"}}}}
+/// ```
+///
+/// Output (Gemini settings.json):
+/// ```json
+/// {"mcpServers":{"name":{"command":"node","args":["path"],"env":{"K":"V"}}}}
+/// ```
+///
+/// Gemini uses the same format as Claude, so this is essentially a pass-through
+/// but we validate and re-serialize to ensure correctness.
+fn convert_mcp_json_to_gemini_settings(mcp_json: &str) -> Result<String, String> {
+    let parsed: serde_json::Value =
+        serde_json::from_str(mcp_json).map_err(|e| format!("Invalid MCP JSON: {}", e))?;
+
+    // Verify structure
+    parsed
+        .get("mcpServers")
+        .and_then(|v| v.as_object())
+        .ok_or("MCP JSON missing mcpServers object")?;
+
+    // Gemini uses the same mcpServers format
+    serde_json::to_string_pretty(&parsed)
+        .map_err(|e| format!("Failed to serialize Gemini settings: {}", e))
+}
+
+/// Prompts above this size are kept out of argv. Linux caps a single
+/// argument at 128 KiB (`MAX_ARG_STRLEN`), and argv is visible in `ps`.
+pub const ARGV_PROMPT_LIMIT: usize = 64 * 1024;
+
+/// Agent settings shared by headless runs (`spawn_agent`) and interactive
+/// terminals (`spawn_pty`).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AgentOptions<'a> {
+    pub working_directory: Option<&'a str>,
+    pub model: Option<&'a str>,
+    pub system_prompt: Option<&'a str>,
+    pub json_schema: Option<&'a str>,
+    pub no_session_persistence: bool,
+    /// An empty list allows no tools at all.
+    pub allowed_tools: Option<&'a [String]>,
+    pub max_turns: Option<u32>,
+    /// Claude-format MCP config JSON.
+    pub mcp_config: Option<&'a str>,
+}
+
+/// How the Claude session id is passed to the agent.
+#[derive(Debug, Clone, Copy)]
+pub enum SessionArg<'a> {
+    /// Start a new session with this id (`--session-id`).
+    New(&'a str),
+    /// Continue an existing session (`--resume`).
+    Resume(&'a str),
+}
+
+/// Files written once per logical process and referenced by every attempt.
+#[derive(Debug, Default)]
+pub struct PreparedFiles {
+    gemini_system_prompt: Option<PathBuf>,
+    codex_output_schema: Option<PathBuf>,
+    /// Claude or Amp system prompt too large for argv.
+    system_prompt_file: Option<PathBuf>,
+}
+
+/// Write the temp and working-directory files the agent reads its system
+/// prompt, schema and MCP config from.
+pub fn prepare_files(
+    opts: &AgentOptions,
+    agent: Agent,
+    temp_ctx: &mut TempContext,
+) -> Result<PreparedFiles, String> {
+    let mut prepared = PreparedFiles::default();
+
+    // System prompt — file-based mechanisms
+    if let Some(system_prompt) = opts.system_prompt {
+        match agent {
+            Agent::Codex => {
+                // Write AGENTS.md in the working directory for Codex to pick up
+                if let Some(dir) = opts.working_directory {
+                    temp_ctx.write_workdir_file(
+                        Path::new(dir),
+                        "AGENTS.md",
+                        system_prompt,
+                    )?;
+                }
+            }
+            Agent::Gemini => {
+                // Write system prompt to temp file for GEMINI_SYSTEM_MD
+                let path = temp_ctx.write_temp_file("system_prompt.md", system_prompt)?;
+                prepared.gemini_system_prompt = Some(path);
+            }
+            Agent::Claude | Agent::Amp if system_prompt.len() > ARGV_PROMPT_LIMIT => {
+                let path = temp_ctx.write_temp_file("append_system_prompt.md", system_prompt)?;
+                prepared.system_prompt_file = Some(path);
+            }
+            _ => {}
+        }
+    }
+
+    // JSON schema — Codex reads it from a file
+    if let Some(schema) = opts.json_schema {
+        if agent == Agent::Codex {
+            // Codex (OpenAI) requires additionalProperties:false on every
+            // object in the schema.  Patch it before writing the temp file.
+            let patched = {
+                let mut v: serde_json::Value = serde_json::from_str(schema)
+                    .map_err(|e| format!("Invalid JSON schema: {}", e))?;
+                patch_schema_for_openai(&mut v);
+                serde_json::to_string(&v)
+                    .map_err(|e| format!("Failed to serialize patched schema: {}", e))?
+            };
+            let path = temp_ctx.write_temp_file("output_schema.json", &patched)?;
+            prepared.codex_output_schema = Some(path);
+        }
+    }
+
+    // MCP config — file-based mechanisms
+    if let Some(mcp_config) = opts.mcp_config {
+        match agent {
+            Agent::Codex => {
+                // Write .codex/config.toml in the working directory
+                if let Some(dir) = opts.working_directory {
+                    let toml = convert_mcp_json_to_codex_toml(mcp_config)?;
+                    temp_ctx.write_workdir_file(
+                        Path::new(dir),
+                        ".codex/config.toml",
+                        &toml,
+                    )?;
+                }
+            }
+            Agent::Gemini => {
+                // Write .gemini/settings.json in the working directory
+                if let Some(dir) = opts.working_directory {
+                    let settings = convert_mcp_json_to_gemini_settings(mcp_config)?;
+                    temp_ctx.write_workdir_file(
+                        Path::new(dir),
+                        ".gemini/settings.json",
+                        &settings,
+                    )?;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(prepared)
+}
+
+/// Arguments and environment for the options both spawn paths share:
+/// model, session, system prompt, schema, tools, turns and MCP config.
+#[derive(Debug, Default)]
+pub struct AgentFlags {
+    pub args: Vec<OsString>,
+    pub env: Vec<(&'static str, OsString)>,
+}
+
+impl AgentFlags {
+    pub fn new(
+        opts: &AgentOptions,
+        agent: Agent,
+        prepared: &PreparedFiles,
+        session: Option<SessionArg>,
+    ) -> Self {
+        let mut flags = AgentFlags::default();
+
+        // Model override (persona_model)
+        if let Some(model) = opts.model {
+            match agent {
+                Agent::Claude | Agent::Codex | Agent::Gemini | Agent::Amp => {
+                    flags.arg("--model", model);
+                }
+                Agent::OpenCode => {}
+            }
+        }
+
+        // Session ID (Claude only)
+        if agent.supports_session_id() {
+            match session {
+                Some(SessionArg::New(id)) => flags.arg("--session-id", id),
+                Some(SessionArg::Resume(id)) => flags.arg("--resume", id),
+                None => {}
+            }
+        }
+
+        // System prompt — flag or env var; file-based agents were handled in
+        // `prepare_files`.
+        if let Some(ref path) = prepared.system_prompt_file {
+            if agent == Agent::Claude {
+                flags.arg("--append-system-prompt-file", path);
+            } else {
+                // Amp has no file flag, so it is pointed at the file instead.
+                let pointer = format!(
+                    "Further instructions are in the file {}. Read it before you start and follow them.",
+                    path.display()
+                );
+                flags.arg("--append-system-prompt", pointer);
+            }
+        } else if let Some(system_prompt) = opts.system_prompt {
+            if matches!(agent, Agent::Claude | Agent::Amp) {
+                flags.arg("--append-system-prompt", system_prompt);
+            }
+        }
+
+        // JSON schema
+        if let Some(schema) = opts.json_schema {
+            if agent == Agent::Claude {
+                flags.arg("--json-schema", schema);
+            }
+        }
+        if let Some(ref path) = prepared.codex_output_schema {
+            flags.arg("--output-schema", path);
+        }
+
+        // No session persistence (Claude only)
+        if agent.supports_no_session_persistence() && opts.no_session_persistence {
+            flags.args.push("--no-session-persistence".into());
+        }
+
+        // Allowed tools (Claude only)
+        if agent.supports_allowed_tools() {
+            if let Some(tools) = opts.allowed_tools {
+                if tools.is_empty() {
+                    // An empty list means "no tools at all" — pass a non-existent tool
+                    // name so the CLI restricts to zero real tools.
+                    flags.arg("--allowedTools", "_none_");
+                } else {
+                    for tool in tools {
+                        flags.arg("--allowedTools", tool);
+                    }
+                }
+            }
+        }
+
+        // Max turns (Claude only)
+        if agent.supports_max_turns() {
+            if let Some(max_turns) = opts.max_turns {
+                flags.arg("--max-turns", max_turns.to_string());
+            }
+        }
+
+        // MCP config — Claude takes it inline; Codex and Gemini read the files
+        // written by `prepare_files`.
+        if let Some(mcp_config) = opts.mcp_config {
+            if agent == Agent::Claude {
+                flags.arg("--mcp-config", mcp_config);
+            }
+        }
+
+        if let Some(ref path) = prepared.gemini_system_prompt {
+            flags.env.push(("GEMINI_SYSTEM_MD", path.into()));
+        }
+
+        flags
+    }
+
+    fn arg(&mut self, flag: &str, value: impl AsRef<OsStr>) {
+        self.args.push(flag.into());
+        self.args.push(value.as_ref().to_owned());
+    }
+
+    pub fn apply_to_command(&self, cmd: &mut tokio::process::Command) {
+        cmd.args(&self.args);
+        cmd.envs(self.env.iter().map(|(k, v)| (*k, v)));
+    }
+
+    pub fn apply_to_builder(&self, cmd: &mut portable_pty::CommandBuilder) {
+        cmd.args(&self.args);
+        for (k, v) in &self.env {
+            cmd.env(k, v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags_for(agent: Agent, system_prompt: &str) -> (AgentFlags, PreparedFiles, TempContext) {
+        let mut ctx = TempContext::new(&format!("test-{}", uuid::Uuid::new_v4())).unwrap();
+        let opts = AgentOptions {
+            system_prompt: Some(system_prompt),
+            ..Default::default()
+        };
+        let prepared = prepare_files(&opts, agent, &mut ctx).unwrap();
+        (AgentFlags::new(&opts, agent, &prepared, None), prepared, ctx)
+    }
+
+    #[test]
+    fn large_system_prompts_go_through_a_file() {
+        let big = "s".repeat(4 << 20);
+        for agent in [Agent::Claude, Agent::Amp] {
+            let (flags, prepared, ctx) = flags_for(agent, &big);
+            assert!(flags.args.iter().all(|a| a.len() < 1024), "{:?} put the prompt in argv", agent);
+            let file = prepared.system_prompt_file.clone().unwrap();
+            assert_eq!(std::fs::read_to_string(&file).unwrap(), big);
+            assert!(flags.args.iter().any(|a| a.to_string_lossy().contains(&*file.to_string_lossy())));
+            ctx.cleanup();
+        }
+    }
+
+    #[test]
+    fn small_system_prompts_stay_in_argv() {
+        for agent in [Agent::Claude, Agent::Amp] {
+            let (flags, _, ctx) = flags_for(agent, "Be brief.");
+            assert_eq!(flags.args, vec![OsString::from("--append-system-prompt"), "Be brief.".into()]);
+            ctx.cleanup();
+        }
+    }
+}
@@ -67,4 +67,16 @@ impl Agent {
     pub fn supports_verbose(&self) -> bool {
         matches!(self, Agent::Claude)
     }
+
+    /// Whether this agent reads its prompt from stdin in headless mode
+    /// (`claude -p`, `codex exec -`).
+    pub fn supports_stdin_prompt(&self) -> bool {
+        matches!(self, Agent::Claude | Agent::Codex)
+    }
+
+    /// Whether this agent accepts follow-up user messages on stdin
+    /// (`--input-format stream-json`).
+    pub fn supports_stream_input(&self) -> bool {
+        matches!(self, Agent::Claude)
+    }
 }
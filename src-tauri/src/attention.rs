@@ -0,0 +1,276 @@
+use crate::store;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use tokio::sync::Mutex;
+
+/// A screen condition that raises `PtyEvent::Attention`, such as an agent
+/// asking for permission. Checked against the visible screen once output
+/// has settled.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttentionMatcher {
+    /// Sent as the event's `reason`, e.g. `permission_prompt`.
+    pub reason: String,
+    /// Only match sessions running this agent.
+    pub agent: Option<String>,
+    /// Regex the screen text must match; `^` and `$` match at row
+    /// boundaries.
+    pub pattern: Option<String>,
+    /// Text the screen must contain.
+    pub contains: Option<String>,
+    /// Regex the screen must not match, such as a "working" indicator.
+    pub unless: Option<String>,
+    /// Only look at the last this many non-blank rows.
+    pub last_rows: Option<u16>,
+}
+
+/// Matchers used until the user saves their own.
+pub fn default_matchers() -> Vec<AttentionMatcher> {
+    vec![
+        AttentionMatcher {
+            reason: "permission_prompt".to_string(),
+            pattern: Some(
+                r"(?i)do you want to (proceed|make this edit|create|run)|allow (this )?command\?|\[y/n\]"
+                    .to_string(),
+            ),
+            last_rows: Some(20),
+            ..AttentionMatcher::default()
+        },
+        AttentionMatcher {
+            reason: "turn_finished".to_string(),
+            agent: Some("claude".to_string()),
+            contains: Some("? for shortcuts".to_string()),
+            unless: Some(r"(?i)esc to interrupt".to_string()),
+            last_rows: Some(6),
+            ..AttentionMatcher::default()
+        },
+    ]
+}
+
+pub struct CompiledMatcher {
+    reason: String,
+    agent: Option<String>,
+    pattern: Option<Regex>,
+    contains: Option<String>,
+    unless: Option<Regex>,
+    last_rows: Option<u16>,
+}
+
+fn regex(pattern: &Option<String>) -> Result<Option<Regex>, String> {
+    pattern
+        .as_deref()
+        .map(|p| {
+            RegexBuilder::new(p)
+                .multi_line(true)
+                .build()
+                .map_err(|e| format!("Invalid pattern {:?}: {}", p, e))
+        })
+        .transpose()
+}
+
+impl CompiledMatcher {
+    fn compile(matcher: &AttentionMatcher) -> Result<Self, String> {
+        if matcher.reason.trim().is_empty() {
+            return Err("Attention matcher reason must not be empty".to_string());
+        }
+        if matcher.pattern.is_none() && matcher.contains.is_none() {
+            return Err(format!(
+                "Attention matcher '{}' needs a pattern or text to look for",
+                matcher.reason
+            ));
+        }
+        Ok(CompiledMatcher {
+            reason: matcher.reason.clone(),
+            agent: matcher.agent.clone(),
+            pattern: regex(&matcher.pattern)?,
+            contains: matcher.contains.clone(),
+            unless: regex(&matcher.unless)?,
+            last_rows: matcher.last_rows,
+        })
+    }
+
+    fn matches(&self, agent: Option<&str>, screen: &vt100::Screen) -> bool {
+        if self.agent.is_some() && self.agent.as_deref() != agent {
+            return false;
+        }
+        let text = match self.last_rows {
+            Some(n) => {
+                let (_, cols) = screen.size();
+                let rows: Vec<String> = screen
+                    .rows(0, cols)
+                    .filter(|r| !r.trim().is_empty())
+                    .collect();
+                rows[rows.len().saturating_sub(n as usize)..].join("\n")
+            }
+            None => screen.contents(),
+        };
+        self.pattern.as_ref().map_or(true, |p| p.is_match(&text))
+            && self.contains.as_deref().map_or(true, |c| text.contains(c))
+            && !self.unless.as_ref().is_some_and(|u| u.is_match(&text))
+    }
+}
+
+pub fn compile(matchers: &[AttentionMatcher]) -> Result<Vec<CompiledMatcher>, String> {
+    matchers.iter().map(CompiledMatcher::compile).collect()
+}
+
+/// Which reasons a session's screen currently matches, so each is raised
+/// once when it appears rather than on every check.
+#[derive(Default)]
+pub struct AttentionState {
+    active: HashSet<String>,
+}
+
+impl AttentionState {
+    /// Reasons that match now but didn't at the last check.
+    pub fn check(
+        &mut self,
+        matchers: &[CompiledMatcher],
+        agent: Option<&str>,
+        screen: &vt100::Screen,
+    ) -> Vec<String> {
+        let now: HashSet<String> = matchers
+            .iter()
+            .filter(|m| m.matches(agent, screen))
+            .map(|m| m.reason.clone())
+            .collect();
+        let mut raised: Vec<String> = now.difference(&self.active).cloned().collect();
+        raised.sort();
+        self.active = now;
+        raised
+    }
+}
+
+/// `~/.stagehand/data/attention_matchers.json`, shared by all projects.
+pub struct AttentionStore {
+    path: Option<PathBuf>,
+    lock: Mutex<()>,
+}
+
+impl AttentionStore {
+    pub fn new() -> Self {
+        Self {
+            path: store::data_file("attention_matchers.json"),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// The saved matchers, or the defaults if none have been saved.
+    pub fn load(&self) -> Result<Vec<AttentionMatcher>, String> {
+        let path = self.path.as_deref().ok_or("Could not find home directory")?;
+        let saved: Option<Vec<AttentionMatcher>> = store::read_json(path)?;
+        Ok(saved.unwrap_or_else(default_matchers))
+    }
+
+    pub async fn list(&self) -> Result<Vec<AttentionMatcher>, String> {
+        let _guard = self.lock.lock().await;
+        self.load()
+    }
+
+    /// Replace all matchers, after checking that they compile.
+    pub async fn save(&self, matchers: &[AttentionMatcher]) -> Result<(), String> {
+        compile(matchers)?;
+        let path = self.path.as_deref().ok_or("Could not find home directory")?;
+        let _guard = self.lock.lock().await;
+        store::write_json(path, &matchers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen(text: &str) -> vt100::Parser {
+        let mut parser = vt100::Parser::new(10, 40, 0);
+        parser.process(text.replace('\n', "\r\n").as_bytes());
+        parser
+    }
+
+    fn matcher(reason: &str) -> AttentionMatcher {
+        AttentionMatcher {
+            reason: reason.to_string(),
+            ..AttentionMatcher::default()
+        }
+    }
+
+    #[test]
+    fn compile_rejects_incomplete_matchers() {
+        assert!(compile(&[matcher(" ")]).is_err());
+        assert!(compile(&[matcher("prompt")]).is_err());
+        let bad = AttentionMatcher {
+            pattern: Some("(".to_string()),
+            ..matcher("prompt")
+        };
+        assert!(compile(&[bad]).is_err());
+        assert!(compile(&default_matchers()).is_ok());
+    }
+
+    #[test]
+    fn matches_pattern_contains_and_unless() {
+        let m = CompiledMatcher::compile(&AttentionMatcher {
+            pattern: Some(r"^> $".to_string()),
+            contains: Some("ready".to_string()),
+            unless: Some("(?i)working".to_string()),
+            ..matcher("idle")
+        })
+        .unwrap();
+        assert!(m.matches(None, screen("ready\n> ").screen()));
+        // `^` and `$` match at row boundaries only.
+        assert!(!m.matches(None, screen("ready >").screen()));
+        assert!(!m.matches(None, screen("set\n> ").screen()));
+        assert!(!m.matches(None, screen("ready\n> \nWorking...").screen()));
+    }
+
+    #[test]
+    fn matches_only_the_given_agent() {
+        let m = CompiledMatcher::compile(&AttentionMatcher {
+            agent: Some("claude".to_string()),
+            contains: Some("? for shortcuts".to_string()),
+            ..matcher("turn_finished")
+        })
+        .unwrap();
+        let parser = screen("? for shortcuts");
+        assert!(m.matches(Some("claude"), parser.screen()));
+        assert!(!m.matches(Some("codex"), parser.screen()));
+        assert!(!m.matches(None, parser.screen()));
+    }
+
+    #[test]
+    fn last_rows_skips_blank_rows_and_older_text() {
+        let m = CompiledMatcher::compile(&AttentionMatcher {
+            contains: Some("[y/n]".to_string()),
+            last_rows: Some(2),
+            ..matcher("permission_prompt")
+        })
+        .unwrap();
+        // Trailing blank rows of the screen don't count towards the limit.
+        assert!(m.matches(None, screen("one\nRun it? [y/n]\n\n").screen()));
+        assert!(!m.matches(None, screen("Run it? [y/n]\ntwo\nthree").screen()));
+    }
+
+    #[test]
+    fn check_raises_each_reason_once_while_it_matches() {
+        let matchers = compile(&[
+            AttentionMatcher {
+                contains: Some("[y/n]".to_string()),
+                ..matcher("permission_prompt")
+            },
+            AttentionMatcher {
+                contains: Some("$ ".to_string()),
+                ..matcher("idle")
+            },
+        ])
+        .unwrap();
+        let mut state = AttentionState::default();
+        let both = screen("$ rm -rf build [y/n]");
+        assert_eq!(state.check(&matchers, None, both.screen()), ["idle", "permission_prompt"]);
+        assert!(state.check(&matchers, None, both.screen()).is_empty());
+
+        let answered = screen("$ rm -rf build");
+        assert!(state.check(&matchers, None, answered.screen()).is_empty());
+        // Raised again once it has gone away and come back.
+        assert_eq!(state.check(&matchers, None, both.screen()), ["permission_prompt"]);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::pty_manager::{PtyManager, PtyMeta, PtySpawn};
+use crate::temp_context::{SharedTempContext, TempContext};
+use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+use std::time::{Duration, Instant};
+use tauri::ipc::{Channel, InvokeResponseBody};
+use tokio::sync::mpsc;
+
+/// How long one echo round trip may take before the benchmark gives up.
+const ROUND_TRIP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Bytes of input queued on the stalled session.
+const FLOOD_BYTES: usize = 1 << 20;
+
+/// Echo latencies measured by `pty_round_trips`.
+#[derive(Debug)]
+pub struct RoundTripStats {
+    pub samples: usize,
+    pub p50: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+struct BenchSession {
+    id: String,
+    output: mpsc::UnboundedReceiver<String>,
+    seen: String,
+}
+
+fn spawn_shell(pm: &PtyManager, index: usize) -> Result<BenchSession, String> {
+    let pair = native_pty_system()
+        .openpty(PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 0,
+            pixel_height: 0,
+        })
+        .map_err(|e| format!("Failed to open PTY: {}", e))?;
+    let mut cmd = CommandBuilder::new("sh");
+    cmd.env("PS1", "");
+    let child = pair
+        .slave
+        .spawn_command(cmd)
+        .map_err(|e| format!("Failed to spawn sh: {}", e))?;
+    drop(pair.slave);
+
+    let (tx, output) = mpsc::unbounded_channel();
+    let events = Channel::new(move |body| {
+        if let InvokeResponseBody::Json(json) = body {
+            if let Ok(event) = serde_json::from_str::<serde_json::Value>(&json) {
+                if let Some(data) = event.get("data").and_then(|d| d.as_str()) {
+                    let _ = tx.send(data.to_string());
+                }
+            }
+        }
+        Ok(())
+    });
+
+    let id = format!("bench-{}", index);
+    let temp = SharedTempContext::new(TempContext::new(&id)?);
+    pm.start(
+        id.clone(),
+        PtySpawn {
+            master: pair.master,
+            child,
+            events,
+            temp,
+            meta: PtyMeta::default(),
+            recorder: None,
+        },
+    )?;
+    Ok(BenchSession {
+        id,
+        output,
+        seen: String::new(),
+    })
+}
+
+impl BenchSession {
+    async fn round_trip(&mut self, pm: &PtyManager, marker: usize) -> Result<Duration, String> {
+        // The shell expands the arithmetic, so the terminal's echo of the
+        // typed line doesn't match.
+        let expected = format!("rt{}done", marker);
+        let started = Instant::now();
+        pm.write(&self.id, format!("echo rt$(({}+0))done\n", marker).into_bytes())
+            .await?;
+        while !self.seen.contains(&expected) {
+            let chunk = tokio::time::timeout(ROUND_TRIP_TIMEOUT, self.output.recv())
+                .await
+                .map_err(|_| format!("Timed out waiting for {} on {}", expected, self.id))?
+                .ok_or_else(|| format!("{} exited", self.id))?;
+            self.seen.push_str(&chunk);
+        }
+        self.seen.clear();
+        Ok(started.elapsed())
+    }
+}
+
+/// Spawn `sessions` shells, stall the first one (stopped, with a megabyte
+/// of input queued against it) and time `rounds` echo round trips on each
+/// of the others, all at once.
+pub async fn pty_round_trips(sessions: usize, rounds: usize) -> Result<RoundTripStats, String> {
+    let pm = PtyManager::new();
+    let mut shells = Vec::new();
+    for i in 0..sessions.max(2) {
+        shells.push(spawn_shell(&pm, i)?);
+    }
+    let mut shells = shells.into_iter();
+    let stalled = shells.next().expect("at least two sessions");
+
+    pm.pause(&stalled.id).await?;
+    let flood = {
+        let pm = pm.clone();
+        let id = stalled.id.clone();
+        tokio::spawn(async move { pm.write(&id, vec![b'x'; FLOOD_BYTES]).await })
+    };
+
+    let mut tasks = Vec::new();
+    for mut shell in shells {
+        let pm = pm.clone();
+        tasks.push(tokio::spawn(async move {
+            let mut latencies = Vec::with_capacity(rounds);
+            for round in 0..rounds {
+                latencies.push(shell.round_trip(&pm, round).await?);
+            }
+            let _ = pm.kill(&shell.id).await;
+            Ok::<_, String>(latencies)
+        }));
+    }
+
+    let mut latencies = Vec::new();
+    for task in tasks {
+        latencies.extend(task.await.map_err(|e| e.to_string())??);
+    }
+
+    let _ = pm.kill(&stalled.id).await;
+    flood.abort();
+    pm.shutdown(Duration::from_secs(1), Duration::from_secs(1)).await;
+
+    RoundTripStats::from_latencies(latencies)
+}
+
+impl RoundTripStats {
+    fn from_latencies(mut latencies: Vec<Duration>) -> Result<Self, String> {
+        if latencies.is_empty() {
+            return Err("No round trips were measured".to_string());
+        }
+        latencies.sort();
+        let at = |q: f64| latencies[((latencies.len() - 1) as f64 * q) as usize];
+        Ok(Self {
+            samples: latencies.len(),
+            p50: at(0.5),
+            p99: at(0.99),
+            max: latencies[latencies.len() - 1],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_need_samples() {
+        assert!(RoundTripStats::from_latencies(Vec::new()).is_err());
+
+        let ms = |n| Duration::from_millis(n);
+        let stats = RoundTripStats::from_latencies((1..=100).rev().map(ms).collect()).unwrap();
+        assert_eq!((stats.samples, stats.p50, stats.p99, stats.max), (100, ms(50), ms(99), ms(100)));
+    }
+}
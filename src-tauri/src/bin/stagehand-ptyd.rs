@@ -0,0 +1,10 @@
+// Detached PTY session host. Launched by Stagehand for persistent terminal
+// sessions; owns their PTYs so they survive the app quitting or updating.
+
+fn main() {
+    let runtime = tokio::runtime::Runtime::new().expect("failed to start tokio runtime");
+    if let Err(e) = runtime.block_on(app_lib::ptyd::serve()) {
+        eprintln!("stagehand-ptyd: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,61 @@
+use crate::store;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// A command run in one of a task's terminals.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandRecord {
+    pub session_id: String,
+    pub command: String,
+    pub cwd: Option<String>,
+    /// `None` when the shell didn't report it.
+    pub exit_code: Option<i32>,
+    pub started_at_ms: u64,
+    pub duration_ms: u64,
+}
+
+/// Task ids are uuids; anything else could escape the directory.
+fn history_path(task_id: &str) -> Result<PathBuf, String> {
+    if task_id.is_empty() || !task_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(format!("Invalid task id: {}", task_id));
+    }
+    store::data_file("command_history")
+        .map(|dir| dir.join(format!("{}.jsonl", task_id)))
+        .ok_or_else(|| "Could not determine home directory".to_string())
+}
+
+/// Append to `~/.stagehand/data/command_history/<task id>.jsonl`.
+pub fn append(task_id: &str, record: &CommandRecord) -> Result<(), String> {
+    let path = history_path(task_id)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create dir {:?}: {}", parent, e))?;
+    }
+    let line = serde_json::to_string(record).map_err(|e| e.to_string())?;
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| writeln!(file, "{}", line))
+        .map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+/// A task's commands, oldest first, keeping only the last `limit`.
+pub fn list(task_id: &str, limit: Option<usize>) -> Result<Vec<CommandRecord>, String> {
+    let path = history_path(task_id)?;
+    let raw = match std::fs::read_to_string(&path) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {:?}: {}", path, e)),
+    };
+    let mut records: Vec<CommandRecord> = raw
+        .lines()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect();
+    if let Some(limit) = limit {
+        records.drain(..records.len().saturating_sub(limit));
+    }
+    Ok(records)
+}
@@ -0,0 +1,49 @@
+use crate::env_profiles::{EnvProfile, EnvProfileStore, ResolvedEnv};
+use tauri::State;
+
+#[tauri::command]
+pub async fn list_env_profiles(
+    project_id: String,
+    store: State<'_, EnvProfileStore>,
+) -> Result<Vec<EnvProfile>, String> {
+    store.list(&project_id).await
+}
+
+#[tauri::command]
+pub async fn save_env_profile(
+    project_id: String,
+    profile: EnvProfile,
+    store: State<'_, EnvProfileStore>,
+) -> Result<(), String> {
+    store.upsert(&project_id, profile).await
+}
+
+#[tauri::command]
+pub async fn delete_env_profile(
+    project_id: String,
+    name: String,
+    store: State<'_, EnvProfileStore>,
+) -> Result<(), String> {
+    store.delete(&project_id, &name).await
+}
+
+/// Resolve the profile selected in spawn args, if any.
+pub async fn resolve_for_spawn(
+    store: &EnvProfileStore,
+    project_id: Option<&str>,
+    profile: Option<&str>,
+    working_directory: Option<&str>,
+) -> Result<Option<ResolvedEnv>, String> {
+    let Some(name) = profile else {
+        return Ok(None);
+    };
+    let project_id = project_id.ok_or("envProfile requires projectId")?;
+    let env = store.resolve(project_id, name, working_directory).await?;
+    log::info!(
+        "Applying env profile '{}'{}: {}",
+        env.profile,
+        if env.clear_env { " (clean env)" } else { "" },
+        env.masked()
+    );
+    Ok(Some(env))
+}
@@ -1,3 +1,5 @@
+use crate::git_repo::{self, BranchInfo, CommitInfo, DiffFile, DiffSpec, MergeCheck, RepoStatus, TaskDiff};
+use serde::Deserialize;
 use tokio::process::Command;
 
 async fn run_command(binary: &str, args: Vec<String>, working_directory: String) -> Result<String, String> {
@@ -37,6 +39,122 @@ pub async fn run_gh_command(args: Vec<String>, working_directory: String) -> Res
     run_command("gh", args, working_directory).await
 }
 
+/// Run a libgit2 call off the async runtime; large repositories can take
+/// a while to scan.
+pub(crate) async fn blocking<T: Send + 'static>(f: impl FnOnce() -> Result<T, String> + Send + 'static) -> Result<T, String> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| format!("Git task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn git_status(working_directory: String) -> Result<RepoStatus, String> {
+    blocking(move || git_repo::status(&working_directory)).await
+}
+
+/// Diff `base` (default HEAD) against `head` (default the working tree),
+/// limited to `paths`. `merge_base` diffs from where the two diverged, like
+/// `base...head`.
+#[tauri::command]
+pub async fn git_diff(
+    working_directory: String,
+    base: Option<String>,
+    head: Option<String>,
+    paths: Option<Vec<String>>,
+    merge_base: Option<bool>,
+    include_untracked: Option<bool>,
+    stat_only: Option<bool>,
+) -> Result<Vec<DiffFile>, String> {
+    blocking(move || {
+        let paths = paths.unwrap_or_default();
+        let spec = DiffSpec {
+            base: base.as_deref(),
+            head: head.as_deref(),
+            paths: &paths,
+            merge_base: merge_base.unwrap_or(false),
+            include_untracked: include_untracked.unwrap_or(false),
+            stat_only: stat_only.unwrap_or(false),
+            ..Default::default()
+        };
+        git_repo::diff(&working_directory, spec)
+    })
+    .await
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TaskDiffOptions {
+    pub paths: Vec<String>,
+    /// Include untracked files when diffing the working tree.
+    pub include_untracked: bool,
+    pub ignore_whitespace: bool,
+    pub context_lines: Option<u32>,
+    /// Mark the changed words within paired lines.
+    pub word_diff: bool,
+    /// Files larger than this are returned with stats but no hunks.
+    pub max_file_bytes: Option<u64>,
+    pub stat_only: bool,
+}
+
+/// A task branch's changes since it diverged from `base`. Without `head`
+/// the working tree is compared, uncommitted changes included.
+#[tauri::command]
+pub async fn task_diff(
+    working_directory: String,
+    base: String,
+    head: Option<String>,
+    options: Option<TaskDiffOptions>,
+) -> Result<TaskDiff, String> {
+    blocking(move || {
+        let options = options.unwrap_or_default();
+        let spec = DiffSpec {
+            base: Some(&base),
+            head: head.as_deref(),
+            paths: &options.paths,
+            merge_base: true,
+            include_untracked: options.include_untracked,
+            stat_only: options.stat_only,
+            ignore_whitespace: options.ignore_whitespace,
+            context_lines: options.context_lines,
+            word_diff: options.word_diff,
+            max_file_bytes: options.max_file_bytes,
+        };
+        git_repo::task_diff(&working_directory, spec)
+    })
+    .await
+}
+
+/// Dry-run merging `theirs` into `ours` and report the conflicts, without
+/// touching the working tree or index.
+#[tauri::command]
+pub async fn check_merge_conflicts(
+    working_directory: String,
+    ours: String,
+    theirs: String,
+) -> Result<MergeCheck, String> {
+    blocking(move || git_repo::check_merge(&working_directory, &ours, &theirs)).await
+}
+
+#[tauri::command]
+pub async fn git_log(
+    working_directory: String,
+    range: Option<String>,
+    max_count: Option<usize>,
+) -> Result<Vec<CommitInfo>, String> {
+    blocking(move || git_repo::log(&working_directory, range.as_deref(), max_count)).await
+}
+
+#[tauri::command]
+pub async fn git_branches(working_directory: String) -> Result<Vec<BranchInfo>, String> {
+    blocking(move || git_repo::branches(&working_directory)).await
+}
+
+/// A file's content at `rev`, or `None` if it doesn't exist there.
+#[tauri::command]
+pub async fn git_show_file(working_directory: String, rev: String, path: String) -> Result<Option<String>, String> {
+    blocking(move || git_repo::show_file(&working_directory, &rev, &path)).await
+}
+
 /// Max file size for reading: 10 MB
 const MAX_READ_SIZE: u64 = 10 * 1024 * 1024;
 
@@ -0,0 +1,19 @@
+use crate::hooks::{HookStore, ProjectHooks};
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_project_hooks(
+    project_id: String,
+    store: State<'_, HookStore>,
+) -> Result<ProjectHooks, String> {
+    store.get(&project_id).await
+}
+
+#[tauri::command]
+pub async fn save_project_hooks(
+    project_id: String,
+    hooks: ProjectHooks,
+    store: State<'_, HookStore>,
+) -> Result<(), String> {
+    store.set(&project_id, hooks).await
+}
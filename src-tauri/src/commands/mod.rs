@@ -1,4 +1,9 @@
 pub mod editor;
+pub mod env;
 pub mod git;
+pub mod hooks;
+pub mod overlap;
 pub mod process;
 pub mod pty;
+pub mod recording;
+pub mod worktree;
@@ -0,0 +1,48 @@
+use crate::commands::git::blocking;
+use crate::events::OverlapEvent;
+use crate::task_overlap::{self, ChangesCache, OverlapReport, OverlapWatches, TaskBranch};
+use tauri::ipc::Channel;
+use tauri::State;
+
+/// Files and hunks that `tasks` both change, each compared against its
+/// merge base with `base`, and a suggested merge order.
+#[tauri::command]
+pub async fn task_overlaps(
+    working_directory: String,
+    base: String,
+    tasks: Vec<TaskBranch>,
+) -> Result<OverlapReport, String> {
+    blocking(move || task_overlap::compute(&working_directory, &base, &tasks, &mut ChangesCache::new())).await
+}
+
+/// Keep comparing `tasks` as their branches move, reporting through
+/// `on_event`. Returns a watch id for `update_overlap_watch` and
+/// `stop_overlap_watch`.
+#[tauri::command]
+pub async fn watch_task_overlaps(
+    working_directory: String,
+    base: String,
+    tasks: Vec<TaskBranch>,
+    on_event: Channel<OverlapEvent>,
+    watches: State<'_, OverlapWatches>,
+) -> Result<String, String> {
+    Ok(watches.start(working_directory, base, tasks, on_event))
+}
+
+/// Replace the tasks of a watch, e.g. when one is created or merged.
+#[tauri::command]
+pub async fn update_overlap_watch(
+    watch_id: String,
+    tasks: Vec<TaskBranch>,
+    watches: State<'_, OverlapWatches>,
+) -> Result<(), String> {
+    watches.update(&watch_id, tasks)
+}
+
+#[tauri::command]
+pub async fn stop_overlap_watch(
+    watch_id: String,
+    watches: State<'_, OverlapWatches>,
+) -> Result<(), String> {
+    watches.stop(&watch_id)
+}
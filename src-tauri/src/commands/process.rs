@@ -1,237 +1,25 @@
+use crate::agent_args::{prepare_files, AgentFlags, AgentOptions, PreparedFiles, SessionArg, ARGV_PROMPT_LIMIT};
 use crate::agents::Agent;
-use crate::events::AgentStreamEvent;
-use crate::process_manager::ProcessManager;
+use crate::commands::env::resolve_for_spawn;
+use crate::env_profiles::{EnvProfileStore, ResolvedEnv};
+use crate::commands::pty::{open_session_blocking, SpawnPtyArgs};
+use crate::events::{AgentStreamEvent, PtyEvent};
+use crate::hooks::{self, HookCommand, HookFailurePolicy, HookPhase, HookResult, HookStore, ResolvedHooks};
+use crate::process_manager::{ProcessEntry, ProcessManager, ProcessSummary, ResumeContext};
+use crate::pty_manager::PtyManager;
+use crate::retry::{self, AttemptOutcome, RetryPolicy};
+use crate::signals::{self, Signal};
+use crate::temp_context::{SharedTempContext, TempContext};
 use serde::Deserialize;
-use std::path::{Path, PathBuf};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 use tauri::ipc::Channel;
 use tauri::State;
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Command;
-
-/// Temporary directory context for a spawned agent process.
-/// Creates `~/.stagehand/tmp/<process_id>/` for temp files (system prompt files,
-/// schema files, config files) and tracks working-directory files that need
-/// cleanup after the process exits.
-struct TempContext {
-    /// The per-process temp directory under ~/.stagehand/tmp/
-    dir: PathBuf,
-    /// Files written into the agent's working directory that must be cleaned up.
-    workdir_files: Vec<PathBuf>,
-}
-
-impl TempContext {
-    fn new(process_id: &str) -> Result<Self, String> {
-        let home = dirs::home_dir().ok_or("Could not find home directory")?;
-        let dir = home.join(".stagehand").join("tmp").join(process_id);
-        std::fs::create_dir_all(&dir)
-            .map_err(|e| format!("Failed to create temp dir {:?}: {}", dir, e))?;
-        Ok(Self {
-            dir,
-            workdir_files: Vec::new(),
-        })
-    }
-
-    /// Write a file into the per-process temp directory. Returns the full path.
-    fn write_temp_file(&self, name: &str, content: &str) -> Result<PathBuf, String> {
-        let path = self.dir.join(name);
-        std::fs::write(&path, content)
-            .map_err(|e| format!("Failed to write temp file {:?}: {}", path, e))?;
-        Ok(path)
-    }
-
-    /// Write a file into the working directory and track it for cleanup.
-    fn write_workdir_file(&mut self, workdir: &Path, relative_path: &str, content: &str) -> Result<PathBuf, String> {
-        let path = workdir.join(relative_path);
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)
-                .map_err(|e| format!("Failed to create dir {:?}: {}", parent, e))?;
-        }
-        std::fs::write(&path, content)
-            .map_err(|e| format!("Failed to write workdir file {:?}: {}", path, e))?;
-        self.workdir_files.push(path.clone());
-        Ok(path)
-    }
-
-    /// Clean up all temp files and directories.
-    fn cleanup(self) {
-        // Remove working directory files
-        for path in &self.workdir_files {
-            let _ = std::fs::remove_file(path);
-            // Try to remove parent dir if empty (e.g. .codex/ or .gemini/)
-            if let Some(parent) = path.parent() {
-                let _ = std::fs::remove_dir(parent);
-            }
-        }
-        // Remove the per-process temp directory
-        let _ = std::fs::remove_dir_all(&self.dir);
-    }
-}
-
-/// Patch a JSON Schema so it conforms to OpenAI's strict structured-output rules:
-///   1. Every object must have `"additionalProperties": false`.
-///   2. Every property defined in `properties` must appear in `required`.
-///      Properties that were previously optional are made nullable instead
-///      (type becomes `["<original>", "null"]`).
-///
-/// This lets the canonical stage templates stay agent-agnostic while Codex
-/// gets a compliant schema at the boundary.
-fn patch_schema_for_openai(value: &mut serde_json::Value) {
-    if let Some(obj) = value.as_object_mut() {
-        if obj.get("type").and_then(|v| v.as_str()) == Some("object") {
-            // 1. additionalProperties: false
-            obj.entry("additionalProperties")
-                .or_insert(serde_json::Value::Bool(false));
-
-            // 2. Ensure every key in `properties` is in `required`.
-            //    For newly-required keys, make the property nullable so the
-            //    model can still omit the value by sending null.
-            if let Some(props) = obj.get("properties").and_then(|p| p.as_object()).cloned() {
-                let all_keys: Vec<String> = props.keys().cloned().collect();
-
-                let existing_required: std::collections::HashSet<String> = obj
-                    .get("required")
-                    .and_then(|r| r.as_array())
-                    .map(|arr| {
-                        arr.iter()
-                            .filter_map(|v| v.as_str().map(String::from))
-                            .collect()
-                    })
-                    .unwrap_or_default();
-
-                // Make previously-optional properties nullable
-                if let Some(props_mut) = obj.get_mut("properties").and_then(|p| p.as_object_mut()) {
-                    for key in &all_keys {
-                        if !existing_required.contains(key) {
-                            if let Some(prop) = props_mut.get_mut(key).and_then(|p| p.as_object_mut()) {
-                                if let Some(ty) = prop.get("type").cloned() {
-                                    if ty.is_string() {
-                                        prop.insert(
-                                            "type".to_string(),
-                                            serde_json::json!([ty.as_str().unwrap(), "null"]),
-                                        );
-                                    }
-                                    // If already an array type, append "null" if missing
-                                    else if let Some(arr) = ty.as_array() {
-                                        if !arr.iter().any(|v| v.as_str() == Some("null")) {
-                                            let mut new_arr = arr.clone();
-                                            new_arr.push(serde_json::json!("null"));
-                                            prop.insert("type".to_string(), serde_json::Value::Array(new_arr));
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-
-                // Set required to ALL keys
-                let required_arr: Vec<serde_json::Value> = all_keys
-                    .into_iter()
-                    .map(serde_json::Value::String)
-                    .collect();
-                obj.insert("required".to_string(), serde_json::Value::Array(required_arr));
-            }
-        }
-
-        // Recurse into all sub-values
-        for (_, v) in obj.iter_mut() {
-            patch_schema_for_openai(v);
-        }
-    } else if let Some(arr) = value.as_array_mut() {
-        for v in arr.iter_mut() {
-            patch_schema_for_openai(v);
-        }
-    }
-}
-
-/// Convert Claude-format MCP config JSON to Codex `.codex/config.toml` format.
-///
-/// Input (Claude format):
-/// ```json
-/// {"mcpServers":{"name":{"command":"node","args":["path"],"env":{"K":"V"}}}}
-/// ```
-///
-/// Output (Codex TOML):
-/// ```toml
-/// [mcp_servers.name]
-/// command = "node"
-/// args = ["path"]
-/// env = { K = "V" }
-/// ```
-fn convert_mcp_json_to_codex_toml(mcp_json: &str) -> Result<String, String> {
-    let parsed: serde_json::Value =
-        serde_json::from_str(mcp_json).map_err(|e| format!("Invalid MCP JSON: {}", e))?;
-
-    let servers = parsed
-        .get("mcpServers")
-        .and_then(|v| v.as_object())
-        .ok_or("MCP JSON missing mcpServers object")?;
-
-    let mut toml = String::new();
-    for (name, config) in servers {
-        // Codex expects [mcp_servers.<name>] tables, NOT [[mcp_servers]] arrays.
-        toml.push_str(&format!("[mcp_servers.{:?}]\n", name));
-
-        if let Some(command) = config.get("command").and_then(|v| v.as_str()) {
-            toml.push_str(&format!("command = {:?}\n", command));
-        }
-
-        if let Some(args) = config.get("args").and_then(|v| v.as_array()) {
-            let args_str: Vec<String> = args
-                .iter()
-                .filter_map(|a| a.as_str())
-                .map(|a| format!("{:?}", a))
-                .collect();
-            toml.push_str(&format!("args = [{}]\n", args_str.join(", ")));
-        }
-
-        if let Some(env) = config.get("env").and_then(|v| v.as_object()) {
-            if !env.is_empty() {
-                let pairs: Vec<String> = env
-                    .iter()
-                    .map(|(k, v)| {
-                        let val = v.as_str().unwrap_or("");
-                        format!("{} = {:?}", k, val)
-                    })
-                    .collect();
-                toml.push_str(&format!("env = {{ {} }}\n", pairs.join(", ")));
-            }
-        }
-
-        toml.push('\n');
-    }
-
-    Ok(toml)
-}
-
-/// Convert Claude-format MCP config JSON to Gemini `.gemini/settings.json` format.
-///
-/// Input (Claude format):
-/// ```json
-/// {"mcpServers":{"name":{"command":"node","args":["path"],"env":{"K":"V"}}}}
-/// ```
-///
-/// Output (Gemini settings.json):
-/// ```json
-/// {"mcpServers":{"name":{"command":"node","args":["path"],"env":{"K":"V"}}}}
-/// ```
-///
-/// Gemini uses the same format as Claude, so this is essentially a pass-through
-/// but we validate and re-serialize to ensure correctness.
-fn convert_mcp_json_to_gemini_settings(mcp_json: &str) -> Result<String, String> {
-    let parsed: serde_json::Value =
-        serde_json::from_str(mcp_json).map_err(|e| format!("Invalid MCP JSON: {}", e))?;
-
-    // Verify structure
-    parsed
-        .get("mcpServers")
-        .and_then(|v| v.as_object())
-        .ok_or("MCP JSON missing mcpServers object")?;
-
-    // Gemini uses the same mcpServers format
-    serde_json::to_string_pretty(&parsed)
-        .map_err(|e| format!("Failed to serialize Gemini settings: {}", e))
-}
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::oneshot;
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -249,25 +37,103 @@ pub struct SpawnAgentArgs {
     pub allowed_tools: Option<Vec<String>>,
     pub max_turns: Option<u32>,
     pub mcp_config: Option<String>,
+    /// Keep stdin open for follow-up messages (`--input-format stream-json`).
+    /// The prompt is sent as the first message and the agent keeps running
+    /// until `close_agent_input` is called or the process is killed.
+    pub stream_input: Option<bool>,
+    /// Retry transient failures under the same process id.
+    pub retry: Option<RetryPolicy>,
+    /// How to pass the prompt; defaults to `auto`.
+    pub prompt_delivery: Option<PromptDelivery>,
+    pub project_id: Option<String>,
+    /// Name of the project environment profile to apply.
+    pub env_profile: Option<String>,
+    /// Stage template id, used to pick stage-specific lifecycle hooks.
+    pub stage_id: Option<String>,
 }
 
-#[tauri::command]
-pub async fn spawn_agent(
-    args: SpawnAgentArgs,
-    on_event: Channel<AgentStreamEvent>,
-    process_manager: State<'_, ProcessManager>,
-) -> Result<String, String> {
-    let process_id = uuid::Uuid::new_v4().to_string();
+impl SpawnAgentArgs {
+    fn agent_options(&self) -> AgentOptions<'_> {
+        AgentOptions {
+            working_directory: self.working_directory.as_deref(),
+            model: self.persona_model.as_deref(),
+            system_prompt: self.append_system_prompt.as_deref(),
+            json_schema: self.json_schema.as_deref(),
+            no_session_persistence: self.no_session_persistence.unwrap_or(false),
+            allowed_tools: self.allowed_tools.as_deref(),
+            max_turns: self.max_turns,
+            mcp_config: self.mcp_config.as_deref(),
+        }
+    }
+}
 
-    let agent = args
-        .agent
-        .as_deref()
-        .and_then(Agent::from_str_opt)
-        .unwrap_or(Agent::Claude);
+/// How long a run being taken over gets to exit on its own before it is
+/// killed, and how long its cleanup may then take.
+const TAKEOVER_GRACE: Duration = Duration::from_secs(3);
+const TAKEOVER_BOUND: Duration = Duration::from_secs(2);
 
-    // Create temp context for this process
-    let mut temp_ctx = TempContext::new(&process_id)?;
+/// Prompt for a retried attempt that resumes the interrupted Claude session.
+const RETRY_CONTINUE_PROMPT: &str =
+    "The previous run was interrupted by a transient error. Continue where you left off.";
 
+/// Build a stream-json user-message envelope as accepted by
+/// `claude --input-format stream-json`.
+fn user_message_envelope(text: &str) -> String {
+    serde_json::json!({
+        "type": "user",
+        "message": {
+            "role": "user",
+            "content": [{ "type": "text", "text": text }],
+        },
+    })
+    .to_string()
+}
+
+/// How the prompt reaches the agent process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptDelivery {
+    /// Argv for small prompts, otherwise stdin or a temp file.
+    #[default]
+    Auto,
+    Argv,
+    /// Claude `-p` and Codex `exec -` read the prompt from stdin.
+    Stdin,
+    /// Write the prompt to a temp file and point the agent at it.
+    File,
+}
+
+impl PromptDelivery {
+    /// Pick a concrete delivery (never `Auto`) for a prompt.
+    fn resolve(self, agent: Agent, prompt_len: usize) -> PromptDelivery {
+        match self {
+            PromptDelivery::Auto if prompt_len <= ARGV_PROMPT_LIMIT => PromptDelivery::Argv,
+            PromptDelivery::Auto | PromptDelivery::Stdin if agent.supports_stdin_prompt() => {
+                PromptDelivery::Stdin
+            }
+            PromptDelivery::Auto | PromptDelivery::Stdin => PromptDelivery::File,
+            other => other,
+        }
+    }
+}
+
+/// The prompt as passed to a single attempt.
+enum PromptArg<'a> {
+    Argv(&'a str),
+    Stdin,
+    File(&'a Path),
+}
+
+/// Build the command for a single attempt.
+fn build_command(
+    args: &SpawnAgentArgs,
+    agent: Agent,
+    prepared: &PreparedFiles,
+    env: Option<&ResolvedEnv>,
+    prompt: PromptArg,
+    session: Option<SessionArg>,
+    stream_input: bool,
+) -> Command {
     let mut cmd = if agent == Agent::Codex {
         let mut c = Command::new(agent.binary());
         c.arg("exec");
@@ -276,17 +142,42 @@ pub async fn spawn_agent(
         Command::new(agent.binary())
     };
 
+    if let Some(env) = env {
+        env.apply_to_command(&mut cmd);
+    }
+
     // Auto-approve flag
     if let Some(flag) = agent.auto_approve_flag() {
         cmd.arg(flag);
     }
 
-    // Prompt — Codex takes the prompt as a positional argument;
-    // other agents use the `-p` flag.
-    if agent == Agent::Codex {
-        cmd.arg(&args.prompt);
+    // Prompt — Codex takes the prompt as a positional argument (`-` for
+    // stdin); other agents use the `-p` flag. With streaming input the
+    // prompt is written to stdin as the first message instead.
+    if stream_input {
+        cmd.arg("-p").arg("--input-format").arg("stream-json");
     } else {
-        cmd.arg("-p").arg(&args.prompt);
+        let file_prompt;
+        let text = match prompt {
+            PromptArg::Argv(text) => Some(text),
+            PromptArg::Stdin => None,
+            PromptArg::File(path) => {
+                file_prompt = format!(
+                    "Your full instructions are in the file {}. Read it and carry them out.",
+                    path.display()
+                );
+                Some(file_prompt.as_str())
+            }
+        };
+        match (agent, text) {
+            (Agent::Codex, Some(text)) => cmd.arg(text),
+            (Agent::Codex, None) => cmd.arg("-"),
+            (_, Some(text)) => cmd.arg("-p").arg(text),
+            (_, None) => cmd.arg("-p"),
+        };
+        if matches!(prompt, PromptArg::Stdin) {
+            cmd.stdin(std::process::Stdio::piped());
+        }
     }
 
     // Output format
@@ -309,212 +200,551 @@ pub async fn spawn_agent(
         }
     }
 
-    // Model override (persona_model)
-    if let Some(ref model) = args.persona_model {
-        match agent {
-            Agent::Claude | Agent::Codex | Agent::Gemini | Agent::Amp => {
-                cmd.arg("--model").arg(model);
-            }
-            _ => {}
-        }
+    AgentFlags::new(&args.agent_options(), agent, prepared, session).apply_to_command(&mut cmd);
+
+    if let Some(ref dir) = args.working_directory {
+        cmd.current_dir(dir);
     }
 
-    // Session ID (Claude only)
-    if agent.supports_session_id() {
-        if let Some(ref session_id) = args.session_id {
-            cmd.arg("--session-id").arg(session_id);
+    cmd.stdout(std::process::Stdio::piped());
+    cmd.stderr(std::process::Stdio::piped());
+    if stream_input {
+        cmd.stdin(std::process::Stdio::piped());
+    }
+
+    // Run the agent in its own process group so pause/resume reach the
+    // tools it spawns as well.
+    #[cfg(unix)]
+    cmd.process_group(0);
+
+    cmd
+}
+
+type SharedStdin = Arc<tokio::sync::Mutex<ChildStdin>>;
+
+/// Spawn one attempt. Prompts delivered over stdin are written from a
+/// background task so a multi-megabyte prompt can't stall the caller; with
+/// streaming input the prompt becomes the first message and stdin stays open.
+async fn spawn_attempt(
+    mut cmd: Command,
+    agent: Agent,
+    prompt: &str,
+    delivery: PromptDelivery,
+    stream_input: bool,
+) -> Result<(Child, Option<SharedStdin>), String> {
+    let mut child = cmd.spawn().map_err(|e| format!("Failed to spawn {}: {}", agent.binary(), e))?;
+
+    let payload = if stream_input {
+        format!("{}\n", user_message_envelope(prompt))
+    } else if delivery == PromptDelivery::Stdin {
+        prompt.to_string()
+    } else {
+        return Ok((child, None));
+    };
+
+    let stdin: SharedStdin = Arc::new(tokio::sync::Mutex::new(
+        child.stdin.take().ok_or("stdin not piped")?,
+    ));
+    // Take the lock before returning so follow-up messages queue behind
+    // the prompt.
+    let mut guard = stdin.clone().lock_owned().await;
+    let binary = agent.binary().to_string();
+    tokio::spawn(async move {
+        if let Err(e) = guard.write_all(payload.as_bytes()).await {
+            log::warn!("Failed to send prompt to {}: {}", binary, e);
         }
+        let _ = guard.flush().await;
+    });
+
+    // A plain stdin prompt ends with EOF once the writer task drops its
+    // handle; only streaming input keeps stdin around for later messages.
+    Ok((child, stream_input.then_some(stdin)))
+}
+
+/// Number of trailing stderr lines and error events kept for failure
+/// classification.
+const TAIL_LINES: usize = 20;
+
+fn push_tail(tail: &mut VecDeque<String>, line: &str) {
+    if tail.len() == TAIL_LINES {
+        tail.pop_front();
     }
+    tail.push_back(line.to_string());
+}
 
-    // System prompt — per-agent mechanism
-    if let Some(ref system_prompt) = args.append_system_prompt {
-        match agent {
-            Agent::Claude | Agent::Amp => {
-                cmd.arg("--append-system-prompt").arg(system_prompt);
-            }
-            Agent::Codex => {
-                // Write AGENTS.md in the working directory for Codex to pick up
-                if let Some(ref dir) = args.working_directory {
-                    temp_ctx.write_workdir_file(
-                        Path::new(dir),
-                        "AGENTS.md",
-                        system_prompt,
-                    )?;
+/// Stream an attempt's output until it exits. Returns `None` when the
+/// process was killed on request.
+async fn run_attempt(
+    child: &mut Child,
+    on_event: &Channel<AgentStreamEvent>,
+    kill_rx: &mut oneshot::Receiver<()>,
+) -> Option<AttemptOutcome> {
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+
+    let stdout_event = on_event.clone();
+    let stdout_task = tokio::spawn(async move {
+        let mut count = 0;
+        let mut errors = VecDeque::new();
+        if let Some(stdout) = stdout {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                count += 1;
+                if let Some(error) = retry::structured_error(&line) {
+                    push_tail(&mut errors, &error);
                 }
+                let _ = stdout_event.send(AgentStreamEvent::StdoutLine { line });
             }
-            Agent::Gemini => {
-                // Write system prompt to temp file and set GEMINI_SYSTEM_MD env var
-                let path = temp_ctx.write_temp_file("system_prompt.md", system_prompt)?;
-                cmd.env("GEMINI_SYSTEM_MD", &path);
+        }
+        (count, errors)
+    });
+
+    let stderr_event = on_event.clone();
+    let stderr_task = tokio::spawn(async move {
+        let mut tail = VecDeque::new();
+        if let Some(stderr) = stderr {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                push_tail(&mut tail, &line);
+                let _ = stderr_event.send(AgentStreamEvent::StderrLine { line });
             }
-            Agent::OpenCode => {
-                // No system prompt support
+        }
+        tail
+    });
+
+    let exit = tokio::select! {
+        status = child.wait() => {
+            Some(status.ok().and_then(|s| s.code()))
+        }
+        _ = kill_rx => {
+            // Take the tools the agent started down with it.
+            if let Some(pid) = child.id() {
+                let _ = signals::signal_group(pid, Signal::Kill);
             }
+            let _ = child.kill().await;
+            None
         }
-    }
+    };
+
+    let (stdout_lines, stdout_errors) = stdout_task.await.unwrap_or_default();
+    let stderr_tail = stderr_task.await.unwrap_or_default();
+
+    exit.map(|exit_code| AttemptOutcome {
+        exit_code,
+        stdout_lines,
+        tail: stdout_errors.into_iter().chain(stderr_tail).collect(),
+    })
+}
+
+/// Everything the background task needs to drive one logical agent run:
+/// pre-run hooks, attempts with retries, and post-run hooks with feedback.
+struct AgentRun {
+    process_id: String,
+    args: SpawnAgentArgs,
+    agent: Agent,
+    temp_ctx: SharedTempContext,
+    prepared: PreparedFiles,
+    env: Option<ResolvedEnv>,
+    hooks: ResolvedHooks,
+    policy: RetryPolicy,
+    stream_input: bool,
+    on_event: Channel<AgentStreamEvent>,
+    pm: ProcessManager,
+}
 
-    // JSON schema — per-agent mechanism
-    if let Some(ref schema) = args.json_schema {
-        match agent {
-            Agent::Claude => {
-                cmd.arg("--json-schema").arg(schema);
+impl AgentRun {
+    async fn spawn(&self, prompt: &str, session: Option<SessionArg<'_>>) -> Result<(Child, Option<SharedStdin>), String> {
+        let delivery = self
+            .args
+            .prompt_delivery
+            .unwrap_or_default()
+            .resolve(self.agent, prompt.len());
+        let prompt_file;
+        let prompt_arg = match delivery {
+            PromptDelivery::Stdin => PromptArg::Stdin,
+            PromptDelivery::File => {
+                prompt_file = self.temp_ctx.write_temp_file("prompt.md", prompt)?;
+                PromptArg::File(&prompt_file)
             }
-            Agent::Codex => {
-                // Codex (OpenAI) requires additionalProperties:false on every
-                // object in the schema.  Patch it before writing the temp file.
-                let patched = {
-                    let mut v: serde_json::Value = serde_json::from_str(schema)
-                        .map_err(|e| format!("Invalid JSON schema: {}", e))?;
-                    patch_schema_for_openai(&mut v);
-                    serde_json::to_string(&v)
-                        .map_err(|e| format!("Failed to serialize patched schema: {}", e))?
-                };
-                let path = temp_ctx.write_temp_file("output_schema.json", &patched)?;
-                cmd.arg("--output-schema").arg(&path);
+            _ => PromptArg::Argv(prompt),
+        };
+        let cmd = build_command(
+            &self.args,
+            self.agent,
+            &self.prepared,
+            self.env.as_ref(),
+            prompt_arg,
+            session,
+            self.stream_input,
+        );
+        spawn_attempt(cmd, self.agent, prompt, delivery, self.stream_input).await
+    }
+
+    /// Spawn a follow-up attempt and point the process entry at it. Reports
+    /// spawn failures as an `error` event.
+    async fn respawn(&self, prompt: &str, session: Option<SessionArg<'_>>) -> Option<Child> {
+        match self.spawn(prompt, session).await {
+            Ok((child, stdin)) => {
+                self.pm.replace_attempt(&self.process_id, child.id(), stdin).await;
+                Some(child)
             }
-            _ => {
-                // Other agents don't support JSON schema via CLI flag
+            Err(message) => {
+                self.error(message);
+                None
             }
         }
     }
 
-    // No session persistence (Claude only)
-    if agent.supports_no_session_persistence() && args.no_session_persistence.unwrap_or(false) {
-        cmd.arg("--no-session-persistence");
+    /// Session argument for the next attempt. Once an attempt has produced
+    /// output the Claude session exists, so it is continued instead of
+    /// started again.
+    fn session(&self, started: bool) -> Option<SessionArg<'_>> {
+        let id = self.args.session_id.as_deref()?;
+        if started && self.agent.supports_session_id() {
+            Some(SessionArg::Resume(id))
+        } else {
+            Some(SessionArg::New(id))
+        }
     }
 
-    // Allowed tools (Claude only)
-    if agent.supports_allowed_tools() {
-        if let Some(ref tools) = args.allowed_tools {
-            if tools.is_empty() {
-                // An empty list means "no tools at all" — pass a non-existent tool
-                // name so the CLI restricts to zero real tools.
-                cmd.arg("--allowedTools").arg("_none_");
-            } else {
-                for tool in tools {
-                    cmd.arg("--allowedTools").arg(tool);
-                }
-            }
-        }
+    /// Whether the run should end after the current step: the app is
+    /// shutting down or the run is being taken over.
+    async fn stopping(&self) -> bool {
+        self.pm.is_shutting_down() || self.pm.is_handed_over(&self.process_id).await
     }
 
-    // Max turns (Claude only)
-    if agent.supports_max_turns() {
-        if let Some(max_turns) = args.max_turns {
-            cmd.arg("--max-turns").arg(max_turns.to_string());
-        }
+    fn error(&self, message: String) {
+        let _ = self.on_event.send(AgentStreamEvent::Error {
+            process_id: self.process_id.clone(),
+            message,
+        });
     }
 
-    // MCP config — per-agent mechanism
-    if let Some(ref mcp_config) = args.mcp_config {
-        match agent {
-            Agent::Claude => {
-                cmd.arg("--mcp-config").arg(mcp_config);
-            }
-            Agent::Codex => {
-                // Write .codex/config.toml in the working directory
-                if let Some(ref dir) = args.working_directory {
-                    let toml = convert_mcp_json_to_codex_toml(mcp_config)?;
-                    temp_ctx.write_workdir_file(
-                        Path::new(dir),
-                        ".codex/config.toml",
-                        &toml,
-                    )?;
+    /// Run a hook, with its process group recorded on the entry so pausing
+    /// the run stops the hook.
+    async fn hook(
+        &self,
+        hook: &HookCommand,
+        phase: HookPhase,
+        kill_rx: &mut oneshot::Receiver<()>,
+    ) -> Option<HookResult> {
+        let child = match hooks::spawn_hook(hook, self.args.working_directory.as_deref(), self.env.as_ref()) {
+            Ok(child) => child,
+            Err(output) => return Some(HookResult { exit_code: None, output }),
+        };
+        self.pm.set_hook_group(&self.process_id, child.id()).await;
+        let result = hooks::run_hook(hook, phase, &self.process_id, child, &self.on_event, kill_rx, &self.pm).await;
+        self.pm.set_hook_group(&self.process_id, None).await;
+        result
+    }
+
+    /// Drive the run to completion and return the exit code to report.
+    /// `first` is the already-spawned first attempt when there are no
+    /// pre-run hooks.
+    async fn supervise(
+        &self,
+        first: Option<Child>,
+        kill_rx: &mut oneshot::Receiver<()>,
+    ) -> Option<i32> {
+        let mut prompt = self.args.prompt.clone();
+        let mut session_started = false;
+
+        let mut child = match first {
+            Some(child) => child,
+            None => {
+                for hook in &self.hooks.pre {
+                    let result = self.hook(hook, HookPhase::Pre, kill_rx).await?;
+                    if result.success() {
+                        continue;
+                    }
+                    match hook.on_failure {
+                        HookFailurePolicy::Abort => {
+                            self.error(format!("Pre-run hook `{}` failed", hook.command));
+                            return Some(result.exit_code.unwrap_or(1));
+                        }
+                        HookFailurePolicy::Warn => {}
+                        HookFailurePolicy::Feedback => {
+                            prompt = format!(
+                                "{}\n\n{}",
+                                hook_feedback(&[(hook, &result)], "before you started"),
+                                prompt
+                            );
+                        }
+                    }
                 }
+                if self.stopping().await {
+                    return None;
+                }
+                self.respawn(&prompt, self.session(false)).await?
+            }
+        };
+
+        let mut feedback_rounds = 0;
+        loop {
+            let exit_code = self
+                .run_with_retries(child, &prompt, &mut session_started, kill_rx)
+                .await?;
+            if exit_code != Some(0) || self.hooks.post.is_empty() || self.stopping().await {
+                return exit_code;
             }
-            Agent::Gemini => {
-                // Write .gemini/settings.json in the working directory
-                if let Some(ref dir) = args.working_directory {
-                    let settings = convert_mcp_json_to_gemini_settings(mcp_config)?;
-                    temp_ctx.write_workdir_file(
-                        Path::new(dir),
-                        ".gemini/settings.json",
-                        &settings,
-                    )?;
+
+            let mut failed = Vec::new();
+            for hook in &self.hooks.post {
+                let result = self.hook(hook, HookPhase::Post, kill_rx).await?;
+                if result.success() {
+                    continue;
+                }
+                match hook.on_failure {
+                    HookFailurePolicy::Abort => {
+                        self.error(format!("Post-run hook `{}` failed", hook.command));
+                        return Some(result.exit_code.unwrap_or(1));
+                    }
+                    HookFailurePolicy::Warn => {}
+                    HookFailurePolicy::Feedback => failed.push((hook, result)),
                 }
             }
-            _ => {
-                // Amp, OpenCode: no MCP config support
+            if failed.is_empty() {
+                return exit_code;
             }
+            if feedback_rounds >= self.hooks.max_feedback_rounds {
+                self.error(format!(
+                    "Post-run hooks still failing after {} feedback round(s)",
+                    feedback_rounds
+                ));
+                return Some(1);
+            }
+            feedback_rounds += 1;
+
+            let refs: Vec<_> = failed.iter().map(|(h, r)| (*h, r)).collect();
+            let feedback = hook_feedback(&refs, "after you finished");
+            // Agents without a resumable session get the original task
+            // again, with the feedback appended.
+            prompt = if session_started && self.agent.supports_session_id() && self.args.session_id.is_some() {
+                feedback
+            } else {
+                format!("{}\n\n{}", self.args.prompt, feedback)
+            };
+            child = self.respawn(&prompt, self.session(session_started)).await?;
         }
     }
 
-    if let Some(ref dir) = args.working_directory {
-        cmd.current_dir(dir);
+    /// Run an attempt, retrying transient failures per the retry policy.
+    /// Returns `None` when killed.
+    async fn run_with_retries(
+        &self,
+        mut child: Child,
+        prompt: &str,
+        session_started: &mut bool,
+        kill_rx: &mut oneshot::Receiver<()>,
+    ) -> Option<Option<i32>> {
+        let mut attempt = 1;
+        loop {
+            let outcome = run_attempt(&mut child, &self.on_event, kill_rx).await?;
+            *session_started |= outcome.stdout_lines > 0;
+            // An agent that exited because the app is shutting down or the
+            // run was taken over looks like a crash, but must not be
+            // started again.
+            let stopping = self.stopping().await;
+            let kind = match retry::classify(&outcome) {
+                Some(kind) if self.policy.should_retry(attempt, kind) && !stopping => kind,
+                _ => return Some(outcome.exit_code),
+            };
+
+            let delay = self.policy.delay_after(attempt);
+            attempt += 1;
+            let _ = self.on_event.send(AgentStreamEvent::Retrying {
+                process_id: self.process_id.clone(),
+                attempt,
+                reason: kind,
+                delay_ms: delay.as_millis() as u64,
+            });
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = &mut *kill_rx => return None,
+            }
+
+            let resuming = *session_started && self.agent.supports_session_id() && self.args.session_id.is_some();
+            let prompt = if resuming { RETRY_CONTINUE_PROMPT } else { prompt };
+            child = self.respawn(prompt, self.session(*session_started)).await?;
+        }
     }
+}
 
-    cmd.stdout(std::process::Stdio::piped());
-    cmd.stderr(std::process::Stdio::piped());
+/// Describe failed hooks for the agent.
+fn hook_feedback(failed: &[(&HookCommand, &HookResult)], when: &str) -> String {
+    let mut out = String::from("The following commands failed when run ");
+    out.push_str(when);
+    out.push_str(". Fix the problems they report.\n");
+    for (hook, result) in failed {
+        out.push_str(&format!(
+            "\n$ {}\n(exit code {})\n```\n{}\n```\n",
+            hook.command,
+            result.exit_code.map_or("none".to_string(), |c| c.to_string()),
+            result.output
+        ));
+    }
+    out
+}
 
-    let mut child = cmd.spawn().map_err(|e| format!("Failed to spawn {}: {}", agent.binary(), e))?;
+#[tauri::command]
+pub async fn spawn_agent(
+    args: SpawnAgentArgs,
+    on_event: Channel<AgentStreamEvent>,
+    process_manager: State<'_, ProcessManager>,
+    env_store: State<'_, EnvProfileStore>,
+    hook_store: State<'_, HookStore>,
+) -> Result<String, String> {
+    if process_manager.is_shutting_down() {
+        return Err("Stagehand is shutting down".to_string());
+    }
+
+    let process_id = uuid::Uuid::new_v4().to_string();
 
-    let (kill_tx, kill_rx) = tokio::sync::oneshot::channel::<()>();
+    let agent = args
+        .agent
+        .as_deref()
+        .and_then(Agent::from_str_opt)
+        .unwrap_or(Agent::Claude);
+
+    let stream_input = args.stream_input.unwrap_or(false);
+    if stream_input {
+        if !agent.supports_stream_input() {
+            return Err(format!(
+                "Streaming input is unsupported for {}",
+                agent.binary()
+            ));
+        }
+        if args.output_format.as_deref().unwrap_or("stream-json") != "stream-json" {
+            return Err("Streaming input requires stream-json output format".to_string());
+        }
+    }
+
+    let policy = args.retry.clone().unwrap_or_default();
+
+    let env = resolve_for_spawn(
+        &env_store,
+        args.project_id.as_deref(),
+        args.env_profile.as_deref(),
+        args.working_directory.as_deref(),
+    )
+    .await?;
+
+    let hooks = match args.project_id.as_deref() {
+        Some(project_id) => hook_store.resolve(project_id, args.stage_id.as_deref()).await?,
+        None => ResolvedHooks::default(),
+    };
+
+    // Create temp context for this process. Layering files into the
+    // working directory waits on the backups lock and may run git.
+    let name = process_id.clone();
+    let (args, prepared) = tokio::task::spawn_blocking(move || {
+        let prepared = TempContext::new(&name).and_then(|mut temp_ctx| {
+            match prepare_files(&args.agent_options(), agent, &mut temp_ctx) {
+                Ok(p) => Ok((temp_ctx, p)),
+                Err(e) => {
+                    temp_ctx.cleanup();
+                    Err(e)
+                }
+            }
+        });
+        (args, prepared)
+    })
+    .await
+    .map_err(|e| format!("Failed to prepare agent files: {}", e))?;
+    let (temp_ctx, prepared) = prepared?;
+    let temp_ctx = SharedTempContext::new(temp_ctx);
+
+    let session_id = args.session_id.clone();
+    let stage_execution_id = args.stage_execution_id.clone();
+    let run = AgentRun {
+        process_id: process_id.clone(),
+        args,
+        agent,
+        temp_ctx,
+        prepared,
+        env,
+        hooks,
+        policy,
+        stream_input,
+        on_event: on_event.clone(),
+        pm: process_manager.inner().clone(),
+    };
+
+    // Without pre-run hooks the first attempt is spawned here so spawn
+    // errors reach the caller directly; otherwise it starts once the hooks
+    // have run in the background.
+    let (first, stdin) = if run.hooks.pre.is_empty() {
+        match run.spawn(&run.args.prompt, run.session(false)).await {
+            Ok((child, stdin)) => (Some(child), stdin),
+            Err(e) => {
+                run.temp_ctx.cleanup_blocking().await;
+                return Err(e);
+            }
+        }
+    } else {
+        (None, None)
+    };
+
+    let (kill_tx, mut kill_rx) = oneshot::channel::<()>();
 
     process_manager
         .register(
             process_id.clone(),
-            kill_tx,
-            args.stage_execution_id.clone(),
-            args.session_id.clone(),
+            ProcessEntry {
+                agent,
+                pid: first.as_ref().and_then(|c| c.id()),
+                hook_pgid: None,
+                events: on_event.clone(),
+                kill_tx: Some(kill_tx),
+                stage_execution_id,
+                session_id: session_id.clone(),
+                stdin,
+                input_closed: false,
+                paused_since: None,
+                paused_total: Duration::ZERO,
+                resumed_at_ms: None,
+                temp: run.temp_ctx.clone(),
+                resume: ResumeContext {
+                    working_directory: run.args.working_directory.clone(),
+                    persona_model: run.args.persona_model.clone(),
+                    append_system_prompt: run.args.append_system_prompt.clone(),
+                    mcp_config: run.args.mcp_config.clone(),
+                    project_id: run.args.project_id.clone(),
+                    env_profile: run.args.env_profile.clone(),
+                    allowed_tools: run.args.allowed_tools.clone(),
+                    max_turns: run.args.max_turns,
+                    no_session_persistence: run.args.no_session_persistence.unwrap_or(false),
+                },
+                handed_over_to: None,
+            },
         )
         .await;
 
     let _ = on_event.send(AgentStreamEvent::Started {
         process_id: process_id.clone(),
-        session_id: args.session_id.clone(),
+        session_id,
     });
 
-    let stdout = child.stdout.take().ok_or("stdout not piped")?;
-    let stderr = child.stderr.take().ok_or("stderr not piped")?;
-
-    let stdout_event = on_event.clone();
-    let stdout_task = tokio::spawn(async move {
-        let reader = BufReader::new(stdout);
-        let mut lines = reader.lines();
-        while let Ok(Some(line)) = lines.next_line().await {
-            let _ = stdout_event.send(AgentStreamEvent::StdoutLine { line });
-        }
-    });
-
-    let stderr_event = on_event.clone();
-    let stderr_task = tokio::spawn(async move {
-        let reader = BufReader::new(stderr);
-        let mut lines = reader.lines();
-        while let Ok(Some(line)) = lines.next_line().await {
-            let _ = stderr_event.send(AgentStreamEvent::StderrLine { line });
-        }
-    });
-
-    let pid = process_id.clone();
-    let completion_event = on_event.clone();
-    let pm = process_manager.inner().clone();
-
     tokio::spawn(async move {
-        let exit_code = tokio::select! {
-            status = child.wait() => {
-                match status {
-                    Ok(s) => s.code(),
-                    Err(_) => None,
-                }
-            }
-            _ = kill_rx => {
-                let _ = child.kill().await;
-                None
-            }
-        };
+        let exit_code = run.supervise(first, &mut kill_rx).await;
 
-        let _ = stdout_task.await;
-        let _ = stderr_task.await;
+        let AgentRun {
+            process_id,
+            temp_ctx,
+            on_event,
+            pm,
+            ..
+        } = run;
 
         // Clean up temp files after process exits
-        temp_ctx.cleanup();
+        temp_ctx.cleanup_blocking().await;
 
-        let _ = completion_event.send(AgentStreamEvent::Completed {
-            process_id: pid.clone(),
+        let _ = on_event.send(AgentStreamEvent::Completed {
+            process_id: process_id.clone(),
             exit_code,
         });
 
-        pm.remove(&pid).await;
+        pm.remove(&process_id).await;
     });
 
     Ok(process_id)
@@ -528,33 +758,125 @@ pub async fn kill_process(
     process_manager.kill(&process_id).await
 }
 
+/// Send a follow-up user message to a running agent started with `streamInput`.
 #[tauri::command]
-pub async fn list_processes(
+pub async fn send_agent_message(
+    process_id: String,
+    text: String,
     process_manager: State<'_, ProcessManager>,
-) -> Result<Vec<String>, String> {
-    Ok(process_manager.list_running().await)
+) -> Result<(), String> {
+    process_manager
+        .write_line(&process_id, &user_message_envelope(&text))
+        .await
+}
+
+/// Close a running agent's input so it exits after its current turn.
+#[tauri::command]
+pub async fn close_agent_input(
+    process_id: String,
+    process_manager: State<'_, ProcessManager>,
+) -> Result<(), String> {
+    process_manager.close_input(&process_id).await
 }
 
-#[derive(serde::Serialize)]
+/// Stop a running agent (and everything it spawned) without killing it.
+#[tauri::command]
+pub async fn pause_process(
+    process_id: String,
+    process_manager: State<'_, ProcessManager>,
+) -> Result<(), String> {
+    process_manager.pause(&process_id).await
+}
+
+#[tauri::command]
+pub async fn resume_process(
+    process_id: String,
+    process_manager: State<'_, ProcessManager>,
+) -> Result<(), String> {
+    process_manager.resume(&process_id).await
+}
+
+/// The terminal a run is taken over into.
+#[derive(Debug, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct ProcessInfo {
-    pub process_id: String,
-    pub stage_execution_id: Option<String>,
+pub struct TakeoverArgs {
+    pub task_id: Option<String>,
+    pub cols: Option<u16>,
+    pub rows: Option<u16>,
+}
+
+/// Stop a headless Claude run and continue its session by hand in an
+/// interactive terminal in the same working directory. Returns the PTY
+/// session id.
+#[tauri::command]
+pub async fn takeover_process(
+    process_id: String,
+    args: Option<TakeoverArgs>,
+    on_event: Channel<PtyEvent>,
+    process_manager: State<'_, ProcessManager>,
+    pty_manager: State<'_, PtyManager>,
+    env_store: State<'_, EnvProfileStore>,
+) -> Result<String, String> {
+    if pty_manager.is_shutting_down() {
+        return Err("Stagehand is shutting down".to_string());
+    }
+
+    let pty_id = uuid::Uuid::new_v4().to_string();
+    let handover = process_manager.begin_handover(&process_id, &pty_id).await?;
+    process_manager
+        .wait_for_exit(&process_id, TAKEOVER_GRACE, TAKEOVER_BOUND)
+        .await?;
+
+    let terminal = args.unwrap_or_default();
+    let resume = handover.resume;
+    let args = SpawnPtyArgs {
+        agent: Some(handover.agent.binary().to_string()),
+        session_id: Some(handover.session_id),
+        resume: true,
+        persona_model: resume.persona_model,
+        append_system_prompt: resume.append_system_prompt,
+        mcp_config: resume.mcp_config,
+        working_directory: resume.working_directory,
+        project_id: resume.project_id,
+        env_profile: resume.env_profile,
+        allowed_tools: resume.allowed_tools,
+        max_turns: resume.max_turns,
+        task_id: terminal.task_id,
+        cols: terminal.cols,
+        rows: terminal.rows,
+        ..SpawnPtyArgs::default()
+    };
+    let env = resolve_for_spawn(
+        &env_store,
+        args.project_id.as_deref(),
+        args.env_profile.as_deref(),
+        args.working_directory.as_deref(),
+    )
+    .await?;
+
+    let mut spawn = open_session_blocking(pty_id.clone(), args, env, on_event).await?;
+    spawn.meta.taken_over_from = Some(process_id);
+    spawn.meta.stage_execution_id = handover.stage_execution_id;
+    let temp_ctx = spawn.temp.clone();
+    if let Err(e) = pty_manager.start(pty_id.clone(), spawn) {
+        temp_ctx.cleanup_blocking().await;
+        return Err(e);
+    }
+    Ok(pty_id)
+}
+
+#[tauri::command]
+pub async fn list_processes(
+    process_manager: State<'_, ProcessManager>,
+) -> Result<Vec<String>, String> {
+    Ok(process_manager.list_running().await)
 }
 
 #[tauri::command]
 pub async fn list_processes_detailed(
     process_manager: State<'_, ProcessManager>,
-) -> Result<Vec<ProcessInfo>, String> {
-    Ok(process_manager
-        .list_running_detailed()
-        .await
-        .into_iter()
-        .map(|(process_id, stage_execution_id)| ProcessInfo {
-            process_id,
-            stage_execution_id,
-        })
-        .collect())
+) -> Result<Vec<ProcessSummary>, String> {
+    Ok(process_manager.list_running_detailed().await)
 }
 
 #[tauri::command]
@@ -577,3 +899,149 @@ pub async fn check_agent_available(agent: Option<String>) -> Result<String, Stri
         Err(format!("{} CLI returned error", resolved.binary()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(prompt: &str) -> SpawnAgentArgs {
+        serde_json::from_value(serde_json::json!({ "prompt": prompt })).unwrap()
+    }
+
+    fn argv(cmd: &Command) -> Vec<String> {
+        cmd.as_std().get_args().map(|a| a.to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn auto_delivery_switches_at_the_argv_limit() {
+        let auto = PromptDelivery::Auto;
+        assert_eq!(auto.resolve(Agent::Claude, ARGV_PROMPT_LIMIT), PromptDelivery::Argv);
+        assert_eq!(auto.resolve(Agent::Claude, ARGV_PROMPT_LIMIT + 1), PromptDelivery::Stdin);
+        assert_eq!(auto.resolve(Agent::Codex, 5 << 20), PromptDelivery::Stdin);
+        assert_eq!(auto.resolve(Agent::Gemini, 5 << 20), PromptDelivery::File);
+        assert_eq!(auto.resolve(Agent::Amp, 5 << 20), PromptDelivery::File);
+        // Explicit choices are kept, except stdin for agents that can't read it.
+        assert_eq!(PromptDelivery::Argv.resolve(Agent::Claude, 5 << 20), PromptDelivery::Argv);
+        assert_eq!(PromptDelivery::File.resolve(Agent::Claude, 1), PromptDelivery::File);
+        assert_eq!(PromptDelivery::Stdin.resolve(Agent::Amp, 1), PromptDelivery::File);
+    }
+
+    #[test]
+    fn large_prompts_stay_out_of_argv() {
+        let big = "x".repeat(5 << 20);
+        let a = args(&big);
+        let codex = build_command(&a, Agent::Codex, &PreparedFiles::default(), None, PromptArg::Stdin, None, false);
+        assert_eq!(argv(&codex), vec!["exec", "--dangerously-bypass-approvals-and-sandbox", "-", "--json"]);
+        let claude = build_command(&a, Agent::Claude, &PreparedFiles::default(), None, PromptArg::Stdin, None, false);
+        assert!(argv(&claude).iter().all(|a| a.len() < 1024), "{:?}", argv(&claude));
+
+        let ctx = TempContext::new(&format!("test-{}", uuid::Uuid::new_v4())).unwrap();
+        let path = ctx.write_temp_file("prompt.md", &big).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), big);
+        let gemini = build_command(&a, Agent::Gemini, &PreparedFiles::default(), None, PromptArg::File(&path), None, false);
+        let gemini = argv(&gemini);
+        assert!(gemini.iter().all(|a| a.len() < 1024));
+        assert!(gemini.iter().any(|a| a.contains(&*path.to_string_lossy())));
+        ctx.cleanup();
+    }
+
+    #[tokio::test]
+    async fn large_prompts_arrive_whole_over_stdin() {
+        let big = "y".repeat(8 << 20);
+        let mut cmd = Command::new("wc");
+        cmd.arg("-c");
+        cmd.stdin(std::process::Stdio::piped());
+        cmd.stdout(std::process::Stdio::piped());
+        let (child, stdin) = spawn_attempt(cmd, Agent::Claude, &big, PromptDelivery::Stdin, false)
+            .await
+            .unwrap();
+        // The handle is dropped after the write, so the agent sees EOF.
+        assert!(stdin.is_none());
+        let out = child.wait_with_output().await.unwrap();
+        assert_eq!(String::from_utf8_lossy(&out.stdout).trim(), big.len().to_string());
+    }
+
+    #[tokio::test]
+    async fn follow_up_messages_queue_behind_a_large_first_message() {
+        let mut cmd = Command::new("cat");
+        cmd.stdin(std::process::Stdio::piped());
+        cmd.stdout(std::process::Stdio::piped());
+        let big = "z".repeat(3 << 20);
+        let (child, stdin) = spawn_attempt(cmd, Agent::Claude, &big, PromptDelivery::Argv, true)
+            .await
+            .unwrap();
+        let stdin = stdin.unwrap();
+        let out = tokio::spawn(child.wait_with_output());
+        stdin.lock().await.write_all(b"second\n").await.unwrap();
+        drop(stdin);
+        let out = out.await.unwrap().unwrap();
+        let text = String::from_utf8_lossy(&out.stdout);
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], user_message_envelope(&big));
+        assert_eq!(lines[1], "second");
+    }
+
+    #[test]
+    fn user_messages_use_the_stream_json_envelope() {
+        let line = user_message_envelope("fix the \"tests\"\nplease");
+        assert!(!line.contains('\n'));
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "type": "user",
+                "message": {
+                    "role": "user",
+                    "content": [{ "type": "text", "text": "fix the \"tests\"\nplease" }],
+                },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn messages_are_refused_once_input_is_closed() {
+        let mut cmd = Command::new("cat");
+        cmd.stdin(std::process::Stdio::piped());
+        cmd.stdout(std::process::Stdio::piped());
+        let mut child = cmd.spawn().unwrap();
+        let stdin = Arc::new(tokio::sync::Mutex::new(child.stdin.take().unwrap()));
+        let (kill_tx, _kill_rx) = oneshot::channel();
+        let temp = TempContext::new(&format!("test-{}", uuid::Uuid::new_v4())).unwrap();
+        let temp = SharedTempContext::new(temp);
+        let pm = ProcessManager::new();
+        let entry = ProcessEntry {
+            agent: Agent::Claude,
+            pid: child.id(),
+            hook_pgid: None,
+            events: Channel::new(|_| Ok(())),
+            kill_tx: Some(kill_tx),
+            stage_execution_id: None,
+            session_id: None,
+            stdin: Some(stdin),
+            input_closed: false,
+            paused_since: None,
+            paused_total: Duration::ZERO,
+            resumed_at_ms: None,
+            temp: temp.clone(),
+            resume: ResumeContext::default(),
+            handed_over_to: None,
+        };
+        pm.register("p".to_string(), entry).await;
+
+        // What `send_agent_message` and `close_agent_input` do.
+        pm.write_line("p", &user_message_envelope("hello")).await.unwrap();
+        pm.close_input("p").await.unwrap();
+        assert_eq!(
+            pm.write_line("p", &user_message_envelope("again")).await.unwrap_err(),
+            "Process input is closed"
+        );
+        assert_eq!(pm.close_input("p").await.unwrap_err(), "Process has no open input");
+
+        // The agent saw the first message and then EOF.
+        let out = child.wait_with_output().await.unwrap();
+        let expected = format!("{}\n", user_message_envelope("hello"));
+        assert_eq!(String::from_utf8_lossy(&out.stdout), expected);
+        temp.cleanup();
+    }
+}
@@ -1,65 +1,234 @@
+use crate::agent_args::{prepare_files, AgentFlags, AgentOptions, SessionArg};
 use crate::agents::Agent;
+use crate::attention::{AttentionMatcher, AttentionStore};
+use crate::command_history::{self, CommandRecord};
+use crate::commands::env::resolve_for_spawn;
+use crate::env_profiles::{EnvProfileStore, ResolvedEnv};
 use crate::events::PtyEvent;
-use crate::pty_manager::{PtyEntry, PtyManager};
+use crate::pty_manager::{ForegroundProcess, PtyManager, PtyMeta, PtySpawn, PtySummary, ScreenSnapshot};
+use crate::ptyd;
+use crate::recording::Recorder;
+use crate::shell_integration::{self, Shell};
+use crate::signals::Signal;
+use crate::temp_context::{SharedTempContext, TempContext};
+use crate::terminal_profiles::{TerminalProfile, TerminalProfileStore};
 use portable_pty::{CommandBuilder, PtySize, native_pty_system};
-use serde::Deserialize;
-use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
 use tauri::ipc::Channel;
 use tauri::State;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SpawnPtyArgs {
     pub agent: Option<String>,
+    /// Program to run instead of an agent or the user's shell, such as
+    /// `cargo`, `npm` or `psql`.
+    pub command: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Variables set on top of the environment profile, if any.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Start the shell as a login shell, or run `command` through one so it
+    /// sees the user's login environment.
+    #[serde(default)]
+    pub login_shell: bool,
+    /// Saved terminal profile to take `command`, `args`, `env` and
+    /// `login_shell` from; fields set here win.
+    pub terminal_profile: Option<String>,
+    /// Load the bash, zsh or fish integration snippet into a shell session,
+    /// so it reports command boundaries, exit codes and its cwd.
+    #[serde(default)]
+    pub shell_integration: bool,
     pub persona_model: Option<String>,
     pub working_directory: Option<String>,
     pub append_system_prompt: Option<String>,
+    /// Claude-format MCP config JSON, as for `spawn_agent`.
+    pub mcp_config: Option<String>,
+    /// Claude session to start, or to continue when `resume` is set.
+    pub session_id: Option<String>,
+    /// Continue `session_id` (`--resume`), such as a headless stage's
+    /// conversation, instead of starting it.
+    #[serde(default)]
+    pub resume: bool,
+    /// An empty list allows no tools at all.
+    pub allowed_tools: Option<Vec<String>>,
+    pub max_turns: Option<u32>,
     pub cols: Option<u16>,
     pub rows: Option<u16>,
+    pub project_id: Option<String>,
+    /// Name of the project environment profile to apply.
+    pub env_profile: Option<String>,
+    /// Task the session belongs to, so the frontend can find it again with
+    /// `list_ptys` after a reload.
+    pub task_id: Option<String>,
+    /// Run the session in the `stagehand-ptyd` host so it outlives the app.
+    #[serde(default)]
+    pub persistent: bool,
+    /// Send output as exact bytes (base64 `output_bytes` events) rather than
+    /// decoded text, for sixel or kitty graphics.
+    #[serde(default)]
+    pub binary: bool,
+    /// Record the session as an asciicast v2 file; see `list_recordings`.
+    #[serde(default)]
+    pub record: bool,
 }
 
-/// Track files written into the working directory for PTY sessions, cleaned up on exit.
-struct PtyTempFiles {
-    workdir_files: Vec<PathBuf>,
-    /// Temp dir under ~/.stagehand/tmp/ for agent-specific temp files
-    temp_dir: Option<PathBuf>,
-}
+#[tauri::command]
+pub async fn spawn_pty(
+    args: SpawnPtyArgs,
+    on_event: Channel<PtyEvent>,
+    pty_manager: State<'_, PtyManager>,
+    env_store: State<'_, EnvProfileStore>,
+    terminal_profiles: State<'_, TerminalProfileStore>,
+) -> Result<String, String> {
+    if pty_manager.is_shutting_down() {
+        return Err("Stagehand is shutting down".to_string());
+    }
+
+    let mut args = args;
+    if let Some(name) = args.terminal_profile.clone() {
+        apply_profile(&mut args, terminal_profiles.get(&name).await?);
+    }
+    if args.agent.is_some() && args.command.is_some() {
+        return Err("Pass either agent or command, not both".to_string());
+    }
+
+    let session_id = uuid::Uuid::new_v4().to_string();
+
+    let env = resolve_for_spawn(
+        &env_store,
+        args.project_id.as_deref(),
+        args.env_profile.as_deref(),
+        args.working_directory.as_deref(),
+    )
+    .await?;
+
+    if args.persistent {
+        pty_manager
+            .start_persistent(session_id.clone(), args, env, on_event)
+            .await?;
+        return Ok(session_id);
+    }
 
-impl PtyTempFiles {
-    fn new() -> Self {
-        Self { workdir_files: Vec::new(), temp_dir: None }
+    let spawn = open_session_blocking(session_id.clone(), args, env, on_event).await?;
+    let temp_ctx = spawn.temp.clone();
+    if let Err(e) = pty_manager.start(session_id.clone(), spawn) {
+        temp_ctx.cleanup_blocking().await;
+        return Err(e);
     }
 
-    fn ensure_temp_dir(&mut self, session_id: &str) -> Result<PathBuf, String> {
-        if let Some(ref dir) = self.temp_dir {
-            return Ok(dir.clone());
+    Ok(session_id)
+}
+
+impl SpawnPtyArgs {
+    fn agent_options(&self) -> AgentOptions<'_> {
+        AgentOptions {
+            working_directory: self.working_directory.as_deref(),
+            model: self.persona_model.as_deref(),
+            system_prompt: self.append_system_prompt.as_deref(),
+            allowed_tools: self.allowed_tools.as_deref(),
+            max_turns: self.max_turns,
+            mcp_config: self.mcp_config.as_deref(),
+            ..AgentOptions::default()
         }
-        let home = dirs::home_dir().ok_or("Could not find home directory")?;
-        let dir = home.join(".stagehand").join("tmp").join(format!("pty-{}", session_id));
-        std::fs::create_dir_all(&dir)
-            .map_err(|e| format!("Failed to create temp dir {:?}: {}", dir, e))?;
-        self.temp_dir = Some(dir.clone());
-        Ok(dir)
     }
 
-    fn cleanup(self) {
-        for path in &self.workdir_files {
-            let _ = std::fs::remove_file(path);
+    fn session(&self) -> Option<SessionArg<'_>> {
+        let id = self.session_id.as_deref()?;
+        Some(if self.resume {
+            SessionArg::Resume(id)
+        } else {
+            SessionArg::New(id)
+        })
+    }
+}
+
+/// Fill in what `args` leaves unset from a terminal profile.
+fn apply_profile(args: &mut SpawnPtyArgs, profile: TerminalProfile) {
+    if args.command.is_none() {
+        args.command = profile.command;
+        if args.args.is_empty() {
+            args.args = profile.args;
         }
-        if let Some(ref dir) = self.temp_dir {
-            let _ = std::fs::remove_dir_all(dir);
+    }
+    args.login_shell |= profile.login_shell;
+    for (key, value) in profile.env {
+        args.env.entry(key).or_insert(value);
+    }
+    if let Some(term) = profile.term {
+        args.env.entry("TERM".to_string()).or_insert(term);
+    }
+}
+
+/// The user's shell: `$SHELL`, then their passwd entry, then `/bin/sh`.
+pub fn default_shell() -> String {
+    if let Some(shell) = std::env::var("SHELL").ok().filter(|s| !s.is_empty()) {
+        return shell;
+    }
+    #[cfg(unix)]
+    {
+        // SAFETY: getpwuid returns a pointer into static storage or null;
+        // the shell is copied out before anything else can overwrite it.
+        let shell = unsafe {
+            let pw = libc::getpwuid(libc::getuid());
+            if pw.is_null() || (*pw).pw_shell.is_null() {
+                None
+            } else {
+                std::ffi::CStr::from_ptr((*pw).pw_shell)
+                    .to_str()
+                    .ok()
+                    .map(str::to_string)
+            }
+        };
+        if let Some(shell) = shell.filter(|s| !s.is_empty() && Path::new(s).exists()) {
+            return shell;
         }
     }
+    "/bin/sh".to_string()
 }
 
-#[tauri::command]
-pub async fn spawn_pty(
+/// Argv running `command` from `shell` as a login shell. The shell execs the
+/// command with its arguments passed through untouched, so nothing needs
+/// quoting. fish has no `$0` or `$@` and gets them all in `$argv`; shells we
+/// don't recognise may lack both, so `/bin/sh` stands in for them.
+fn login_shell_argv(shell: &str, command: &str, args: &[String]) -> Vec<String> {
+    let (shell, script) = match Shell::from_path(shell) {
+        Some(Shell::Fish) => (shell, "exec $argv"),
+        Some(Shell::Bash | Shell::Zsh) => (shell, "exec \"$0\" \"$@\""),
+        None => ("/bin/sh", "exec \"$0\" \"$@\""),
+    };
+    let mut argv: Vec<String> = [shell, "-l", "-c", script, command]
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    argv.extend(args.iter().cloned());
+    argv
+}
+
+/// `open_session` for async callers: layering config files into the
+/// working directory waits on the backups lock and may run git.
+pub async fn open_session_blocking(
+    session_id: String,
     args: SpawnPtyArgs,
-    on_event: Channel<PtyEvent>,
-    pty_manager: State<'_, PtyManager>,
-) -> Result<String, String> {
-    let session_id = uuid::Uuid::new_v4().to_string();
+    env: Option<ResolvedEnv>,
+    events: Channel<PtyEvent>,
+) -> Result<PtySpawn, String> {
+    tokio::task::spawn_blocking(move || open_session(&session_id, &args, env.as_ref(), events))
+        .await
+        .map_err(|e| format!("Failed to open PTY: {}", e))?
+}
 
+/// Open a PTY and spawn the agent, command or shell described by `args` in it. Used
+/// by `spawn_pty` and by the `stagehand-ptyd` host for persistent sessions.
+pub fn open_session(
+    session_id: &str,
+    args: &SpawnPtyArgs,
+    env: Option<&ResolvedEnv>,
+    events: Channel<PtyEvent>,
+) -> Result<PtySpawn, String> {
     let agent = args
         .agent
         .as_deref()
@@ -68,8 +237,6 @@ pub async fn spawn_pty(
     let cols = args.cols.unwrap_or(120);
     let rows = args.rows.unwrap_or(24);
 
-    let mut temp_files = PtyTempFiles::new();
-
     let pty_system = native_pty_system();
     let pair = pty_system
         .openpty(PtySize {
@@ -80,171 +247,127 @@ pub async fn spawn_pty(
         })
         .map_err(|e| format!("Failed to open PTY: {}", e))?;
 
-    let label: &str = if agent.is_some() { "agent" } else { "shell" };
+    // Temp files under ~/.stagehand/tmp/<pid>/pty-<id>/, plus files written into
+    // the working directory; all removed when the session exits.
+    let mut temp_ctx = TempContext::new(&format!("pty-{}", session_id))?;
+
+    let label: &str = if agent.is_some() {
+        "agent"
+    } else if args.command.is_some() {
+        "command"
+    } else {
+        "shell"
+    };
 
+    let mut integration = None;
     let mut cmd = if let Some(agent) = agent {
         let mut c = CommandBuilder::new(agent.binary());
-
+        if let Some(env) = env {
+            env.apply_to_builder(&mut c);
+        }
         if let Some(flag) = agent.auto_approve_flag() {
             c.arg(flag);
         }
 
-        // Model override
-        if let Some(ref model) = args.persona_model {
-            match agent {
-                Agent::Claude | Agent::Codex | Agent::Gemini | Agent::Amp => {
-                    c.arg("--model");
-                    c.arg(model);
-                }
-                Agent::OpenCode => {}
+        let opts = args.agent_options();
+        let prepared = match prepare_files(&opts, agent, &mut temp_ctx) {
+            Ok(p) => p,
+            Err(e) => {
+                temp_ctx.cleanup();
+                return Err(e);
             }
+        };
+        AgentFlags::new(&opts, agent, &prepared, args.session()).apply_to_builder(&mut c);
+        c
+    } else if let Some(ref command) = args.command {
+        if command.trim().is_empty() {
+            temp_ctx.cleanup();
+            return Err("Command must not be empty".to_string());
         }
-
-        // System prompt — per-agent mechanism
-        if let Some(ref system_prompt) = args.append_system_prompt {
-            match agent {
-                Agent::Claude | Agent::Amp => {
-                    c.arg("--append-system-prompt");
-                    c.arg(system_prompt);
-                }
-                Agent::Codex => {
-                    // Write AGENTS.md in the working directory
-                    if let Some(ref dir) = args.working_directory {
-                        let path = Path::new(dir).join("AGENTS.md");
-                        std::fs::write(&path, system_prompt)
-                            .map_err(|e| format!("Failed to write AGENTS.md: {}", e))?;
-                        temp_files.workdir_files.push(path);
-                    }
-                }
-                Agent::Gemini => {
-                    // Write system prompt to temp file and set env var
-                    let temp_dir = temp_files.ensure_temp_dir(&session_id)?;
-                    let path = temp_dir.join("system_prompt.md");
-                    std::fs::write(&path, system_prompt)
-                        .map_err(|e| format!("Failed to write Gemini system prompt: {}", e))?;
-                    c.env("GEMINI_SYSTEM_MD", path.to_string_lossy().as_ref());
-                }
-                Agent::OpenCode => {
-                    // No system prompt support
-                }
-            }
+        let mut c = if args.login_shell {
+            CommandBuilder::from_argv(
+                login_shell_argv(&default_shell(), command, &args.args)
+                    .into_iter()
+                    .map(Into::into)
+                    .collect(),
+            )
+        } else {
+            let mut c = CommandBuilder::new(command);
+            c.args(&args.args);
+            c
+        };
+        if let Some(env) = env {
+            env.apply_to_builder(&mut c);
         }
-
         c
     } else {
-        // Raw shell — use $SHELL or fall back to /bin/zsh
-        let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/zsh".to_string());
-        CommandBuilder::new(shell)
+        let shell = default_shell();
+        let mut c = CommandBuilder::new(&shell);
+        integration = Shell::from_path(&shell).filter(|_| args.shell_integration);
+        if args.login_shell && integration.is_none() {
+            c.arg("-l");
+        }
+        if let Some(env) = env {
+            env.apply_to_builder(&mut c);
+        }
+        c
     };
 
+    for (key, value) in &args.env {
+        cmd.env(key, value);
+    }
+
+    // After the environment, since zsh's integration replaces ZDOTDIR.
+    if let Some(shell) = integration {
+        let zdotdir = args.env.get("ZDOTDIR").cloned().or_else(|| std::env::var("ZDOTDIR").ok());
+        if let Err(e) = shell_integration::inject(shell, &mut cmd, &temp_ctx, args.login_shell, zdotdir.as_deref()) {
+            temp_ctx.cleanup();
+            return Err(e);
+        }
+    }
+
     if let Some(ref dir) = args.working_directory {
         cmd.cwd(dir);
     }
 
-    let child = pair
-        .slave
-        .spawn_command(cmd)
-        .map_err(|e| format!("Failed to spawn {} in PTY: {}", label, e))?;
+    let child = match pair.slave.spawn_command(cmd) {
+        Ok(child) => child,
+        Err(e) => {
+            temp_ctx.cleanup();
+            return Err(format!("Failed to spawn {} in PTY: {}", label, e));
+        }
+    };
 
     // Drop the slave side — the child owns it now
     drop(pair.slave);
 
-    let writer = pair
-        .master
-        .take_writer()
-        .map_err(|e| format!("Failed to get PTY writer: {}", e))?;
-
-    let reader = pair
-        .master
-        .try_clone_reader()
-        .map_err(|e| format!("Failed to get PTY reader: {}", e))?;
-
-    let (kill_tx, kill_rx) = tokio::sync::oneshot::channel::<()>();
-
-    let entry = PtyEntry {
-        writer,
-        child,
-        master_pty: pair.master,
-        kill_tx: Some(kill_tx),
+    let meta = PtyMeta {
+        agent: agent.map(|a| a.binary().to_string()),
+        command: args.command.clone(),
+        task_id: args.task_id.clone(),
+        working_directory: args.working_directory.clone(),
+        cols,
+        rows,
+        binary: args.binary,
+        ..PtyMeta::default()
+    };
+    // A recording that can't be started shouldn't stop the session.
+    let recorder = if args.record {
+        Recorder::create(session_id, &meta)
+            .inspect_err(|e| log::warn!("Not recording PTY session {}: {}", session_id, e))
+            .ok()
+    } else {
+        None
     };
 
-    pty_manager.register(session_id.clone(), entry).await;
-
-    let _ = on_event.send(PtyEvent::Started {
-        id: session_id.clone(),
-    });
-
-    // Spawn a blocking read loop for PTY output
-    let sid = session_id.clone();
-    let output_event = on_event.clone();
-    tokio::task::spawn_blocking(move || {
-        use std::io::Read;
-        let mut reader = reader;
-        let mut buf = [0u8; 4096];
-        loop {
-            match reader.read(&mut buf) {
-                Ok(0) => break,
-                Ok(n) => {
-                    let data = String::from_utf8_lossy(&buf[..n]).to_string();
-                    let _ = output_event.send(PtyEvent::Output { data });
-                }
-                Err(_) => break,
-            }
-        }
-        let _ = sid; // keep sid alive for the duration
-    });
-
-    // Spawn a task that waits for child exit or kill signal
-    let sid = session_id.clone();
-    let pm = pty_manager.inner().clone();
-    let exit_event = on_event.clone();
-    tokio::spawn(async move {
-        let exit_code: Option<i32> = tokio::select! {
-            // Poll child exit in a blocking thread
-            result = tokio::task::spawn_blocking({
-                let pm = pm.clone();
-                let sid = sid.clone();
-                move || -> Option<i32> {
-                    loop {
-                        std::thread::sleep(std::time::Duration::from_millis(100));
-                        let rt = tokio::runtime::Handle::current();
-                        let mut sessions = rt.block_on(pm.sessions.lock());
-                        if let Some(entry) = sessions.get_mut(&sid) {
-                            if let Ok(Some(status)) = entry.child.try_wait() {
-                                return Some(status.exit_code() as i32);
-                            }
-                        } else {
-                            return None;
-                        }
-                        drop(sessions);
-                    }
-                }
-            }) => {
-                result.unwrap_or(None)
-            }
-            _ = kill_rx => {
-                // Kill requested — try to kill the child
-                let mut sessions = pm.sessions.lock().await;
-                if let Some(entry) = sessions.get_mut(&sid) {
-                    let _ = entry.child.kill();
-                }
-                drop(sessions);
-                None
-            }
-        };
-
-        // Clean up temp files after PTY exits
-        temp_files.cleanup();
-
-        let _ = exit_event.send(PtyEvent::Exited {
-            id: sid.clone(),
-            exit_code,
-        });
-
-        pm.remove(&sid).await;
-    });
-
-    Ok(session_id)
+    Ok(PtySpawn {
+        master: pair.master,
+        child,
+        events,
+        temp: SharedTempContext::new(temp_ctx),
+        meta,
+        recorder,
+    })
 }
 
 #[tauri::command]
@@ -253,7 +376,7 @@ pub async fn write_to_pty(
     data: String,
     pty_manager: State<'_, PtyManager>,
 ) -> Result<(), String> {
-    pty_manager.write(&id, data.as_bytes()).await
+    pty_manager.write(&id, data.into_bytes()).await
 }
 
 #[tauri::command]
@@ -266,6 +389,22 @@ pub async fn resize_pty(
     pty_manager.resize(&id, cols, rows).await
 }
 
+#[tauri::command]
+pub async fn pause_pty(
+    id: String,
+    pty_manager: State<'_, PtyManager>,
+) -> Result<(), String> {
+    pty_manager.pause(&id).await
+}
+
+#[tauri::command]
+pub async fn resume_pty(
+    id: String,
+    pty_manager: State<'_, PtyManager>,
+) -> Result<(), String> {
+    pty_manager.resume(&id).await
+}
+
 #[tauri::command]
 pub async fn kill_pty(
     id: String,
@@ -273,3 +412,152 @@ pub async fn kill_pty(
 ) -> Result<(), String> {
     pty_manager.kill(&id).await
 }
+
+/// Send SIGINT, SIGTSTP, SIGHUP or SIGTERM to the process in the
+/// session's foreground, as typing Ctrl-C or Ctrl-Z would.
+#[tauri::command]
+pub async fn signal_pty(
+    id: String,
+    signal: Signal,
+    pty_manager: State<'_, PtyManager>,
+) -> Result<(), String> {
+    pty_manager.signal(&id, signal).await
+}
+
+/// The process in the foreground of the session's terminal: the shell
+/// itself when idle, otherwise the command it is running.
+#[tauri::command]
+pub async fn pty_foreground(
+    id: String,
+    pty_manager: State<'_, PtyManager>,
+) -> Result<ForegroundProcess, String> {
+    pty_manager.foreground(&id).await
+}
+
+/// Whether `persistent` sessions can be spawned in this build.
+#[tauri::command]
+pub fn persistent_terminals_available() -> bool {
+    ptyd::host_available()
+}
+
+#[tauri::command]
+pub async fn list_ptys(pty_manager: State<'_, PtyManager>) -> Result<Vec<PtySummary>, String> {
+    Ok(pty_manager.list().await)
+}
+
+/// Subscribe to a running session, replaying its scrollback first. Returns
+/// a subscriber id for `detach_pty`.
+#[tauri::command]
+pub async fn attach_pty(
+    id: String,
+    on_event: Channel<PtyEvent>,
+    pty_manager: State<'_, PtyManager>,
+) -> Result<u64, String> {
+    pty_manager.attach(&id, on_event).await
+}
+
+#[tauri::command]
+pub async fn detach_pty(
+    id: String,
+    subscriber_id: u64,
+    pty_manager: State<'_, PtyManager>,
+) -> Result<(), String> {
+    pty_manager.detach(&id, subscriber_id)
+}
+
+/// The session's visible screen text and cursor position, as rendered by
+/// the backend's own terminal emulator.
+#[tauri::command]
+pub async fn pty_snapshot(
+    id: String,
+    pty_manager: State<'_, PtyManager>,
+) -> Result<ScreenSnapshot, String> {
+    pty_manager.snapshot(&id).await
+}
+
+/// Commands that finished in the task's terminals, oldest first, keeping
+/// the last `limit`.
+#[tauri::command]
+pub async fn list_command_history(
+    task_id: String,
+    limit: Option<usize>,
+) -> Result<Vec<CommandRecord>, String> {
+    command_history::list(&task_id, limit)
+}
+
+#[tauri::command]
+pub async fn list_attention_matchers(
+    store: State<'_, AttentionStore>,
+) -> Result<Vec<AttentionMatcher>, String> {
+    store.list().await
+}
+
+/// Replace the attention matchers. Running sessions, including those in
+/// `stagehand-ptyd`, use them from their next check.
+#[tauri::command]
+pub async fn save_attention_matchers(
+    matchers: Vec<AttentionMatcher>,
+    store: State<'_, AttentionStore>,
+    pty_manager: State<'_, PtyManager>,
+) -> Result<(), String> {
+    store.save(&matchers).await?;
+    pty_manager.set_attention_matchers(&matchers)?;
+    pty_manager.send_attention_matchers_to_host(&matchers).await
+}
+
+#[tauri::command]
+pub async fn list_terminal_profiles(
+    store: State<'_, TerminalProfileStore>,
+) -> Result<Vec<TerminalProfile>, String> {
+    store.list().await
+}
+
+#[tauri::command]
+pub async fn save_terminal_profile(
+    profile: TerminalProfile,
+    store: State<'_, TerminalProfileStore>,
+) -> Result<(), String> {
+    store.upsert(profile).await
+}
+
+#[tauri::command]
+pub async fn delete_terminal_profile(
+    name: String,
+    store: State<'_, TerminalProfileStore>,
+) -> Result<(), String> {
+    store.delete(&name).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn login_shell_argv_per_shell() {
+        let args = strings(&["--watch", "a b"]);
+        assert_eq!(
+            login_shell_argv("/bin/bash", "npm", &args),
+            strings(&["/bin/bash", "-l", "-c", "exec \"$0\" \"$@\"", "npm", "--watch", "a b"])
+        );
+        assert_eq!(
+            login_shell_argv("/opt/homebrew/bin/fish", "npm", &args),
+            strings(&["/opt/homebrew/bin/fish", "-l", "-c", "exec $argv", "npm", "--watch", "a b"])
+        );
+        assert_eq!(
+            login_shell_argv("/usr/bin/nu", "npm", &[]),
+            strings(&["/bin/sh", "-l", "-c", "exec \"$0\" \"$@\"", "npm"])
+        );
+    }
+
+    #[test]
+    fn login_shell_passes_arguments_through_untouched() {
+        let args = strings(&["%s|", "a b", "$HOME", "'"]);
+        let argv = login_shell_argv("/bin/bash", "printf", &args);
+        let out = std::process::Command::new(&argv[0]).args(&argv[1..]).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "a b|$HOME|'|");
+    }
+}
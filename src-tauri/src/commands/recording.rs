@@ -0,0 +1,37 @@
+use crate::events::PtyEvent;
+use crate::recording::{self, Playbacks, RecordingSummary};
+use std::time::Duration;
+use tauri::ipc::Channel;
+use tauri::State;
+
+#[tauri::command]
+pub async fn list_recordings() -> Result<Vec<RecordingSummary>, String> {
+    recording::list()
+}
+
+/// Play a recording back through `on_event` at `speed` times real time
+/// (default 1), with pauses capped at `idle_limit_ms`. Returns a playback
+/// id for `stop_playback`.
+#[tauri::command]
+pub async fn play_recording(
+    id: String,
+    speed: Option<f64>,
+    idle_limit_ms: Option<u64>,
+    on_event: Channel<PtyEvent>,
+    playbacks: State<'_, Playbacks>,
+) -> Result<String, String> {
+    playbacks.start(
+        id,
+        speed.unwrap_or(1.0),
+        idle_limit_ms.map(Duration::from_millis),
+        on_event,
+    )
+}
+
+#[tauri::command]
+pub async fn stop_playback(
+    playback_id: String,
+    playbacks: State<'_, Playbacks>,
+) -> Result<(), String> {
+    playbacks.stop(&playback_id)
+}
@@ -0,0 +1,88 @@
+use crate::commands::git::blocking;
+use crate::worktree_manager::{WorktreeInfo, WorktreeManager};
+use tauri::State;
+
+/// Create a worktree for a task. See `WorktreeManager::create` for naming
+/// and defaults.
+#[tauri::command]
+pub async fn create_worktree(
+    manager: State<'_, WorktreeManager>,
+    working_directory: String,
+    task_id: String,
+    branch: Option<String>,
+    base: Option<String>,
+    base_dir: Option<String>,
+) -> Result<WorktreeInfo, String> {
+    let manager = manager.inner().clone();
+    blocking(move || {
+        manager.create(
+            &working_directory,
+            &task_id,
+            branch.as_deref(),
+            base.as_deref(),
+            base_dir.as_deref(),
+        )
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn list_worktrees(
+    manager: State<'_, WorktreeManager>,
+    working_directory: String,
+) -> Result<Vec<WorktreeInfo>, String> {
+    let manager = manager.inner().clone();
+    blocking(move || manager.list(&working_directory)).await
+}
+
+#[tauri::command]
+pub async fn lock_worktree(
+    manager: State<'_, WorktreeManager>,
+    working_directory: String,
+    path: String,
+    reason: Option<String>,
+) -> Result<(), String> {
+    let manager = manager.inner().clone();
+    blocking(move || manager.lock(&working_directory, &path, reason.as_deref())).await
+}
+
+#[tauri::command]
+pub async fn unlock_worktree(
+    manager: State<'_, WorktreeManager>,
+    working_directory: String,
+    path: String,
+) -> Result<(), String> {
+    let manager = manager.inner().clone();
+    blocking(move || manager.unlock(&working_directory, &path)).await
+}
+
+/// Drop worktrees whose directory is gone; returns their paths.
+#[tauri::command]
+pub async fn prune_worktrees(
+    manager: State<'_, WorktreeManager>,
+    working_directory: String,
+) -> Result<Vec<String>, String> {
+    let manager = manager.inner().clone();
+    blocking(move || manager.prune(&working_directory)).await
+}
+
+/// Remove a worktree. Refuses locked or dirty worktrees unless `force`.
+#[tauri::command]
+pub async fn remove_worktree(
+    manager: State<'_, WorktreeManager>,
+    working_directory: String,
+    path: String,
+    force: Option<bool>,
+    delete_branch: Option<bool>,
+) -> Result<(), String> {
+    let manager = manager.inner().clone();
+    blocking(move || {
+        manager.remove(
+            &working_directory,
+            &path,
+            force.unwrap_or(false),
+            delete_branch.unwrap_or(false),
+        )
+    })
+    .await
+}
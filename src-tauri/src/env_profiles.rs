@@ -0,0 +1,236 @@
+use crate::file_lock::FileLock;
+use crate::store;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tokio::sync::Mutex;
+
+/// Variables kept from the parent environment when a profile clears it, so
+/// the agent binary can still be found and run.
+const ESSENTIAL_VARS: &[&str] = &["PATH", "HOME", "USER", "LOGNAME", "SHELL", "TERM", "LANG", "TMPDIR"];
+
+const MASK: &str = "********";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvVar {
+    pub key: String,
+    /// `None` when listing a secret, or when saving a secret whose stored
+    /// value should be kept.
+    pub value: Option<String>,
+    #[serde(default)]
+    pub secret: bool,
+}
+
+/// A named set of environment variables for a project.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvProfile {
+    pub name: String,
+    /// `.env` files loaded in order; relative paths resolve against the
+    /// spawn's working directory. Missing files are skipped.
+    #[serde(default)]
+    pub env_files: Vec<String>,
+    /// Keys loaded from `env_files` that should be treated as secret.
+    #[serde(default)]
+    pub secret_keys: Vec<String>,
+    /// Inline variables; these override values from `env_files`.
+    #[serde(default)]
+    pub vars: Vec<EnvVar>,
+    /// Start from an empty environment (plus `ESSENTIAL_VARS`) instead of
+    /// inheriting Stagehand's.
+    #[serde(default)]
+    pub clear_env: bool,
+}
+
+impl EnvProfile {
+    /// Copy with secret values removed, for sending to the frontend.
+    fn redacted(&self) -> Self {
+        let mut profile = self.clone();
+        for var in profile.vars.iter_mut().filter(|v| v.secret) {
+            var.value = None;
+        }
+        profile
+    }
+}
+
+/// The environment a spawn should run with.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ResolvedEnv {
+    pub profile: String,
+    pub vars: BTreeMap<String, String>,
+    secret_keys: HashSet<String>,
+    pub clear_env: bool,
+}
+
+impl ResolvedEnv {
+    /// Parent variables that survive `clear_env`.
+    fn essentials() -> impl Iterator<Item = (String, String)> {
+        ESSENTIAL_VARS
+            .iter()
+            .filter_map(|k| std::env::var(k).ok().map(|v| (k.to_string(), v)))
+    }
+
+    pub fn apply_to_command(&self, cmd: &mut tokio::process::Command) {
+        if self.clear_env {
+            cmd.env_clear();
+            cmd.envs(Self::essentials());
+        }
+        cmd.envs(&self.vars);
+    }
+
+    pub fn apply_to_builder(&self, cmd: &mut portable_pty::CommandBuilder) {
+        if self.clear_env {
+            cmd.env_clear();
+            for (k, v) in Self::essentials() {
+                cmd.env(k, v);
+            }
+        }
+        for (k, v) in &self.vars {
+            cmd.env(k, v);
+        }
+    }
+
+    /// `KEY=value` pairs with secret values masked, for logging.
+    pub fn masked(&self) -> String {
+        self.vars
+            .iter()
+            .map(|(k, v)| {
+                if self.secret_keys.contains(k) {
+                    format!("{}={}", k, MASK)
+                } else {
+                    format!("{}={}", k, v)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Persists environment profiles per project in
+/// `~/.stagehand/data/env_profiles.json`. Secrets are stored in the same
+/// file, which is only readable by the current user.
+pub struct EnvProfileStore {
+    path: Option<PathBuf>,
+    lock: Mutex<()>,
+}
+
+type ProfilesByProject = HashMap<String, Vec<EnvProfile>>;
+
+impl EnvProfileStore {
+    pub fn new() -> Self {
+        Self {
+            path: store::data_file("env_profiles.json"),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn load(&self) -> Result<ProfilesByProject, String> {
+        let path = self.path.as_deref().ok_or("Could not find home directory")?;
+        store::read_json(path)
+    }
+
+    /// Held across a load and save; see `store::lock_json`.
+    async fn lock_file(&self) -> Result<FileLock, String> {
+        let path = self.path.as_deref().ok_or("Could not find home directory")?;
+        store::lock_json_async(path).await
+    }
+
+    fn save(&self, all: &ProfilesByProject) -> Result<(), String> {
+        let path = self.path.as_deref().ok_or("Could not find home directory")?;
+        store::write_json(path, all)
+    }
+
+    /// Profiles for a project, with secret values redacted.
+    pub async fn list(&self, project_id: &str) -> Result<Vec<EnvProfile>, String> {
+        let _guard = self.lock.lock().await;
+        let all = self.load()?;
+        Ok(all
+            .get(project_id)
+            .map(|profiles| profiles.iter().map(EnvProfile::redacted).collect())
+            .unwrap_or_default())
+    }
+
+    /// Insert or replace a profile by name. Secret variables sent without a
+    /// value keep their stored value.
+    pub async fn upsert(&self, project_id: &str, mut profile: EnvProfile) -> Result<(), String> {
+        if profile.name.trim().is_empty() {
+            return Err("Profile name must not be empty".to_string());
+        }
+        let _guard = self.lock.lock().await;
+        let _file = self.lock_file().await?;
+        let mut all = self.load()?;
+        let profiles = all.entry(project_id.to_string()).or_default();
+        let existing = profiles.iter().position(|p| p.name == profile.name);
+
+        for var in profile.vars.iter_mut().filter(|v| v.value.is_none()) {
+            var.value = existing
+                .and_then(|i| profiles[i].vars.iter().find(|old| old.key == var.key))
+                .and_then(|old| old.value.clone());
+        }
+
+        match existing {
+            Some(i) => profiles[i] = profile,
+            None => profiles.push(profile),
+        }
+        self.save(&all)
+    }
+
+    pub async fn delete(&self, project_id: &str, name: &str) -> Result<(), String> {
+        let _guard = self.lock.lock().await;
+        let _file = self.lock_file().await?;
+        let mut all = self.load()?;
+        if let Some(profiles) = all.get_mut(project_id) {
+            profiles.retain(|p| p.name != name);
+        }
+        self.save(&all)
+    }
+
+    /// Load a profile and its `.env` files into the environment for a spawn.
+    pub async fn resolve(
+        &self,
+        project_id: &str,
+        name: &str,
+        working_directory: Option<&str>,
+    ) -> Result<ResolvedEnv, String> {
+        let profile = {
+            let _guard = self.lock.lock().await;
+            self.load()?
+                .remove(project_id)
+                .and_then(|profiles| profiles.into_iter().find(|p| p.name == name))
+                .ok_or_else(|| format!("Environment profile '{}' not found", name))?
+        };
+
+        let mut resolved = ResolvedEnv {
+            profile: profile.name.clone(),
+            clear_env: profile.clear_env,
+            secret_keys: profile.secret_keys.iter().cloned().collect(),
+            ..Default::default()
+        };
+
+        for file in &profile.env_files {
+            let path = match working_directory {
+                Some(dir) if Path::new(file).is_relative() => Path::new(dir).join(file),
+                _ => PathBuf::from(file),
+            };
+            if !path.exists() {
+                continue;
+            }
+            let iter = dotenvy::from_path_iter(&path)
+                .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+            for item in iter {
+                let (k, v) = item.map_err(|e| format!("Failed to parse {:?}: {}", path, e))?;
+                resolved.vars.insert(k, v);
+            }
+        }
+
+        for var in profile.vars {
+            if var.secret {
+                resolved.secret_keys.insert(var.key.clone());
+            }
+            resolved.vars.insert(var.key, var.value.unwrap_or_default());
+        }
+
+        Ok(resolved)
+    }
+}
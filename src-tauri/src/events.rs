@@ -1,3 +1,6 @@
+use crate::hooks::HookPhase;
+use crate::retry::FailureKind;
+use crate::task_overlap::OverlapReport;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,6 +15,35 @@ pub enum AgentStreamEvent {
     StdoutLine { line: String },
     #[serde(rename = "stderr_line")]
     StderrLine { line: String },
+    #[serde(rename = "paused")]
+    Paused { process_id: String },
+    #[serde(rename = "resumed")]
+    Resumed { process_id: String, paused_ms: u64 },
+    #[serde(rename = "retrying")]
+    Retrying {
+        process_id: String,
+        attempt: u32,
+        reason: FailureKind,
+        delay_ms: u64,
+    },
+    #[serde(rename = "hook_output")]
+    HookOutput {
+        process_id: String,
+        phase: HookPhase,
+        command: String,
+        stream: String,
+        line: String,
+    },
+    #[serde(rename = "hook_finished")]
+    HookFinished {
+        process_id: String,
+        phase: HookPhase,
+        command: String,
+        exit_code: Option<i32>,
+    },
+    /// The run is being stopped so PTY session `pty_id` can continue it.
+    #[serde(rename = "handed_over")]
+    HandedOver { process_id: String, pty_id: String },
     #[serde(rename = "completed")]
     Completed {
         process_id: String,
@@ -31,8 +63,55 @@ pub enum PtyEvent {
     Started { id: String },
     #[serde(rename = "output")]
     Output { data: String },
+    /// Raw output of a session spawned with `binary`, base64-encoded.
+    #[serde(rename = "output_bytes")]
+    OutputBytes { data: String },
+    /// The screen matched an attention matcher, e.g. a permission prompt.
+    #[serde(rename = "attention")]
+    Attention { id: String, reason: String },
+    /// A command started in a shell, reported by its shell integration or
+    /// found as the terminal's foreground process.
+    #[serde(rename = "command_started")]
+    CommandStarted { id: String, command: String },
+    /// `exit_code` is `None` when the shell didn't report it.
+    #[serde(rename = "command_finished")]
+    CommandFinished {
+        id: String,
+        command: String,
+        exit_code: Option<i32>,
+        duration_ms: u64,
+    },
+    #[serde(rename = "cwd_changed")]
+    CwdChanged { id: String, cwd: String },
+    #[serde(rename = "paused")]
+    Paused { id: String },
+    #[serde(rename = "resumed")]
+    Resumed { id: String, paused_ms: u64 },
+    /// Terminal size changes in a recording being played back.
+    #[serde(rename = "resized")]
+    Resized { id: String, cols: u16, rows: u16 },
     #[serde(rename = "exited")]
     Exited { id: String, exit_code: Option<i32> },
     #[serde(rename = "error")]
     Error { id: String, message: String },
 }
+
+/// Changes in which active tasks touch the same files.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type")]
+pub enum OverlapEvent {
+    /// The full comparison; sent first and whenever it changes.
+    #[serde(rename = "report")]
+    Report { report: OverlapReport },
+    /// Two tasks started changing the same file, or their changes to it
+    /// started touching the same lines (`conflicting`).
+    #[serde(rename = "new_overlap")]
+    NewOverlap {
+        task_a: String,
+        task_b: String,
+        path: String,
+        conflicting: bool,
+    },
+    #[serde(rename = "error")]
+    Error { message: String },
+}
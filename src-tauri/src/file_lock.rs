@@ -0,0 +1,69 @@
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+
+/// An exclusive `flock` on a file, released when dropped. Every `acquire`
+/// opens the file anew, so the lock excludes other threads of this process
+/// as well as other processes.
+pub struct FileLock {
+    _file: File,
+}
+
+fn open(path: &Path) -> Result<File, String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create dir {:?}: {}", parent, e))?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open lock file {:?}: {}", path, e))
+}
+
+impl FileLock {
+    /// Wait until the lock on `path` is free and take it. Blocks, so async
+    /// callers should go through `spawn_blocking`.
+    pub fn acquire(path: &Path) -> Result<Self, String> {
+        let file = open(path)?;
+        lock(&file, false).map_err(|e| format!("Failed to lock {:?}: {}", path, e))?;
+        Ok(Self { _file: file })
+    }
+
+    /// Take the lock on `path` if nobody holds it. `None` means it is held,
+    /// by this or another process.
+    pub fn try_acquire(path: &Path) -> Result<Option<Self>, String> {
+        let file = open(path)?;
+        match lock(&file, true) {
+            Ok(()) => Ok(Some(Self { _file: file })),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(format!("Failed to lock {:?}: {}", path, e)),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn lock(file: &File, nonblocking: bool) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    let op = if nonblocking { libc::LOCK_EX | libc::LOCK_NB } else { libc::LOCK_EX };
+    loop {
+        // SAFETY: flock(2) on a descriptor owned by `file`.
+        if unsafe { libc::flock(file.as_raw_fd(), op) } == 0 {
+            return Ok(());
+        }
+        let err = std::io::Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+/// Without `flock`, waiting always succeeds and a lock that can't wait is
+/// reported as held, so nothing another process might own is touched.
+#[cfg(not(unix))]
+fn lock(_file: &File, nonblocking: bool) -> std::io::Result<()> {
+    if nonblocking {
+        return Err(std::io::ErrorKind::WouldBlock.into());
+    }
+    Ok(())
+}
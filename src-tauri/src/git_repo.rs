@@ -0,0 +1,1244 @@
+use git2::{
+    BranchType, Commit, Delta, DiffFindOptions, DiffOptions, IndexConflict, IndexEntry,
+    MergeFileOptions, MergeOptions, Oid, Patch, Repository, Sort, Status, StatusOptions, Tree,
+};
+use serde::Serialize;
+use std::path::Path;
+
+/// Largest blob `show_file` will return.
+const MAX_SHOW_SIZE: usize = 10 * 1024 * 1024;
+
+/// Word diffs are skipped for line pairs whose token grid is larger than
+/// this, to keep the quadratic comparison cheap.
+const MAX_WORD_DIFF_CELLS: usize = 250_000;
+
+pub fn git_err(e: git2::Error) -> String {
+    e.message().to_string()
+}
+
+/// The repository containing `working_directory`, which may be a
+/// subdirectory or a linked worktree.
+pub fn open(working_directory: &str) -> Result<Repository, String> {
+    Repository::discover(working_directory)
+        .map_err(|e| format!("Not a git repository: {} ({})", working_directory, e.message()))
+}
+
+fn lossy(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// How a file differs, in a status entry or a diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileChange {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    Copied,
+    TypeChange,
+    Untracked,
+    Conflicted,
+}
+
+impl FileChange {
+    fn of(delta: Delta) -> FileChange {
+        match delta {
+            Delta::Added => FileChange::Added,
+            Delta::Deleted => FileChange::Deleted,
+            Delta::Renamed => FileChange::Renamed,
+            Delta::Copied => FileChange::Copied,
+            Delta::Typechange => FileChange::TypeChange,
+            Delta::Untracked => FileChange::Untracked,
+            Delta::Conflicted => FileChange::Conflicted,
+            _ => FileChange::Modified,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusEntry {
+    pub path: String,
+    /// Previous path of a staged rename.
+    pub old_path: Option<String>,
+    /// Change between HEAD and the index.
+    pub staged: Option<FileChange>,
+    /// Change between the index and the working tree; `conflicted` for
+    /// unmerged paths.
+    pub unstaged: Option<FileChange>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RepoStatus {
+    /// `None` on a detached HEAD.
+    pub branch: Option<String>,
+    /// `None` before the first commit.
+    pub head: Option<String>,
+    pub upstream: Option<String>,
+    pub ahead: usize,
+    pub behind: usize,
+    pub files: Vec<StatusEntry>,
+}
+
+fn staged_change(status: Status) -> Option<FileChange> {
+    if status.is_index_new() {
+        Some(FileChange::Added)
+    } else if status.is_index_modified() {
+        Some(FileChange::Modified)
+    } else if status.is_index_deleted() {
+        Some(FileChange::Deleted)
+    } else if status.is_index_renamed() {
+        Some(FileChange::Renamed)
+    } else if status.is_index_typechange() {
+        Some(FileChange::TypeChange)
+    } else {
+        None
+    }
+}
+
+fn unstaged_change(status: Status) -> Option<FileChange> {
+    if status.is_conflicted() {
+        Some(FileChange::Conflicted)
+    } else if status.is_wt_new() {
+        Some(FileChange::Untracked)
+    } else if status.is_wt_modified() {
+        Some(FileChange::Modified)
+    } else if status.is_wt_deleted() {
+        Some(FileChange::Deleted)
+    } else if status.is_wt_renamed() {
+        Some(FileChange::Renamed)
+    } else if status.is_wt_typechange() {
+        Some(FileChange::TypeChange)
+    } else {
+        None
+    }
+}
+
+/// Branch and changed files, like `git status`. Untracked directories are
+/// listed file by file; ignored files are left out.
+pub fn status(working_directory: &str) -> Result<RepoStatus, String> {
+    let repo = open(working_directory)?;
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .renames_head_to_index(true);
+    let statuses = repo.statuses(Some(&mut opts)).map_err(git_err)?;
+    let files = statuses
+        .iter()
+        .filter_map(|entry| {
+            let status = entry.status();
+            let staged = staged_change(status);
+            let unstaged = unstaged_change(status);
+            if staged.is_none() && unstaged.is_none() {
+                return None;
+            }
+            // The entry's own path is the old one for a staged rename.
+            let renamed = entry.head_to_index().filter(|d| d.status() == Delta::Renamed);
+            let old_path = renamed
+                .as_ref()
+                .and_then(|d| d.old_file().path_bytes().map(lossy));
+            let path = renamed
+                .as_ref()
+                .and_then(|d| d.new_file().path_bytes())
+                .unwrap_or(entry.path_bytes());
+            Some(StatusEntry {
+                path: lossy(path),
+                old_path,
+                staged,
+                unstaged,
+            })
+        })
+        .collect();
+
+    let head = repo.head().ok();
+    let branch = match &head {
+        Some(head) if head.is_branch() => head.shorthand().map(str::to_string),
+        Some(_) => None,
+        // Before the first commit HEAD names a branch that doesn't exist yet.
+        None => repo
+            .find_reference("HEAD")
+            .ok()
+            .and_then(|r| r.symbolic_target().map(str::to_string))
+            .map(|t| t.trim_start_matches("refs/heads/").to_string()),
+    };
+    let head_oid = head.as_ref().and_then(|h| h.target());
+
+    let mut upstream = None;
+    let (mut ahead, mut behind) = (0, 0);
+    if let Some(name) = branch.as_deref() {
+        if let Ok(up) = repo
+            .find_branch(name, BranchType::Local)
+            .and_then(|b| b.upstream())
+        {
+            upstream = up.name().ok().flatten().map(str::to_string);
+            if let (Some(local), Some(remote)) = (head_oid, up.get().target()) {
+                (ahead, behind) = repo.graph_ahead_behind(local, remote).map_err(git_err)?;
+            }
+        }
+    }
+
+    Ok(RepoStatus {
+        branch,
+        head: head_oid.map(|o| o.to_string()),
+        upstream,
+        ahead,
+        behind,
+        files,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineKind {
+    Context,
+    Addition,
+    Deletion,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffLine {
+    pub kind: LineKind,
+    /// The line without its trailing newline.
+    pub content: String,
+    pub old_lineno: Option<u32>,
+    pub new_lineno: Option<u32>,
+    /// The line split into changed and unchanged runs, when a word diff was
+    /// asked for and the line pairs with one on the other side.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub words: Option<Vec<WordSpan>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WordSpan {
+    pub text: String,
+    pub changed: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffHunk {
+    /// The `@@ -a,b +c,d @@` line, with any function context.
+    pub header: String,
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffFile {
+    pub path: String,
+    /// Previous path of a renamed or copied file.
+    pub old_path: Option<String>,
+    pub status: FileChange,
+    /// Binary files have no hunks or line counts.
+    pub binary: bool,
+    pub additions: usize,
+    pub deletions: usize,
+    /// The file is over `max_file_bytes`, so its content wasn't read: it
+    /// has no hunks or line counts, and isn't known to be binary.
+    pub too_large: bool,
+    /// Empty when only stats were asked for, or the file is too large.
+    pub hunks: Vec<DiffHunk>,
+}
+
+/// What `diff` compares.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiffSpec<'a> {
+    /// Revision to diff from; HEAD if unset.
+    pub base: Option<&'a str>,
+    /// Revision to diff to; the working tree (with the index) if unset.
+    pub head: Option<&'a str>,
+    /// Only these paths or pathspecs.
+    pub paths: &'a [String],
+    /// Diff from the merge base of `base` and `head`, like `base...head`.
+    pub merge_base: bool,
+    /// Include untracked files when diffing against the working tree.
+    pub include_untracked: bool,
+    /// Leave out hunks and return only per-file stats.
+    pub stat_only: bool,
+    /// Ignore whitespace when comparing lines, like `git diff -w`.
+    pub ignore_whitespace: bool,
+    /// Lines of context around each hunk; git's default of 3 if unset.
+    pub context_lines: Option<u32>,
+    /// Fill in `DiffLine::words` for paired deletions and additions.
+    pub word_diff: bool,
+    /// Leave out the hunks of files larger than this, on either side.
+    pub max_file_bytes: Option<u64>,
+}
+
+fn tree_of<'r>(repo: &'r Repository, rev: &str) -> Result<Tree<'r>, String> {
+    repo.revparse_single(rev)
+        .and_then(|o| o.peel_to_tree())
+        .map_err(|e| format!("Unknown revision {}: {}", rev, e.message()))
+}
+
+fn commit_of<'r>(repo: &'r Repository, rev: &str) -> Result<Commit<'r>, String> {
+    repo.revparse_single(rev)
+        .and_then(|o| o.peel_to_commit())
+        .map_err(|e| format!("Unknown revision {}: {}", rev, e.message()))
+}
+
+fn commit_oid(repo: &Repository, rev: &str) -> Result<Oid, String> {
+    commit_of(repo, rev).map(|c| c.id())
+}
+
+/// Per-file diff with hunks, like `git diff`. Renames are detected.
+pub fn diff(working_directory: &str, spec: DiffSpec) -> Result<Vec<DiffFile>, String> {
+    let repo = open(working_directory)?;
+    let base_tree = if spec.merge_base {
+        let base = commit_oid(&repo, spec.base.unwrap_or("HEAD"))?;
+        let head = commit_oid(&repo, spec.head.unwrap_or("HEAD"))?;
+        let oid = repo.merge_base(base, head).map_err(git_err)?;
+        Some(repo.find_commit(oid).and_then(|c| c.tree()).map_err(git_err)?)
+    } else {
+        match spec.base {
+            Some(rev) => Some(tree_of(&repo, rev)?),
+            // Before the first commit everything is new.
+            None => repo.head().ok().and_then(|h| h.peel_to_tree().ok()),
+        }
+    };
+
+    let mut opts = DiffOptions::new();
+    for path in spec.paths {
+        opts.pathspec(path);
+    }
+    opts.ignore_whitespace(spec.ignore_whitespace);
+    if let Some(lines) = spec.context_lines {
+        opts.context_lines(lines);
+    }
+    // Files over the limit are then treated as binary, so their content is
+    // never loaded.
+    if let Some(max) = spec.max_file_bytes {
+        opts.max_size(i64::try_from(max).unwrap_or(i64::MAX));
+    }
+    // Showing untracked content also includes untracked files, so it is
+    // only set when they were asked for.
+    if spec.include_untracked {
+        opts.include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .show_untracked_content(true);
+    }
+    let mut diff = match spec.head {
+        Some(rev) => {
+            let head_tree = tree_of(&repo, rev)?;
+            repo.diff_tree_to_tree(base_tree.as_ref(), Some(&head_tree), Some(&mut opts))
+        }
+        None => repo.diff_tree_to_workdir_with_index(base_tree.as_ref(), Some(&mut opts)),
+    }
+    .map_err(git_err)?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))
+        .map_err(git_err)?;
+
+    let mut files = Vec::new();
+    for (i, delta) in diff.deltas().enumerate() {
+        let path = delta
+            .new_file()
+            .path_bytes()
+            .or_else(|| delta.old_file().path_bytes())
+            .map(lossy)
+            .unwrap_or_default();
+        let old_path = matches!(delta.status(), Delta::Renamed | Delta::Copied)
+            .then(|| delta.old_file().path_bytes().map(lossy))
+            .flatten();
+        let mut file = DiffFile {
+            path,
+            old_path,
+            status: FileChange::of(delta.status()),
+            binary: delta.flags().is_binary(),
+            additions: 0,
+            deletions: 0,
+            too_large: false,
+            hunks: Vec::new(),
+        };
+        let patch = Patch::from_diff(&diff, i).map_err(git_err)?;
+        if let Some(patch) = patch {
+            // Binary-ness and sizes are only known once the patch has been
+            // built. Files over the limit were marked binary by size alone.
+            let delta = patch.delta();
+            let size = delta.old_file().size().max(delta.new_file().size());
+            file.too_large = spec.max_file_bytes.is_some_and(|max| size > max);
+            file.binary = delta.flags().is_binary() && !file.too_large;
+            if !file.binary && !file.too_large {
+                let (_, additions, deletions) = patch.line_stats().map_err(git_err)?;
+                file.additions = additions;
+                file.deletions = deletions;
+                // Whitespace-only edits leave a delta with nothing in it.
+                if spec.ignore_whitespace
+                    && file.status == FileChange::Modified
+                    && additions + deletions == 0
+                {
+                    continue;
+                }
+                if !spec.stat_only {
+                    file.hunks = hunks(&patch)?;
+                    if spec.word_diff {
+                        for hunk in &mut file.hunks {
+                            diff_words(&mut hunk.lines);
+                        }
+                    }
+                }
+            }
+        }
+        files.push(file);
+    }
+    Ok(files)
+}
+
+fn hunks(patch: &Patch) -> Result<Vec<DiffHunk>, String> {
+    let mut hunks = Vec::new();
+    for h in 0..patch.num_hunks() {
+        let (hunk, count) = patch.hunk(h).map_err(git_err)?;
+        let mut lines = Vec::with_capacity(count);
+        for l in 0..count {
+            let line = patch.line_in_hunk(h, l).map_err(git_err)?;
+            let kind = match line.origin() {
+                ' ' => LineKind::Context,
+                '+' => LineKind::Addition,
+                '-' => LineKind::Deletion,
+                // "No newline at end of file" markers.
+                _ => continue,
+            };
+            let content = lossy(line.content());
+            lines.push(DiffLine {
+                kind,
+                content: content.strip_suffix('\n').unwrap_or(&content).to_string(),
+                old_lineno: line.old_lineno(),
+                new_lineno: line.new_lineno(),
+                words: None,
+            });
+        }
+        hunks.push(DiffHunk {
+            header: lossy(hunk.header()).trim_end().to_string(),
+            old_start: hunk.old_start(),
+            old_lines: hunk.old_lines(),
+            new_start: hunk.new_start(),
+            new_lines: hunk.new_lines(),
+            lines,
+        });
+    }
+    Ok(hunks)
+}
+
+/// Pair each run of deletions with the additions that follow it, line by
+/// line, and mark the words that differ within each pair.
+fn diff_words(lines: &mut [DiffLine]) {
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].kind != LineKind::Deletion {
+            i += 1;
+            continue;
+        }
+        let deleted = i;
+        while i < lines.len() && lines[i].kind == LineKind::Deletion {
+            i += 1;
+        }
+        let added = i;
+        while i < lines.len() && lines[i].kind == LineKind::Addition {
+            i += 1;
+        }
+        for pair in 0..(added - deleted).min(i - added) {
+            if let Some((old, new)) = word_spans(&lines[deleted + pair].content, &lines[added + pair].content) {
+                lines[deleted + pair].words = Some(old);
+                lines[added + pair].words = Some(new);
+            }
+        }
+    }
+}
+
+/// Split into runs of word characters, runs of whitespace and single
+/// punctuation characters.
+fn tokenize(line: &str) -> Vec<&str> {
+    fn class(c: char) -> u8 {
+        if c.is_alphanumeric() || c == '_' {
+            0
+        } else if c.is_whitespace() {
+            1
+        } else {
+            2
+        }
+    }
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut prev = None;
+    for (i, c) in line.char_indices() {
+        let cls = class(c);
+        if i > start && (prev != Some(cls) || cls == 2) {
+            tokens.push(&line[start..i]);
+            start = i;
+        }
+        prev = Some(cls);
+    }
+    if start < line.len() {
+        tokens.push(&line[start..]);
+    }
+    tokens
+}
+
+/// Changed and unchanged spans of both lines, from the longest common
+/// token subsequence. `None` when the lines share nothing or are too long
+/// to compare.
+fn word_spans(old: &str, new: &str) -> Option<(Vec<WordSpan>, Vec<WordSpan>)> {
+    let a = tokenize(old);
+    let b = tokenize(new);
+    if a.is_empty() || b.is_empty() || a.len() * b.len() > MAX_WORD_DIFF_CELLS {
+        return None;
+    }
+    // lcs[i][j] is the common length of a[i..] and b[j..].
+    let width = b.len() + 1;
+    let mut lcs = vec![0u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i * width + j] = if a[i] == b[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+    if lcs[0] == 0 {
+        return None;
+    }
+    let mut a_changed = vec![true; a.len()];
+    let mut b_changed = vec![true; b.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            a_changed[i] = false;
+            b_changed[j] = false;
+            i += 1;
+            j += 1;
+        } else if lcs[(i + 1) * width + j] >= lcs[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    Some((spans(&a, &a_changed), spans(&b, &b_changed)))
+}
+
+fn spans(tokens: &[&str], changed: &[bool]) -> Vec<WordSpan> {
+    let mut spans: Vec<WordSpan> = Vec::new();
+    for (token, &changed) in tokens.iter().zip(changed) {
+        match spans.last_mut() {
+            Some(last) if last.changed == changed => last.text.push_str(token),
+            _ => spans.push(WordSpan { text: token.to_string(), changed }),
+        }
+    }
+    spans
+}
+
+/// A task branch compared with the branch it was started from.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskDiff {
+    /// Where the branches diverged; the diff starts here.
+    pub merge_base: String,
+    /// The compared commit, or `None` for the working tree.
+    pub head: Option<String>,
+    pub files: Vec<DiffFile>,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+/// Diff `head` (the working tree if unset) against its merge base with
+/// `base`, so changes that landed on `base` since don't show up.
+pub fn task_diff(working_directory: &str, spec: DiffSpec) -> Result<TaskDiff, String> {
+    let spec = DiffSpec { merge_base: true, ..spec };
+    let repo = open(working_directory)?;
+    let base = commit_oid(&repo, spec.base.unwrap_or("HEAD"))?;
+    let head = spec.head.map(|rev| commit_oid(&repo, rev)).transpose()?;
+    let tip = match head {
+        Some(oid) => oid,
+        None => commit_oid(&repo, "HEAD")?,
+    };
+    let merge_base = repo.merge_base(base, tip).map_err(git_err)?;
+    let files = diff(working_directory, spec)?;
+    Ok(TaskDiff {
+        merge_base: merge_base.to_string(),
+        head: head.map(|oid| oid.to_string()),
+        additions: files.iter().map(|f| f.additions).sum(),
+        deletions: files.iter().map(|f| f.deletions).sum(),
+        files,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    /// Both sides changed the same lines.
+    Content,
+    /// Both sides added a file at the same path.
+    AddAdd,
+    /// One side changed a file the other deleted.
+    ModifyDelete,
+    /// One side renamed a file the other deleted.
+    RenameDelete,
+    /// The sides renamed a file to different paths.
+    RenameRename,
+    /// The sides changed the file mode differently.
+    Mode,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictHunk {
+    /// Line where the conflict starts in the merged file, counting the
+    /// ours side of earlier conflicts.
+    pub start_line: u32,
+    pub ours: Vec<String>,
+    /// The merge base's version of the lines.
+    pub base: Vec<String>,
+    pub theirs: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeConflict {
+    pub path: String,
+    pub kind: ConflictKind,
+    /// The file's path on each side; `None` where it doesn't exist.
+    pub base_path: Option<String>,
+    pub our_path: Option<String>,
+    pub their_path: Option<String>,
+    pub binary: bool,
+    /// Only content and mode conflicts between text files have hunks.
+    pub hunks: Vec<ConflictHunk>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeCheck {
+    pub ours: String,
+    pub theirs: String,
+    pub merge_base: Option<String>,
+    /// `theirs` is already part of `ours`; merging is a no-op.
+    pub up_to_date: bool,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// libgit2 reports a conflicting rename (rename/delete, rename/rename) as
+/// separate conflicts for the base path and each renamed path. Join them
+/// when the renames kept the content so they can be matched up.
+fn join_renames(conflicts: Vec<IndexConflict>) -> Vec<IndexConflict> {
+    let (mut renamed, mut rest): (Vec<_>, Vec<_>) = conflicts
+        .into_iter()
+        .partition(|c| c.ancestor.is_none() && c.our.is_none() != c.their.is_none());
+    for conflict in &mut rest {
+        let Some(base_id) = conflict
+            .ancestor
+            .as_ref()
+            .filter(|_| conflict.our.is_none() && conflict.their.is_none())
+            .map(|base| base.id)
+        else {
+            continue;
+        };
+        let same = |e: &Option<IndexEntry>| e.as_ref().is_some_and(|e| e.id == base_id);
+        if let Some(i) = renamed.iter().position(|r| same(&r.our)) {
+            conflict.our = renamed.swap_remove(i).our;
+        }
+        if let Some(i) = renamed.iter().position(|r| same(&r.their)) {
+            conflict.their = renamed.swap_remove(i).their;
+        }
+    }
+    rest.extend(renamed);
+    rest
+}
+
+fn conflict_kind(conflict: &IndexConflict) -> ConflictKind {
+    match (&conflict.ancestor, &conflict.our, &conflict.their) {
+        (None, Some(_), Some(_)) => ConflictKind::AddAdd,
+        // Parts of a rename conflict that `join_renames` couldn't match.
+        (None, _, _) | (Some(_), None, None) => ConflictKind::RenameDelete,
+        (Some(base), Some(ours), None) | (Some(base), None, Some(ours)) => {
+            if ours.path == base.path {
+                ConflictKind::ModifyDelete
+            } else {
+                ConflictKind::RenameDelete
+            }
+        }
+        (Some(base), Some(ours), Some(theirs)) => {
+            if ours.path != theirs.path {
+                ConflictKind::RenameRename
+            } else if ours.mode != theirs.mode && ours.mode != base.mode && theirs.mode != base.mode {
+                ConflictKind::Mode
+            } else {
+                ConflictKind::Content
+            }
+        }
+    }
+}
+
+/// Whether `line` is a 7-character conflict marker. The start, base and
+/// end markers may be followed by a space and a label; the separator is
+/// always on its own, so a longer `=====` underline isn't one.
+fn is_marker(line: &str, marker: &str) -> bool {
+    match line.strip_prefix(marker) {
+        Some(rest) => rest.is_empty() || (marker != "=======" && rest.starts_with(' ')),
+        None => false,
+    }
+}
+
+/// Split a diff3-style merge result into its conflicts.
+fn conflict_hunks(merged: &str) -> Vec<ConflictHunk> {
+    #[derive(PartialEq)]
+    enum Part {
+        Ours,
+        Base,
+        Theirs,
+    }
+    let mut hunks = Vec::new();
+    let mut current: Option<(Part, ConflictHunk)> = None;
+    let mut line_no = 1;
+    for line in merged.lines() {
+        match &mut current {
+            None if is_marker(line, "<<<<<<<") => {
+                let hunk = ConflictHunk {
+                    start_line: line_no,
+                    ours: Vec::new(),
+                    base: Vec::new(),
+                    theirs: Vec::new(),
+                };
+                current = Some((Part::Ours, hunk));
+            }
+            None => line_no += 1,
+            Some((part, _)) if *part == Part::Ours && is_marker(line, "|||||||") => *part = Part::Base,
+            Some((part, _)) if *part != Part::Theirs && is_marker(line, "=======") => *part = Part::Theirs,
+            Some((Part::Theirs, _)) if is_marker(line, ">>>>>>>") => {
+                if let Some((_, hunk)) = current.take() {
+                    line_no += hunk.ours.len() as u32;
+                    hunks.push(hunk);
+                }
+            }
+            Some((part, hunk)) => {
+                let lines = match part {
+                    Part::Ours => &mut hunk.ours,
+                    Part::Base => &mut hunk.base,
+                    Part::Theirs => &mut hunk.theirs,
+                };
+                lines.push(line.to_string());
+            }
+        }
+    }
+    hunks
+}
+
+fn is_binary(repo: &Repository, entry: &IndexEntry) -> bool {
+    repo.find_blob(entry.id).map(|b| b.is_binary()).unwrap_or(false)
+}
+
+/// What merging `theirs` into `ours` would conflict on, worked out in
+/// memory so the working tree and index are left alone.
+pub fn check_merge(working_directory: &str, ours: &str, theirs: &str) -> Result<MergeCheck, String> {
+    let repo = open(working_directory)?;
+    let our_commit = commit_of(&repo, ours)?;
+    let their_commit = commit_of(&repo, theirs)?;
+    let merge_base = repo.merge_base(our_commit.id(), their_commit.id()).ok();
+
+    let mut opts = MergeOptions::new();
+    opts.find_renames(true);
+    let index = repo
+        .merge_commits(&our_commit, &their_commit, Some(&opts))
+        .map_err(git_err)?;
+
+    let raw = index
+        .conflicts()
+        .map_err(git_err)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(git_err)?;
+    let mut conflicts = Vec::new();
+    for conflict in join_renames(raw) {
+        let path_of = |e: &Option<IndexEntry>| e.as_ref().map(|e| lossy(&e.path));
+        let kind = conflict_kind(&conflict);
+        let mut entry = MergeConflict {
+            path: path_of(&conflict.our)
+                .or_else(|| path_of(&conflict.their))
+                .or_else(|| path_of(&conflict.ancestor))
+                .unwrap_or_default(),
+            kind,
+            base_path: path_of(&conflict.ancestor),
+            our_path: path_of(&conflict.our),
+            their_path: path_of(&conflict.their),
+            binary: false,
+            hunks: Vec::new(),
+        };
+        if let (Some(base), Some(our), Some(their)) = (&conflict.ancestor, &conflict.our, &conflict.their) {
+            entry.binary = is_binary(&repo, our) || is_binary(&repo, their);
+            if matches!(kind, ConflictKind::Content | ConflictKind::Mode) && !entry.binary {
+                let mut file_opts = MergeFileOptions::new();
+                file_opts
+                    .style_diff3(true)
+                    .ancestor_label("base")
+                    .our_label(ours)
+                    .their_label(theirs);
+                let merged = repo
+                    .merge_file_from_index(base, our, their, Some(&mut file_opts))
+                    .map_err(git_err)?;
+                entry.hunks = conflict_hunks(&String::from_utf8_lossy(merged.content()));
+            }
+        }
+        conflicts.push(entry);
+    }
+    conflicts.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(MergeCheck {
+        ours: our_commit.id().to_string(),
+        theirs: their_commit.id().to_string(),
+        merge_base: merge_base.map(|oid| oid.to_string()),
+        up_to_date: merge_base == Some(their_commit.id()),
+        conflicts,
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitInfo {
+    pub hash: String,
+    pub short_hash: String,
+    /// First line of the message.
+    pub subject: String,
+    /// The rest of the message.
+    pub body: String,
+    pub author_name: String,
+    pub author_email: String,
+    /// Author time, in seconds since the epoch.
+    pub authored_at: i64,
+    pub parents: Vec<String>,
+}
+
+/// Commits reachable from `range`, newest first, like `git log`. `range`
+/// is a revision, `a..b` or `a...b`, with an empty side meaning HEAD; HEAD
+/// if unset.
+pub fn log(working_directory: &str, range: Option<&str>, max_count: Option<usize>) -> Result<Vec<CommitInfo>, String> {
+    let repo = open(working_directory)?;
+    let mut walk = repo.revwalk().map_err(git_err)?;
+    walk.set_sorting(Sort::TIME).map_err(git_err)?;
+    let side = |rev: &str| commit_oid(&repo, if rev.is_empty() { "HEAD" } else { rev });
+    match range {
+        None => {
+            // Nothing to list before the first commit.
+            if repo.head().is_err() {
+                return Ok(Vec::new());
+            }
+            walk.push_head().map_err(git_err)?;
+        }
+        Some(range) => {
+            if let Some((a, b)) = range.split_once("...") {
+                let (a, b) = (side(a)?, side(b)?);
+                walk.push(a).map_err(git_err)?;
+                walk.push(b).map_err(git_err)?;
+                if let Ok(base) = repo.merge_base(a, b) {
+                    walk.hide(base).map_err(git_err)?;
+                }
+            } else if let Some((a, b)) = range.split_once("..") {
+                walk.push(side(b)?).map_err(git_err)?;
+                walk.hide(side(a)?).map_err(git_err)?;
+            } else {
+                walk.push(side(range)?).map_err(git_err)?;
+            }
+        }
+    }
+
+    let mut commits = Vec::new();
+    for oid in walk.take(max_count.unwrap_or(usize::MAX)) {
+        let commit = repo.find_commit(oid.map_err(git_err)?).map_err(git_err)?;
+        let message = lossy(commit.message_bytes());
+        let (subject, body) = message.split_once('\n').unwrap_or((&message, ""));
+        let author = commit.author();
+        let hash = commit.id().to_string();
+        commits.push(CommitInfo {
+            short_hash: hash[..7].to_string(),
+            hash,
+            subject: subject.trim_end().to_string(),
+            body: body.trim().to_string(),
+            author_name: lossy(author.name_bytes()),
+            author_email: lossy(author.email_bytes()),
+            authored_at: author.when().seconds(),
+            parents: commit.parent_ids().map(|p| p.to_string()).collect(),
+        });
+    }
+    Ok(commits)
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchInfo {
+    /// Short name, e.g. `main` or `origin/main`.
+    pub name: String,
+    pub remote: bool,
+    /// Checked out in this working directory.
+    pub current: bool,
+    pub target: Option<String>,
+    pub upstream: Option<String>,
+    /// Commits ahead of and behind `upstream`.
+    pub ahead: Option<usize>,
+    pub behind: Option<usize>,
+}
+
+/// Local then remote branches, each sorted by name. Symbolic refs such as
+/// `origin/HEAD` are left out.
+pub fn branches(working_directory: &str) -> Result<Vec<BranchInfo>, String> {
+    let repo = open(working_directory)?;
+    let mut branches = Vec::new();
+    for branch in repo.branches(None).map_err(git_err)? {
+        let (branch, kind) = branch.map_err(git_err)?;
+        if branch.get().symbolic_target().is_some() {
+            continue;
+        }
+        let Some(name) = branch.name_bytes().ok().map(lossy) else {
+            continue;
+        };
+        let target = branch.get().target();
+        let upstream = branch.upstream().ok();
+        let counts = match (target, upstream.as_ref().and_then(|u| u.get().target())) {
+            (Some(local), Some(remote)) => repo.graph_ahead_behind(local, remote).ok(),
+            _ => None,
+        };
+        branches.push(BranchInfo {
+            name,
+            remote: kind == BranchType::Remote,
+            current: branch.is_head(),
+            target: target.map(|o| o.to_string()),
+            upstream: upstream.and_then(|u| u.name().ok().flatten().map(str::to_string)),
+            ahead: counts.map(|c| c.0),
+            behind: counts.map(|c| c.1),
+        });
+    }
+    branches.sort_by(|a, b| (a.remote, &a.name).cmp(&(b.remote, &b.name)));
+    Ok(branches)
+}
+
+/// A file's content at `rev`, or `None` if it doesn't exist there.
+pub fn show_file(working_directory: &str, rev: &str, path: &str) -> Result<Option<String>, String> {
+    let repo = open(working_directory)?;
+    let tree = tree_of(&repo, rev)?;
+    let entry = match tree.get_path(Path::new(path)) {
+        Ok(entry) => entry,
+        Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(None),
+        Err(e) => return Err(git_err(e)),
+    };
+    let blob = entry
+        .to_object(&repo)
+        .and_then(|o| o.peel_to_blob())
+        .map_err(|_| format!("{} is not a file at {}", path, rev))?;
+    if blob.size() > MAX_SHOW_SIZE {
+        return Err(format!(
+            "File too large ({:.1} MB). Maximum is {} MB.",
+            blob.size() as f64 / (1024.0 * 1024.0),
+            MAX_SHOW_SIZE / (1024 * 1024)
+        ));
+    }
+    if blob.is_binary() {
+        return Err(format!("{} is a binary file", path));
+    }
+    Ok(Some(lossy(blob.content())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::{IndexAddOption, RepositoryInitOptions, Signature};
+    use std::path::PathBuf;
+
+    /// A repository in a temp directory, removed on drop.
+    struct TestRepo {
+        dir: PathBuf,
+        repo: Repository,
+    }
+
+    impl TestRepo {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("stagehand-git-{}", uuid::Uuid::new_v4()));
+            let repo = Repository::init_opts(&dir, RepositoryInitOptions::new().initial_head("main")).unwrap();
+            Self { dir, repo }
+        }
+
+        fn path(&self) -> &str {
+            self.dir.to_str().unwrap()
+        }
+
+        fn write(&self, path: &str, content: impl AsRef<[u8]>) {
+            let file = self.dir.join(path);
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            std::fs::write(file, content).unwrap();
+        }
+
+        /// Stage everything and commit it on HEAD.
+        fn commit(&self, message: &str) -> Oid {
+            let mut index = self.repo.index().unwrap();
+            index.add_all(["*"], IndexAddOption::DEFAULT, None).unwrap();
+            index.update_all(["*"], None).unwrap();
+            index.write().unwrap();
+            let tree = self.repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let sig = Signature::now("Test", "test@example.com").unwrap();
+            let parent = self.repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+            let parents: Vec<&Commit> = parent.iter().collect();
+            self.repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parents).unwrap()
+        }
+    }
+
+    impl TestRepo {
+        fn checkout(&self, branch: &str) {
+            self.repo.set_head(&format!("refs/heads/{}", branch)).unwrap();
+            self.repo
+                .checkout_head(Some(git2::build::CheckoutBuilder::new().force()))
+                .unwrap();
+        }
+    }
+
+    impl Drop for TestRepo {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn line(kind: LineKind, content: &str) -> DiffLine {
+        DiffLine { kind, content: content.to_string(), old_lineno: None, new_lineno: None, words: None }
+    }
+
+    fn changed(spans: &[WordSpan]) -> Vec<(&str, bool)> {
+        spans.iter().map(|s| (s.text.as_str(), s.changed)).collect()
+    }
+
+    #[test]
+    fn conflict_hunks_ignore_underlines_and_longer_markers() {
+        let merged = "\
+Title
+=====
+<<<<<<< ours
+Usage
+==========
+|||||||| not a base marker
+||||||| base
+Use
+=======
+Usage
+-----
+>>>>>>>> not an end marker
+>>>>>>> theirs
+after
+<<<<<<<
+a
+=======
+b
+>>>>>>>
+";
+        let hunks = conflict_hunks(merged);
+        assert_eq!(hunks.len(), 2);
+
+        let first = &hunks[0];
+        assert_eq!(first.start_line, 3);
+        assert_eq!(first.ours, ["Usage", "==========", "|||||||| not a base marker"]);
+        assert_eq!(first.base, ["Use"]);
+        assert_eq!(first.theirs, ["Usage", "-----", ">>>>>>>> not an end marker"]);
+
+        // Counts the ours side of the first conflict.
+        let second = &hunks[1];
+        assert_eq!(second.start_line, 7);
+        assert_eq!(second.ours, ["a"]);
+        assert_eq!(second.theirs, ["b"]);
+        assert!(second.base.is_empty());
+    }
+
+    #[test]
+    fn markers_need_a_space_before_their_label() {
+        assert!(is_marker("<<<<<<< HEAD", "<<<<<<<"));
+        assert!(is_marker(">>>>>>>", ">>>>>>>"));
+        assert!(!is_marker("<<<<<<<HEAD", "<<<<<<<"));
+        assert!(!is_marker("======= ", "======="));
+        assert!(!is_marker("========", "======="));
+    }
+
+    #[test]
+    fn tokens_split_words_whitespace_and_punctuation() {
+        assert_eq!(tokenize("let x  = f(a_b);"), ["let", " ", "x", "  ", "=", " ", "f", "(", "a_b", ")", ";"]);
+        assert_eq!(tokenize("héllo, wörld"), ["héllo", ",", " ", "wörld"]);
+        assert_eq!(tokenize("日本語 テキスト"), ["日本語", " ", "テキスト"]);
+        assert_eq!(tokenize("a🎉🎉b"), ["a", "🎉", "🎉", "b"]);
+        assert!(tokenize("").is_empty());
+    }
+
+    #[test]
+    fn word_spans_mark_what_changed() {
+        let (old, new) = word_spans("let x = 1;", "let y = 1;").unwrap();
+        assert_eq!(changed(&old), [("let ", false), ("x", true), (" = 1;", false)]);
+        assert_eq!(changed(&new), [("let ", false), ("y", true), (" = 1;", false)]);
+
+        let (old, new) = word_spans("naïve café", "naïve crème café").unwrap();
+        assert_eq!(changed(&old), [("naïve café", false)]);
+        assert_eq!(changed(&new), [("naïve ", false), ("crème ", true), ("café", false)]);
+
+        // Nothing in common, or nothing on one side.
+        assert!(word_spans("foo", "bar").is_none());
+        assert!(word_spans("", "bar").is_none());
+    }
+
+    #[test]
+    fn word_spans_skip_lines_over_the_cell_limit() {
+        // Each "a " is two tokens; two lines of `side` words fill the grid exactly.
+        let words = |n: usize| "a ".repeat(n);
+        let side = (MAX_WORD_DIFF_CELLS as f64).sqrt() as usize / 2;
+        assert!(word_spans(&words(side), &format!("b {}", words(side - 1))).is_some());
+        assert!(word_spans(&words(side), &words(side + 1)).is_none());
+    }
+
+    #[test]
+    fn word_diff_pairs_uneven_runs_line_by_line() {
+        let mut lines = vec![
+            line(LineKind::Context, "fn main() {"),
+            line(LineKind::Deletion, "    let a = 1;"),
+            line(LineKind::Deletion, "    let b = 2;"),
+            line(LineKind::Addition, "    let a = 10;"),
+            line(LineKind::Context, "}"),
+            line(LineKind::Deletion, "old"),
+            line(LineKind::Addition, "new"),
+            line(LineKind::Addition, "    let c = 3;"),
+        ];
+        diff_words(&mut lines);
+        let words: Vec<bool> = lines.iter().map(|l| l.words.is_some()).collect();
+        // The second deletion has no addition to pair with; "old" and "new"
+        // share nothing; the extra addition has no pair.
+        assert_eq!(words, [false, true, false, true, false, false, false, false]);
+        assert_eq!(
+            changed(lines[3].words.as_ref().unwrap()),
+            [("    let a = ", false), ("10", true), (";", false)]
+        );
+    }
+
+    fn spec() -> DiffSpec<'static> {
+        DiffSpec::default()
+    }
+
+    #[test]
+    fn max_file_bytes_leaves_large_files_out_but_keeps_binary_ones() {
+        let repo = TestRepo::new();
+        repo.write("small.txt", "one\n");
+        repo.write("large.txt", "line\n".repeat(20));
+        repo.write("image.bin", b"\0\x01\x02");
+        repo.commit("first");
+        repo.write("small.txt", "two\n");
+        repo.write("large.txt", "changed\n".repeat(20));
+        repo.write("image.bin", b"\0\x03\x04");
+
+        let files = diff(repo.path(), DiffSpec { max_file_bytes: Some(50), ..spec() }).unwrap();
+        let file = |path: &str| files.iter().find(|f| f.path == path).unwrap();
+        let small = file("small.txt");
+        assert!(!small.too_large && !small.binary);
+        assert_eq!((small.additions, small.deletions, small.hunks.len()), (1, 1, 1));
+        let large = file("large.txt");
+        assert!(large.too_large && !large.binary);
+        assert_eq!((large.additions, large.deletions, large.hunks.len()), (0, 0, 0));
+        let image = file("image.bin");
+        assert!(image.binary && !image.too_large);
+        assert!(image.hunks.is_empty());
+
+        let files = diff(repo.path(), spec()).unwrap();
+        assert!(files.iter().all(|f| !f.too_large));
+        assert_eq!(files.iter().find(|f| f.path == "large.txt").unwrap().additions, 20);
+    }
+
+    #[test]
+    fn ignore_whitespace_drops_whitespace_only_files() {
+        let repo = TestRepo::new();
+        repo.write("spaces.txt", "a b\n");
+        repo.write("real.txt", "a b\n");
+        repo.commit("first");
+        repo.write("spaces.txt", "a    b\t\n");
+        repo.write("real.txt", "a c\n");
+
+        let paths = |ignore_whitespace| -> Vec<String> {
+            diff(repo.path(), DiffSpec { ignore_whitespace, ..spec() })
+                .unwrap()
+                .into_iter()
+                .map(|f| f.path)
+                .collect()
+        };
+        assert_eq!(paths(false), ["real.txt", "spaces.txt"]);
+        assert_eq!(paths(true), ["real.txt"]);
+    }
+
+    fn subjects(commits: Vec<CommitInfo>) -> Vec<String> {
+        let mut subjects: Vec<String> = commits.into_iter().map(|c| c.subject).collect();
+        subjects.sort();
+        subjects
+    }
+
+    #[test]
+    fn log_ranges() {
+        let repo = TestRepo::new();
+        repo.write("a.txt", "a\n");
+        let first = repo.commit("first");
+        repo.repo.branch("feature", &repo.repo.find_commit(first).unwrap(), false).unwrap();
+        repo.write("m.txt", "m\n");
+        repo.commit("on main\n\nWith a body.\n");
+        repo.checkout("feature");
+        repo.write("f.txt", "f\n");
+        repo.commit("on feature");
+        repo.checkout("main");
+
+        let log = |range: Option<&str>| subjects(log(repo.path(), range, None).unwrap());
+        assert_eq!(log(None), ["first", "on main"]);
+        assert_eq!(log(Some("feature")), ["first", "on feature"]);
+        assert_eq!(log(Some("main..feature")), ["on feature"]);
+        assert_eq!(log(Some("feature..main")), ["on main"]);
+        assert_eq!(log(Some("main...feature")), ["on feature", "on main"]);
+        // An empty side is HEAD, which is main.
+        assert_eq!(log(Some("..feature")), ["on feature"]);
+        assert_eq!(log(Some("feature..")), ["on main"]);
+        assert!(log(Some("main..main")).is_empty());
+        assert!(super::log(repo.path(), Some("nope..main"), None).is_err());
+
+        let commits = super::log(repo.path(), Some("feature..main"), Some(1)).unwrap();
+        assert_eq!(commits[0].body, "With a body.");
+        assert_eq!(commits[0].parents, [first.to_string()]);
+        assert_eq!(super::log(repo.path(), None, Some(1)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn empty_repo_has_a_branch_but_no_head_or_log() {
+        let repo = TestRepo::new();
+        repo.write("new.txt", "x\n");
+        let status = status(repo.path()).unwrap();
+        assert_eq!(status.branch.as_deref(), Some("main"));
+        assert!(status.head.is_none());
+        assert_eq!(status.files.len(), 1);
+        assert_eq!(status.files[0].unstaged, Some(FileChange::Untracked));
+        assert!(log(repo.path(), None, None).unwrap().is_empty());
+        assert!(log(repo.path(), Some("HEAD"), None).is_err());
+    }
+
+    #[test]
+    fn status_lists_staged_renames_and_untracked_files() {
+        let repo = TestRepo::new();
+        repo.write("old.txt", "one\ntwo\nthree\nfour\n");
+        repo.commit("first");
+        std::fs::rename(repo.dir.join("old.txt"), repo.dir.join("new.txt")).unwrap();
+        let mut index = repo.repo.index().unwrap();
+        index.remove_path(Path::new("old.txt")).unwrap();
+        index.add_path(Path::new("new.txt")).unwrap();
+        index.write().unwrap();
+        repo.write("dir/a.txt", "a\n");
+        repo.write("dir/sub/b.txt", "b\n");
+
+        let status = status(repo.path()).unwrap();
+        let mut files: Vec<_> = status
+            .files
+            .iter()
+            .map(|f| (f.path.as_str(), f.old_path.as_deref(), f.staged, f.unstaged))
+            .collect();
+        files.sort_by_key(|f| f.0);
+        assert_eq!(
+            files,
+            [
+                ("dir/a.txt", None, None, Some(FileChange::Untracked)),
+                ("dir/sub/b.txt", None, None, Some(FileChange::Untracked)),
+                ("new.txt", Some("old.txt"), Some(FileChange::Renamed), None),
+            ]
+        );
+    }
+}
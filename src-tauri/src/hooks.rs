@@ -0,0 +1,330 @@
+use crate::env_profiles::ResolvedEnv;
+use crate::events::AgentStreamEvent;
+use crate::process_manager::ProcessManager;
+use crate::signals::{self, Signal};
+use crate::store;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tauri::ipc::Channel;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::{oneshot, Mutex};
+
+/// Lines of hook output kept for failure messages and agent feedback.
+const OUTPUT_TAIL_LINES: usize = 200;
+
+const DEFAULT_MAX_FEEDBACK_ROUNDS: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookPhase {
+    Pre,
+    Post,
+}
+
+/// What to do when a hook exits non-zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookFailurePolicy {
+    /// Fail the run.
+    #[default]
+    Abort,
+    /// Report the failure and carry on.
+    Warn,
+    /// Hand the hook output to the agent: prepended to the prompt for
+    /// pre-run hooks, sent as a follow-up turn for post-run hooks.
+    Feedback,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HookCommand {
+    /// Shell command, run with `sh -c` in the agent's working directory.
+    pub command: String,
+    #[serde(default)]
+    pub on_failure: HookFailurePolicy,
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HookSet {
+    #[serde(default)]
+    pub pre: Vec<HookCommand>,
+    #[serde(default)]
+    pub post: Vec<HookCommand>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectHooks {
+    /// Hooks around every agent run in the project.
+    #[serde(default)]
+    pub project: HookSet,
+    /// Extra hooks per stage template id, run after the project hooks.
+    #[serde(default)]
+    pub stages: HashMap<String, HookSet>,
+    /// How many times post-run feedback may send the agent back to work.
+    pub max_feedback_rounds: Option<u32>,
+}
+
+/// Hooks that apply to one agent run.
+#[derive(Debug, Clone, Default)]
+pub struct ResolvedHooks {
+    pub pre: Vec<HookCommand>,
+    pub post: Vec<HookCommand>,
+    pub max_feedback_rounds: u32,
+}
+
+/// Persists lifecycle hooks per project in `~/.stagehand/data/hooks.json`.
+pub struct HookStore {
+    path: Option<PathBuf>,
+    lock: Mutex<()>,
+}
+
+impl HookStore {
+    pub fn new() -> Self {
+        Self {
+            path: store::data_file("hooks.json"),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn load(&self) -> Result<HashMap<String, ProjectHooks>, String> {
+        let path = self.path.as_deref().ok_or("Could not find home directory")?;
+        store::read_json(path)
+    }
+
+    pub async fn get(&self, project_id: &str) -> Result<ProjectHooks, String> {
+        let _guard = self.lock.lock().await;
+        Ok(self.load()?.remove(project_id).unwrap_or_default())
+    }
+
+    pub async fn set(&self, project_id: &str, hooks: ProjectHooks) -> Result<(), String> {
+        let _guard = self.lock.lock().await;
+        let path = self.path.as_deref().ok_or("Could not find home directory")?;
+        let _file = store::lock_json_async(path).await?;
+        let mut all = self.load()?;
+        all.insert(project_id.to_string(), hooks);
+        store::write_json(path, &all)
+    }
+
+    /// Project hooks followed by the stage's own hooks.
+    pub async fn resolve(&self, project_id: &str, stage_id: Option<&str>) -> Result<ResolvedHooks, String> {
+        let mut hooks = self.get(project_id).await?;
+        let mut resolved = ResolvedHooks {
+            pre: hooks.project.pre,
+            post: hooks.project.post,
+            max_feedback_rounds: hooks.max_feedback_rounds.unwrap_or(DEFAULT_MAX_FEEDBACK_ROUNDS),
+        };
+        if let Some(stage) = stage_id.and_then(|id| hooks.stages.remove(id)) {
+            resolved.pre.extend(stage.pre);
+            resolved.post.extend(stage.post);
+        }
+        Ok(resolved)
+    }
+}
+
+pub struct HookResult {
+    pub exit_code: Option<i32>,
+    /// Last lines of combined stdout/stderr.
+    pub output: String,
+}
+
+impl HookResult {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// Kill the hook's shell and everything it started.
+async fn kill_group(child: &mut Child) {
+    if let Some(pid) = child.id() {
+        let _ = signals::signal_group(pid, Signal::Kill);
+    }
+    let _ = child.kill().await;
+}
+
+/// Start a hook in its own process group, so it can be paused and killed
+/// together with everything it starts.
+pub fn spawn_hook(
+    hook: &HookCommand,
+    working_directory: Option<&str>,
+    env: Option<&ResolvedEnv>,
+) -> Result<Child, String> {
+    let mut cmd = if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        c.arg("/C").arg(&hook.command);
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.arg("-c").arg(&hook.command);
+        c
+    };
+    if let Some(env) = env {
+        env.apply_to_command(&mut cmd);
+    }
+    if let Some(dir) = working_directory {
+        cmd.current_dir(dir);
+    }
+    cmd.stdin(std::process::Stdio::null());
+    cmd.stdout(std::process::Stdio::piped());
+    cmd.stderr(std::process::Stdio::piped());
+    cmd.kill_on_drop(true);
+    #[cfg(unix)]
+    cmd.process_group(0);
+
+    cmd.spawn().map_err(|e| format!("Failed to run hook: {}", e))
+}
+
+/// Resolves once `timeout` has passed for the hook, not counting time the
+/// run spent paused after `started`.
+async fn hook_deadline(pm: &ProcessManager, process_id: &str, timeout: Option<Duration>) {
+    let Some(timeout) = timeout else {
+        return std::future::pending().await;
+    };
+    let started = Instant::now();
+    let paused_before = pm.paused_for(process_id).await;
+    loop {
+        let paused = pm.paused_for(process_id).await.saturating_sub(paused_before);
+        let left = (timeout + paused).saturating_sub(started.elapsed());
+        if left.is_zero() {
+            return;
+        }
+        tokio::time::sleep(left).await;
+    }
+}
+
+/// Wait for a hook started with `spawn_hook`, streaming its output as
+/// `HookOutput` events. Returns `None` if the run was killed while the hook
+/// was running.
+pub async fn run_hook(
+    hook: &HookCommand,
+    phase: HookPhase,
+    process_id: &str,
+    mut child: Child,
+    on_event: &Channel<AgentStreamEvent>,
+    kill_rx: &mut oneshot::Receiver<()>,
+    pm: &ProcessManager,
+) -> Option<HookResult> {
+    let tail = std::sync::Arc::new(std::sync::Mutex::new(VecDeque::new()));
+    let mut readers = Vec::new();
+    let streams: [(&str, Option<Box<dyn tokio::io::AsyncRead + Send + Unpin>>); 2] = [
+        ("stdout", child.stdout.take().map(|s| Box::new(s) as _)),
+        ("stderr", child.stderr.take().map(|s| Box::new(s) as _)),
+    ];
+    for (stream, pipe) in streams {
+        let Some(pipe) = pipe else { continue };
+        let tail = tail.clone();
+        let events = on_event.clone();
+        let process_id = process_id.to_string();
+        let command = hook.command.clone();
+        readers.push(tokio::spawn(async move {
+            let mut lines = BufReader::new(pipe).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                {
+                    let mut tail = tail.lock().unwrap();
+                    if tail.len() == OUTPUT_TAIL_LINES {
+                        tail.pop_front();
+                    }
+                    tail.push_back(line.clone());
+                }
+                let _ = events.send(AgentStreamEvent::HookOutput {
+                    process_id: process_id.clone(),
+                    phase,
+                    command: command.clone(),
+                    stream: stream.to_string(),
+                    line,
+                });
+            }
+        }));
+    }
+
+    // Time paused doesn't count, or pausing a run could time out its hook.
+    let timeout = hook.timeout_secs.map(Duration::from_secs);
+    let exit_code = tokio::select! {
+        status = child.wait() => status.ok().and_then(|s| s.code()),
+        _ = hook_deadline(pm, process_id, timeout) => {
+            kill_group(&mut child).await;
+            None
+        }
+        _ = kill_rx => {
+            kill_group(&mut child).await;
+            return None;
+        }
+    };
+
+    for reader in readers {
+        let _ = reader.await;
+    }
+
+    let output = tail.lock().unwrap().iter().cloned().collect::<Vec<_>>().join("\n");
+    let _ = on_event.send(AgentStreamEvent::HookFinished {
+        process_id: process_id.to_string(),
+        phase,
+        command: hook.command.clone(),
+        exit_code,
+    });
+    Some(HookResult { exit_code, output })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::agents::Agent;
+    use crate::process_manager::ProcessEntry;
+    use crate::temp_context::{SharedTempContext, TempContext};
+
+    #[tokio::test]
+    async fn time_paused_does_not_count_towards_the_timeout() {
+        let pm = ProcessManager::new();
+        let (kill_tx, mut kill_rx) = oneshot::channel();
+        let temp = TempContext::new(&format!("hook-test-{}", uuid::Uuid::new_v4())).unwrap();
+        let temp = SharedTempContext::new(temp);
+        let entry = ProcessEntry {
+            agent: Agent::Claude,
+            pid: None,
+            hook_pgid: None,
+            events: Channel::new(|_| Ok(())),
+            kill_tx: Some(kill_tx),
+            stage_execution_id: None,
+            session_id: None,
+            stdin: None,
+            input_closed: false,
+            paused_since: None,
+            paused_total: Duration::ZERO,
+            resumed_at_ms: None,
+            temp: temp.clone(),
+            resume: Default::default(),
+            handed_over_to: None,
+        };
+        pm.register("p".to_string(), entry).await;
+
+        // `sleep` keeps its wall-clock deadline through a stop, so the hook
+        // ends about 1.3s after it is waited on: past its timeout, but not
+        // past the timeout plus the pause.
+        let hook = HookCommand {
+            command: "sleep 1.5".to_string(),
+            on_failure: HookFailurePolicy::Abort,
+            timeout_secs: Some(1),
+        };
+        let child = spawn_hook(&hook, None, None).unwrap();
+        pm.set_hook_group("p", child.id()).await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        pm.pause("p").await.unwrap();
+        let resume = {
+            let pm = pm.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                pm.resume("p").await.unwrap();
+            })
+        };
+        let events = Channel::new(|_| Ok(()));
+        let result = run_hook(&hook, HookPhase::Pre, "p", child, &events, &mut kill_rx, &pm).await;
+        resume.await.unwrap();
+        assert_eq!(result.unwrap().exit_code, Some(0));
+        temp.cleanup();
+    }
+}
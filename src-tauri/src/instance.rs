@@ -0,0 +1,139 @@
+use crate::file_lock::FileLock;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// Locked by an instance for as long as it runs, so a temp root whose lock
+/// can be taken belongs to an instance that is gone, whatever became of its
+/// pid. Also holds the pid, for people reading the directory.
+const LOCK_FILE: &str = "instance.lock";
+
+/// Held while an instance claims its temp root and sweeps the others, so
+/// one starting up is never mistaken for one that has exited.
+const SWEEP_LOCK: &str = ".lock";
+
+/// Temp dirs from versions that didn't scope them per instance have no
+/// owner to check, so they are only removed once nothing has touched them
+/// for this long.
+const UNOWNED_STALE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// This instance's lock, held until `release`.
+static LOCK: Mutex<Option<FileLock>> = Mutex::new(None);
+
+fn tmp_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|h| h.join(".stagehand").join("tmp"))
+}
+
+/// This instance's temp root, `~/.stagehand/tmp/<pid>/`. Every temp dir the
+/// instance creates lives under it, so other instances running at the same
+/// time (a dev build next to a release build, say) never touch it.
+pub fn temp_root() -> Option<&'static Path> {
+    static ROOT: OnceLock<Option<PathBuf>> = OnceLock::new();
+    ROOT.get_or_init(|| tmp_dir().map(|t| t.join(std::process::id().to_string())))
+        .as_deref()
+}
+
+/// Whether the instance with this pid is still running. A pid that was
+/// reused by some other program doesn't count.
+pub fn running(pid: u32) -> bool {
+    let Some(tmp) = tmp_dir() else { return true };
+    held(&tmp.join(pid.to_string()))
+}
+
+/// Whether some running instance holds the lock in `dir`. Dirs without a
+/// lock file aren't held; errors count as held so nothing is removed on a
+/// guess.
+fn held(dir: &Path) -> bool {
+    let lock = dir.join(LOCK_FILE);
+    if !lock.exists() {
+        return false;
+    }
+    !matches!(FileLock::try_acquire(&lock), Ok(Some(_)))
+}
+
+/// Claim this instance's temp root and remove the ones left behind by
+/// instances that are no longer running.
+pub fn init() {
+    let (Some(tmp), Some(root)) = (tmp_dir(), temp_root()) else {
+        return;
+    };
+    let _sweep = match FileLock::acquire(&tmp.join(SWEEP_LOCK)) {
+        Ok(lock) => lock,
+        Err(e) => {
+            log::warn!("Failed to claim instance temp dir: {}", e);
+            return;
+        }
+    };
+    match claim(root) {
+        Ok(lock) => *LOCK.lock().unwrap() = Some(lock),
+        Err(e) => log::warn!("Failed to create instance temp dir {:?}: {}", root, e),
+    }
+
+    let Ok(entries) = std::fs::read_dir(&tmp) else { return };
+    for entry in entries.flatten() {
+        let dir = entry.path();
+        if dir == root || !dir.is_dir() {
+            continue;
+        }
+        let stale = if dir.join(LOCK_FILE).exists() {
+            let live = held(&dir);
+            if live {
+                log::info!("Another Stagehand instance ({:?}) shares ~/.stagehand", entry.file_name());
+            }
+            !live
+        } else {
+            entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.elapsed().ok())
+                .is_some_and(|age| age > UNOWNED_STALE_AFTER)
+        };
+        if stale {
+            match std::fs::remove_dir_all(&dir) {
+                Ok(()) => log::info!("Cleaned up stale temp dir: {:?}", dir),
+                Err(e) => log::warn!("Failed to remove stale temp dir {:?}: {}", dir, e),
+            }
+        }
+    }
+}
+
+/// Create `root` and lock it for the life of the instance. A root left by
+/// an earlier process with the same pid is reused.
+fn claim(root: &Path) -> Result<FileLock, String> {
+    std::fs::create_dir_all(root).map_err(|e| e.to_string())?;
+    let path = root.join(LOCK_FILE);
+    let lock = FileLock::acquire(&path)?;
+    std::fs::write(&path, std::process::id().to_string()).map_err(|e| e.to_string())?;
+    Ok(lock)
+}
+
+/// Remove this instance's temp root on a clean exit.
+pub fn release() {
+    if let Some(root) = temp_root() {
+        let _ = std::fs::remove_dir_all(root);
+    }
+    LOCK.lock().unwrap().take();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_locked_dirs_are_held() {
+        let dir = std::env::temp_dir().join(format!("stagehand-instance-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(!held(&dir));
+
+        // A lock file by itself, as a crashed instance leaves it.
+        std::fs::write(dir.join(LOCK_FILE), "12345").unwrap();
+        assert!(!held(&dir));
+
+        let lock = FileLock::acquire(&dir.join(LOCK_FILE)).unwrap();
+        assert!(held(&dir));
+        drop(lock);
+        assert!(!held(&dir));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
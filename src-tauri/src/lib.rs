@@ -1,11 +1,46 @@
+mod agent_args;
 mod agents;
+mod attention;
+mod command_history;
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench;
+mod env_profiles;
+mod file_lock;
+mod git_repo;
+mod hooks;
+mod instance;
 mod process_manager;
 mod pty_manager;
+mod recording;
+#[cfg(unix)]
+pub mod ptyd;
+#[cfg(not(unix))]
+#[path = "ptyd_unsupported.rs"]
+pub mod ptyd;
 mod events;
 mod commands;
+mod retry;
+mod shell_integration;
+mod shutdown;
+mod signals;
+mod store;
+mod task_overlap;
+mod temp_context;
+mod terminal_profiles;
+mod utf8_decoder;
+mod workdir_overlay;
+mod worktree_manager;
 
+use attention::AttentionStore;
+use env_profiles::EnvProfileStore;
+use hooks::HookStore;
 use process_manager::ProcessManager;
 use pty_manager::PtyManager;
+use recording::Playbacks;
+use task_overlap::OverlapWatches;
+use terminal_profiles::TerminalProfileStore;
+use worktree_manager::WorktreeManager;
 use tauri::Manager;
 use tauri::menu::{MenuBuilder, SubmenuBuilder};
 
@@ -59,7 +94,7 @@ fn fix_path_env() {
     #[cfg(not(target_os = "windows"))]
     {
         use std::process::Command;
-        let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/zsh".to_string());
+        let shell = commands::pty::default_shell();
         if let Ok(output) = Command::new(&shell)
             .args(["-ilc", "echo $PATH"])
             .output()
@@ -85,6 +120,13 @@ pub fn run() {
         .plugin(tauri_plugin_notification::init())
         .manage(ProcessManager::new())
         .manage(PtyManager::new())
+        .manage(Playbacks::new())
+        .manage(EnvProfileStore::new())
+        .manage(HookStore::new())
+        .manage(TerminalProfileStore::new())
+        .manage(AttentionStore::new())
+        .manage(WorktreeManager::new())
+        .manage(OverlapWatches::new())
         .setup(|app| {
             // Build a custom menu without the Close Window (Cmd+W) shortcut
             // so that Cmd+W can be handled by the frontend to close editor tabs.
@@ -127,18 +169,32 @@ pub fn run() {
                 )?;
             }
 
+            shutdown::listen_for_sigterm(app.handle().clone());
+
+            // Claim this instance's temp dir and clean up after dead ones
+            instance::init();
+
+            // Put back working-directory files a crashed instance layered
+            // agent config onto.
+            workdir_overlay::recover_stale();
+
+            let ptys = app.state::<PtyManager>().inner().clone();
+            if let Err(e) = app
+                .state::<AttentionStore>()
+                .load()
+                .and_then(|matchers| ptys.set_attention_matchers(&matchers))
+            {
+                log::warn!("Failed to load attention matchers: {}", e);
+            }
+
+            // Pick up persistent terminal sessions that outlived the last run
+            tauri::async_runtime::spawn(async move { ptys.connect_daemon().await });
+
             // Create ~/.stagehand/data/ directory
             if let Some(home) = dirs::home_dir() {
                 let stagehand_dir = home.join(".stagehand").join("data");
                 std::fs::create_dir_all(&stagehand_dir).ok();
                 log::info!("Stagehand data dir: {:?}", stagehand_dir);
-
-                // Clean up stale temp dirs from crashed processes
-                let tmp_dir = home.join(".stagehand").join("tmp");
-                if tmp_dir.exists() {
-                    std::fs::remove_dir_all(&tmp_dir).ok();
-                    log::info!("Cleaned up stale temp dir: {:?}", tmp_dir);
-                }
             }
 
             Ok(())
@@ -149,10 +205,32 @@ pub fn run() {
             get_mcp_server_path,
             commands::process::spawn_agent,
             commands::process::kill_process,
+            commands::process::send_agent_message,
+            commands::process::close_agent_input,
+            commands::process::pause_process,
+            commands::process::resume_process,
             commands::process::list_processes,
             commands::process::list_processes_detailed,
+            commands::process::takeover_process,
             commands::process::check_agent_available,
             commands::git::run_git_command,
+            commands::git::git_status,
+            commands::git::git_diff,
+            commands::git::task_diff,
+            commands::git::check_merge_conflicts,
+            commands::overlap::task_overlaps,
+            commands::overlap::watch_task_overlaps,
+            commands::overlap::update_overlap_watch,
+            commands::overlap::stop_overlap_watch,
+            commands::git::git_log,
+            commands::git::git_branches,
+            commands::git::git_show_file,
+            commands::worktree::create_worktree,
+            commands::worktree::list_worktrees,
+            commands::worktree::lock_worktree,
+            commands::worktree::unlock_worktree,
+            commands::worktree::prune_worktrees,
+            commands::worktree::remove_worktree,
             commands::git::run_gh_command,
             commands::git::read_file_contents,
             commands::git::read_file_base64,
@@ -160,14 +238,48 @@ pub fn run() {
             commands::pty::spawn_pty,
             commands::pty::write_to_pty,
             commands::pty::resize_pty,
+            commands::pty::pause_pty,
+            commands::pty::resume_pty,
             commands::pty::kill_pty,
+            commands::pty::signal_pty,
+            commands::pty::pty_foreground,
+            commands::pty::list_ptys,
+            commands::pty::persistent_terminals_available,
+            commands::pty::attach_pty,
+            commands::pty::detach_pty,
+            commands::pty::list_terminal_profiles,
+            commands::pty::save_terminal_profile,
+            commands::pty::delete_terminal_profile,
+            commands::pty::pty_snapshot,
+            commands::pty::list_command_history,
+            commands::pty::list_attention_matchers,
+            commands::pty::save_attention_matchers,
+            commands::recording::list_recordings,
+            commands::recording::play_recording,
+            commands::recording::stop_playback,
             commands::editor::open_in_external_editor,
+            commands::env::list_env_profiles,
+            commands::env::save_env_profile,
+            commands::env::delete_env_profile,
+            commands::hooks::get_project_hooks,
+            commands::hooks::save_project_hooks,
         ])
-        .on_window_event(|_window, event| {
+        .on_page_load(|webview, payload| {
+            // A reload drops the channels of the old page's watches.
+            if payload.event() == tauri::webview::PageLoadEvent::Started {
+                webview.state::<OverlapWatches>().stop_all();
+            }
+        })
+        .on_window_event(|window, event| {
             if let tauri::WindowEvent::Destroyed = event {
-                // Process cleanup happens via Drop
+                shutdown::shutdown_blocking(window.app_handle(), "window closed");
             }
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::ExitRequested { .. } | tauri::RunEvent::Exit = event {
+                shutdown::shutdown_blocking(app, "app exit");
+            }
+        });
 }
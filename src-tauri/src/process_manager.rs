@@ -1,42 +1,113 @@
+use crate::agents::Agent;
+use crate::events::AgentStreamEvent;
+use crate::shutdown::{self, ShutdownReport};
+use crate::signals::{self, Signal};
+use crate::temp_context::SharedTempContext;
+use serde::Serialize;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::ipc::Channel;
+use tokio::io::AsyncWriteExt;
+use tokio::process::ChildStdin;
 use tokio::sync::{oneshot, Mutex};
 
+/// Spawn settings an interactive terminal needs to continue a run's
+/// session; see `begin_handover`.
+#[derive(Debug, Clone, Default)]
+pub struct ResumeContext {
+    pub working_directory: Option<String>,
+    pub persona_model: Option<String>,
+    pub append_system_prompt: Option<String>,
+    pub mcp_config: Option<String>,
+    pub project_id: Option<String>,
+    pub env_profile: Option<String>,
+    pub allowed_tools: Option<Vec<String>>,
+    pub max_turns: Option<u32>,
+    /// The run's session was never saved, so there is nothing to resume.
+    pub no_session_persistence: bool,
+}
+
+/// A run being handed over to a PTY session, returned by `begin_handover`.
+#[derive(Debug, Clone)]
+pub struct Handover {
+    pub agent: Agent,
+    pub session_id: String,
+    pub stage_execution_id: Option<String>,
+    pub resume: ResumeContext,
+}
+
 pub struct ProcessEntry {
+    pub agent: Agent,
+    /// OS pid of the agent, which also leads its own process group.
+    pub pid: Option<u32>,
+    /// Process group of the lifecycle hook running instead of the agent.
+    pub hook_pgid: Option<u32>,
+    pub events: Channel<AgentStreamEvent>,
     pub kill_tx: Option<oneshot::Sender<()>>,
     pub stage_execution_id: Option<String>,
-    #[allow(dead_code)]
     pub session_id: Option<String>,
+    /// Piped stdin for agents started with streaming input. Kept behind its own
+    /// lock so a slow write never blocks the process table.
+    pub stdin: Option<Arc<Mutex<ChildStdin>>>,
+    /// `close_input` has dropped `stdin`.
+    pub input_closed: bool,
+    /// Set while the process group is stopped with SIGSTOP.
+    pub paused_since: Option<Instant>,
+    /// Time spent paused before the current pause, if any.
+    pub paused_total: Duration,
+    /// Wall-clock time (ms since epoch) of the last resume, so inactivity
+    /// timeouts can restart from there instead of counting paused time.
+    pub resumed_at_ms: Option<u64>,
+    /// Temp and working-directory files written for the run.
+    pub temp: SharedTempContext,
+    pub resume: ResumeContext,
+    /// PTY session that took the run over, once `begin_handover` is called.
+    pub handed_over_to: Option<String>,
+}
+
+impl ProcessEntry {
+    /// The process group currently doing the run's work: a running hook,
+    /// otherwise the agent.
+    fn active_group(&self) -> Option<u32> {
+        self.hook_pgid.or(self.pid)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessSummary {
+    pub process_id: String,
+    pub stage_execution_id: Option<String>,
+    pub paused: bool,
+    pub resumed_at_ms: Option<u64>,
+    /// PTY session continuing this run, while it is being taken over.
+    pub handed_over_to: Option<String>,
 }
 
 #[derive(Clone)]
 pub struct ProcessManager {
     processes: Arc<Mutex<HashMap<String, ProcessEntry>>>,
+    shutting_down: Arc<AtomicBool>,
 }
 
 impl ProcessManager {
     pub fn new() -> Self {
         Self {
             processes: Arc::new(Mutex::new(HashMap::new())),
+            shutting_down: Arc::new(AtomicBool::new(false)),
         }
     }
 
-    pub async fn register(
-        &self,
-        process_id: String,
-        kill_tx: oneshot::Sender<()>,
-        stage_execution_id: Option<String>,
-        session_id: Option<String>,
-    ) {
+    /// Set once `shutdown` starts; no new runs or retries are started after.
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+
+    pub async fn register(&self, process_id: String, entry: ProcessEntry) {
         let mut procs = self.processes.lock().await;
-        procs.insert(
-            process_id,
-            ProcessEntry {
-                kill_tx: Some(kill_tx),
-                stage_execution_id,
-                session_id,
-            },
-        );
+        procs.insert(process_id, entry);
     }
 
     pub async fn remove(&self, process_id: &str) {
@@ -47,6 +118,12 @@ impl ProcessManager {
     pub async fn kill(&self, process_id: &str) -> Result<(), String> {
         let mut procs = self.processes.lock().await;
         if let Some(entry) = procs.get_mut(process_id) {
+            // Stopped children left behind by the kill would never exit.
+            if entry.paused_since.take().is_some() {
+                if let Some(pgid) = entry.active_group() {
+                    let _ = signals::signal_group(pgid, Signal::Cont);
+                }
+            }
             if let Some(tx) = entry.kill_tx.take() {
                 tx.send(()).map_err(|_| "Process already exited".to_string())?;
                 Ok(())
@@ -58,14 +135,258 @@ impl ProcessManager {
         }
     }
 
-    pub async fn kill_all(&self) {
+    /// Point the entry at the process of a new retry attempt.
+    pub async fn replace_attempt(
+        &self,
+        process_id: &str,
+        pid: Option<u32>,
+        stdin: Option<Arc<Mutex<ChildStdin>>>,
+    ) {
         let mut procs = self.processes.lock().await;
-        for (_id, entry) in procs.iter_mut() {
-            if let Some(tx) = entry.kill_tx.take() {
-                let _ = tx.send(());
+        if let Some(entry) = procs.get_mut(process_id) {
+            entry.pid = pid;
+            entry.hook_pgid = None;
+            entry.stdin = stdin;
+            entry.paused_since = None;
+        }
+    }
+
+    /// Record the process group of the hook that has just started, or
+    /// `None` once it has finished. A hook started while the run is paused
+    /// is stopped straight away.
+    pub async fn set_hook_group(&self, process_id: &str, pgid: Option<u32>) {
+        let mut procs = self.processes.lock().await;
+        if let Some(entry) = procs.get_mut(process_id) {
+            entry.hook_pgid = pgid;
+            if let (Some(pgid), Some(_)) = (pgid, entry.paused_since) {
+                let _ = signals::signal_group(pgid, Signal::Stop);
             }
         }
-        procs.clear();
+    }
+
+    /// How long the run has spent paused so far, the current pause included.
+    pub async fn paused_for(&self, process_id: &str) -> Duration {
+        let procs = self.processes.lock().await;
+        procs.get(process_id).map_or(Duration::ZERO, |entry| {
+            entry.paused_total + entry.paused_since.map_or(Duration::ZERO, |since| since.elapsed())
+        })
+    }
+
+    /// Stop the agent's process group, or the running hook's, with SIGSTOP.
+    pub async fn pause(&self, process_id: &str) -> Result<(), String> {
+        let mut procs = self.processes.lock().await;
+        let entry = procs.get_mut(process_id).ok_or("Process not found")?;
+        if entry.paused_since.is_some() {
+            return Err("Process is already paused".to_string());
+        }
+        let pgid = entry.active_group().ok_or("Process has no pid")?;
+        signals::signal_group(pgid, Signal::Stop)?;
+        entry.paused_since = Some(Instant::now());
+        let _ = entry.events.send(AgentStreamEvent::Paused {
+            process_id: process_id.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Continue a paused run's process group with SIGCONT.
+    pub async fn resume(&self, process_id: &str) -> Result<(), String> {
+        let mut procs = self.processes.lock().await;
+        let entry = procs.get_mut(process_id).ok_or("Process not found")?;
+        let paused_since = entry.paused_since.ok_or("Process is not paused")?;
+        let pgid = entry.active_group().ok_or("Process has no pid")?;
+        signals::signal_group(pgid, Signal::Cont)?;
+        entry.paused_since = None;
+        entry.paused_total += paused_since.elapsed();
+        entry.resumed_at_ms = Some(now_ms());
+        let _ = entry.events.send(AgentStreamEvent::Resumed {
+            process_id: process_id.to_string(),
+            paused_ms: paused_since.elapsed().as_millis() as u64,
+        });
+        Ok(())
+    }
+
+    /// Write a single line to the process's stdin and flush it.
+    pub async fn write_line(&self, process_id: &str, line: &str) -> Result<(), String> {
+        let stdin = {
+            let procs = self.processes.lock().await;
+            let entry = procs.get(process_id).ok_or("Process not found")?;
+            match entry.stdin {
+                Some(ref stdin) => stdin.clone(),
+                None if entry.input_closed => return Err("Process input is closed".to_string()),
+                None if entry.agent.supports_stream_input() => {
+                    return Err("Process was not started with streaming input".to_string())
+                }
+                None => {
+                    return Err(format!(
+                        "Sending messages is unsupported for {}",
+                        entry.agent.binary()
+                    ))
+                }
+            }
+        };
+
+        let mut stdin = stdin.lock().await;
+        stdin
+            .write_all(line.as_bytes())
+            .await
+            .map_err(|e| format!("Write failed: {}", e))?;
+        stdin
+            .write_all(b"\n")
+            .await
+            .map_err(|e| format!("Write failed: {}", e))?;
+        stdin
+            .flush()
+            .await
+            .map_err(|e| format!("Flush failed: {}", e))
+    }
+
+    /// Drop the process's stdin handle so the agent sees EOF and finishes.
+    pub async fn close_input(&self, process_id: &str) -> Result<(), String> {
+        let mut procs = self.processes.lock().await;
+        let entry = procs.get_mut(process_id).ok_or("Process not found")?;
+        entry.stdin.take().ok_or("Process has no open input")?;
+        entry.input_closed = true;
+        Ok(())
+    }
+
+    /// Start handing a run's session over to the PTY session `pty_id`. No
+    /// further retries or hooks are started for the run, and its process
+    /// group gets SIGTERM so the agent can save the session and exit.
+    pub async fn begin_handover(&self, process_id: &str, pty_id: &str) -> Result<Handover, String> {
+        let mut procs = self.processes.lock().await;
+        let entry = procs.get_mut(process_id).ok_or("Process not found")?;
+        if entry.handed_over_to.is_some() {
+            return Err("Process is already being taken over".to_string());
+        }
+        if !entry.agent.supports_session_id() {
+            return Err(format!("Taking over is unsupported for {}", entry.agent.binary()));
+        }
+        let session_id = entry
+            .session_id
+            .clone()
+            .ok_or("Process has no session to resume")?;
+        if entry.resume.no_session_persistence {
+            return Err("Process was started without session persistence".to_string());
+        }
+
+        entry.handed_over_to = Some(pty_id.to_string());
+        if let Some(pgid) = entry.active_group() {
+            if entry.paused_since.take().is_some() {
+                let _ = signals::signal_group(pgid, Signal::Cont);
+            }
+            let _ = signals::signal_group(pgid, Signal::Term);
+        }
+        let _ = entry.events.send(AgentStreamEvent::HandedOver {
+            process_id: process_id.to_string(),
+            pty_id: pty_id.to_string(),
+        });
+        Ok(Handover {
+            agent: entry.agent,
+            session_id,
+            stage_execution_id: entry.stage_execution_id.clone(),
+            resume: entry.resume.clone(),
+        })
+    }
+
+    pub async fn is_handed_over(&self, process_id: &str) -> bool {
+        let procs = self.processes.lock().await;
+        procs
+            .get(process_id)
+            .is_some_and(|e| e.handed_over_to.is_some())
+    }
+
+    /// Wait for a run to finish, killing it if it is still going after
+    /// `grace`.
+    pub async fn wait_for_exit(&self, process_id: &str, grace: Duration, bound: Duration) -> Result<(), String> {
+        if self.wait_until_gone(process_id, grace).await {
+            return Ok(());
+        }
+        if let Some(tx) = self
+            .processes
+            .lock()
+            .await
+            .get_mut(process_id)
+            .and_then(|e| e.kill_tx.take())
+        {
+            let _ = tx.send(());
+        }
+        if self.wait_until_gone(process_id, bound).await {
+            Ok(())
+        } else {
+            Err("Process did not exit".to_string())
+        }
+    }
+
+    /// Stop every agent for app exit. Each process group gets SIGTERM; runs
+    /// still going after `grace` are killed. Runs whose supervising task has
+    /// not finished within a further `bound` get their files removed here.
+    pub async fn shutdown(&self, grace: Duration, bound: Duration) -> ShutdownReport {
+        self.shutting_down.store(true, Ordering::SeqCst);
+        let mut report = ShutdownReport::default();
+
+        {
+            let mut procs = self.processes.lock().await;
+            report.running = procs.len();
+            for entry in procs.values_mut() {
+                let Some(pgid) = entry.active_group() else { continue };
+                if entry.paused_since.take().is_some() {
+                    let _ = signals::signal_group(pgid, Signal::Cont);
+                }
+                let _ = signals::signal_group(pgid, Signal::Term);
+            }
+        }
+        if report.running == 0 || self.wait_until_empty(grace).await {
+            return report;
+        }
+
+        {
+            let mut procs = self.processes.lock().await;
+            for entry in procs.values_mut() {
+                if let Some(tx) = entry.kill_tx.take() {
+                    report.forced += 1;
+                    let _ = tx.send(());
+                }
+            }
+        }
+        if self.wait_until_empty(bound).await {
+            return report;
+        }
+
+        let procs = self.processes.lock().await;
+        for (id, entry) in procs.iter() {
+            log::warn!("Agent {} did not finish cleanup in time", id);
+            report.leftover_files += entry.temp.pending_workdir_files();
+            if entry.temp.cleanup() {
+                report.abandoned += 1;
+            }
+        }
+        report
+    }
+
+    async fn wait_until_empty(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.processes.lock().await.is_empty() {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(shutdown::POLL_INTERVAL).await;
+        }
+    }
+
+    async fn wait_until_gone(&self, process_id: &str, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if !self.processes.lock().await.contains_key(process_id) {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(shutdown::POLL_INTERVAL).await;
+        }
     }
 
     pub async fn list_running(&self) -> Vec<String> {
@@ -73,11 +394,24 @@ impl ProcessManager {
         procs.keys().cloned().collect()
     }
 
-    pub async fn list_running_detailed(&self) -> Vec<(String, Option<String>)> {
+    pub async fn list_running_detailed(&self) -> Vec<ProcessSummary> {
         let procs = self.processes.lock().await;
         procs
             .iter()
-            .map(|(id, entry)| (id.clone(), entry.stage_execution_id.clone()))
+            .map(|(id, entry)| ProcessSummary {
+                process_id: id.clone(),
+                stage_execution_id: entry.stage_execution_id.clone(),
+                paused: entry.paused_since.is_some(),
+                resumed_at_ms: entry.resumed_at_ms,
+                handed_over_to: entry.handed_over_to.clone(),
+            })
             .collect()
     }
 }
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
@@ -1,91 +1,1061 @@
-use portable_pty::{Child, MasterPty, PtySize};
-use std::collections::HashMap;
-use std::io::Write;
-use std::sync::Arc;
-use tokio::sync::{oneshot, Mutex};
+use crate::attention::{self, AttentionMatcher, AttentionState, CompiledMatcher};
+use crate::command_history::{self, CommandRecord};
+use crate::commands::pty::SpawnPtyArgs;
+use crate::env_profiles::ResolvedEnv;
+use crate::events::PtyEvent;
+use crate::process_manager::now_ms;
+use crate::ptyd;
+use crate::recording::Recorder;
+use crate::shell_integration::{ShellEvent, ShellMarks, ShellTracker};
+use crate::shutdown::{self, ShutdownReport};
+use crate::signals::{self, Signal};
+use crate::temp_context::SharedTempContext;
+use crate::utf8_decoder::{self, Utf8Decoder};
+use base64::Engine;
+use portable_pty::{Child, ChildKiller, MasterPty, PtySize};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::ipc::Channel;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
 
-pub struct PtyEntry {
-    pub writer: Box<dyn Write + Send>,
-    pub child: Box<dyn Child + Send>,
-    pub master_pty: Box<dyn MasterPty + Send>,
-    pub kill_tx: Option<oneshot::Sender<()>>,
+/// How long to wait for trailing output after the process exits, so
+/// `exited` comes after the last `output` event.
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Output kept per session for replay to subscribers that attach later.
+const SCROLLBACK_BYTES: usize = 512 * 1024;
+
+/// How often a session checks its screen against the attention matchers.
+const ATTENTION_POLL: Duration = Duration::from_millis(250);
+
+/// How long output must have been quiet before the screen is checked, so a
+/// prompt is matched once it has been drawn rather than halfway through.
+const ATTENTION_SETTLE: Duration = Duration::from_millis(500);
+
+/// How often the cwd and foreground process are polled for shells that
+/// don't report them.
+const SHELL_POLL: Duration = Duration::from_secs(1);
+
+/// Size for the emulated screen. Sessions started without a size get 24x80,
+/// and vt100 needs room for a wide character on every row.
+fn screen_size(rows: u16, cols: u16) -> (u16, u16) {
+    if rows == 0 || cols == 0 {
+        (24, 80)
+    } else {
+        (rows, cols.max(2))
+    }
+}
+
+/// The attention matchers in effect, replaced as a whole when they change.
+type Matchers = Arc<Mutex<Arc<Vec<CompiledMatcher>>>>;
+
+/// What the session runs and for whom, reported by `list`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PtyMeta {
+    pub agent: Option<String>,
+    /// Program run by a command session.
+    #[serde(default)]
+    pub command: Option<String>,
+    pub task_id: Option<String>,
+    pub working_directory: Option<String>,
+    pub cols: u16,
+    pub rows: u16,
+    /// Output is sent as base64 `output_bytes` events instead of text.
+    #[serde(default)]
+    pub binary: bool,
+    /// Headless run this session continues, for `takeover_process`.
+    #[serde(default)]
+    pub taken_over_from: Option<String>,
+    #[serde(default)]
+    pub stage_execution_id: Option<String>,
+}
+
+/// A freshly spawned PTY process, handed to `PtyManager::start`.
+pub struct PtySpawn {
+    pub master: Box<dyn MasterPty + Send>,
+    pub child: Box<dyn Child + Send + Sync>,
+    /// First subscriber, normally the channel passed to `spawn_pty`.
+    pub events: Channel<PtyEvent>,
+    /// Temp and working-directory files written for the session.
+    pub temp: SharedTempContext,
+    pub meta: PtyMeta,
+    /// Set when the session is being recorded.
+    pub recorder: Option<Recorder>,
+}
+
+/// A running session, as `list_ptys` returns it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PtySummary {
+    pub id: String,
+    #[serde(flatten)]
+    pub meta: PtyMeta,
+    pub pid: Option<u32>,
+    pub paused: bool,
+    pub started_at_ms: u64,
+    pub subscribers: usize,
+    /// Hosted by `stagehand-ptyd` rather than this process.
+    #[serde(default)]
+    pub persistent: bool,
+    /// The shell's current directory, as last reported or polled.
+    #[serde(default)]
+    pub cwd: Option<String>,
+}
+
+/// The visible screen of a session, as a terminal would show it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScreenSnapshot {
+    pub cols: u16,
+    pub rows: u16,
+    /// One string per screen row, without trailing blanks.
+    pub lines: Vec<String>,
+    pub cursor_row: u16,
+    pub cursor_col: u16,
+    pub cursor_visible: bool,
+    /// Full-screen programs such as editors draw on the alternate screen.
+    pub alternate_screen: bool,
+}
+
+impl ScreenSnapshot {
+    fn of(screen: &vt100::Screen) -> Self {
+        let (rows, cols) = screen.size();
+        let (cursor_row, cursor_col) = screen.cursor_position();
+        ScreenSnapshot {
+            cols,
+            rows,
+            lines: screen.rows(0, cols).map(|r| r.trim_end().to_string()).collect(),
+            cursor_row,
+            cursor_col,
+            cursor_visible: !screen.hide_cursor(),
+            alternate_screen: screen.alternate_screen(),
+        }
+    }
+}
+
+/// The process in the foreground of a session's terminal, such as a
+/// command run from its shell.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForegroundProcess {
+    pub pid: u32,
+    /// `None` where it can't be read from `/proc`.
+    pub name: Option<String>,
+    pub argv: Vec<String>,
+}
+
+impl ForegroundProcess {
+    fn of(pid: u32) -> Self {
+        let argv = process_argv(pid).unwrap_or_default();
+        let name = process_name(pid).or_else(|| {
+            let program = argv.first()?;
+            Some(program.rsplit('/').next().unwrap_or(program).to_string())
+        });
+        ForegroundProcess { pid, name, argv }
+    }
+}
+
+/// A process's arguments, from `/proc/<pid>/cmdline`. `None` where `/proc`
+/// doesn't exist or the process is gone.
+pub fn process_argv(pid: u32) -> Option<Vec<String>> {
+    let raw = std::fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+    Some(
+        raw.split(|&b| b == 0)
+            .filter(|a| !a.is_empty())
+            .map(|a| String::from_utf8_lossy(a).into_owned())
+            .collect(),
+    )
+}
+
+/// A process's name, from `/proc/<pid>/comm`.
+fn process_name(pid: u32) -> Option<String> {
+    let name = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+    Some(name.trim_end().to_string())
+}
+
+/// Session state shared by its reader thread, its actor and attach/list
+/// calls. Output is recorded and fanned out under the same lock, so a
+/// subscriber attaching mid-stream sees every byte exactly once.
+struct Shared {
+    id: String,
+    scrollback: VecDeque<Vec<u8>>,
+    scrollback_bytes: usize,
+    /// `None` in binary mode.
+    decoder: Option<Utf8Decoder>,
+    recorder: Option<Recorder>,
+    /// Terminal emulator fed with the output, for snapshots and matchers.
+    screen: vt100::Parser<ShellMarks>,
+    shell: ShellTracker,
+    /// Output arrived since the screen was last checked for attention.
+    screen_changed: bool,
+    last_output: Instant,
+    attention: AttentionState,
+    subscribers: Vec<(u64, Channel<PtyEvent>)>,
+    next_subscriber: u64,
+    meta: PtyMeta,
+    pid: Option<u32>,
+    paused: bool,
+    started_at_ms: u64,
+}
+
+impl Shared {
+    fn subscribe(&mut self, events: Channel<PtyEvent>) -> u64 {
+        let id = self.next_subscriber;
+        self.next_subscriber += 1;
+        self.subscribers.push((id, events));
+        id
+    }
+
+    /// Send to every subscriber, dropping ones whose channel is gone.
+    fn broadcast(&mut self, event: PtyEvent) {
+        self.subscribers
+            .retain(|(_, events)| events.send(event.clone()).is_ok());
+    }
+
+    /// Record and send a chunk of output. Returns the commands it finished,
+    /// for the caller to save to the task's history once the lock is
+    /// released.
+    fn record(&mut self, bytes: &[u8]) -> Vec<(String, CommandRecord)> {
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.output(bytes);
+        }
+        self.screen.process(bytes);
+        let mut finished = Vec::new();
+        for mark in self.screen.callbacks_mut().take() {
+            for event in self.shell.apply(mark) {
+                finished.extend(self.shell_event(event));
+            }
+        }
+        self.screen_changed = true;
+        self.last_output = Instant::now();
+        match self.decoder.as_mut() {
+            Some(decoder) => {
+                let data = decoder.decode(bytes);
+                if !data.is_empty() {
+                    self.broadcast(PtyEvent::Output { data });
+                }
+            }
+            None => {
+                let data = base64::engine::general_purpose::STANDARD.encode(bytes);
+                self.broadcast(PtyEvent::OutputBytes { data });
+            }
+        }
+        self.scrollback_bytes += bytes.len();
+        self.scrollback.push_back(bytes.to_vec());
+        while self.scrollback_bytes > SCROLLBACK_BYTES && self.scrollback.len() > 1 {
+            if let Some(old) = self.scrollback.pop_front() {
+                self.scrollback_bytes -= old.len();
+            }
+        }
+        finished
+    }
+
+    /// Send a shell event. A finished command is returned with the task
+    /// whose history it belongs in.
+    fn shell_event(&mut self, event: ShellEvent) -> Option<(String, CommandRecord)> {
+        let id = self.id.clone();
+        let mut finished = None;
+        let event = match event {
+            ShellEvent::CommandStarted { command } => PtyEvent::CommandStarted { id, command },
+            ShellEvent::CommandFinished {
+                command,
+                cwd,
+                exit_code,
+                started_at_ms,
+                duration_ms,
+            } => {
+                finished = self.meta.task_id.clone().map(|task_id| {
+                    let record = CommandRecord {
+                        session_id: id.clone(),
+                        command: command.clone(),
+                        cwd,
+                        exit_code,
+                        started_at_ms,
+                        duration_ms,
+                    };
+                    (task_id, record)
+                });
+                PtyEvent::CommandFinished {
+                    id,
+                    command,
+                    exit_code,
+                    duration_ms,
+                }
+            }
+            ShellEvent::CwdChanged { cwd } => PtyEvent::CwdChanged { id, cwd },
+        };
+        self.broadcast(event);
+        finished
+    }
+
+    /// Check the settled screen against `matchers`, returning reasons that
+    /// newly match.
+    fn check_attention(&mut self, matchers: &[CompiledMatcher]) -> Vec<String> {
+        if !self.screen_changed || self.paused || self.last_output.elapsed() < ATTENTION_SETTLE {
+            return Vec::new();
+        }
+        self.screen_changed = false;
+        let agent = self.meta.agent.as_deref();
+        self.attention.check(matchers, agent, self.screen.screen())
+    }
+
+    /// Flush a character left incomplete when the output ended.
+    fn finish(&mut self) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.finish();
+        }
+        if let Some(data) = self.decoder.as_mut().map(Utf8Decoder::finish) {
+            if !data.is_empty() {
+                self.broadcast(PtyEvent::Output { data });
+            }
+        }
+    }
+
+    /// The scrollback as a single event, matching what live subscribers
+    /// have been sent so far.
+    fn replay(&self) -> Option<PtyEvent> {
+        if self.scrollback.is_empty() {
+            return None;
+        }
+        let mut bytes: Vec<u8> = self.scrollback.iter().flatten().copied().collect();
+        match &self.decoder {
+            Some(decoder) => {
+                bytes.truncate(bytes.len() - decoder.pending());
+                let data = utf8_decoder::decode_window(&bytes);
+                Some(PtyEvent::Output { data })
+            }
+            None => {
+                let data = base64::engine::general_purpose::STANDARD.encode(&bytes);
+                Some(PtyEvent::OutputBytes { data })
+            }
+        }
+    }
+}
+
+type SharedState = Arc<Mutex<Shared>>;
+
+type Reply = oneshot::Sender<Result<(), String>>;
+
+type RemoteStreams = Arc<Mutex<HashMap<(String, u64), JoinHandle<()>>>>;
+
+/// Requests handled by a session's actor task.
+enum Request {
+    Resize { cols: u16, rows: u16, reply: Reply },
+    Pause(Reply),
+    Resume(Reply),
+    Signal { signal: Signal, reply: Reply },
+    Foreground(oneshot::Sender<Result<ForegroundProcess, String>>),
+    /// Hang up the session as if its terminal had been closed.
+    Hangup,
+    Kill(Reply),
+}
+
+/// Handle to a running session. Input goes straight to the session's writer
+/// thread; everything else is handled by its actor task. Nothing here is
+/// shared between sessions, so a stuck PTY only blocks its own callers.
+#[derive(Clone)]
+struct SessionHandle {
+    input: std::sync::mpsc::Sender<(Vec<u8>, Reply)>,
+    requests: mpsc::UnboundedSender<Request>,
+    temp: SharedTempContext,
+    shared: SharedState,
+}
+
+/// State owned by a session's actor task.
+struct Session {
+    id: String,
+    master: Box<dyn MasterPty + Send>,
+    killer: Box<dyn ChildKiller + Send + Sync>,
+    pid: Option<u32>,
+    shared: SharedState,
+    matchers: Matchers,
+    /// Set while the session is stopped with SIGSTOP.
+    paused_since: Option<Instant>,
+    killed: bool,
+}
+
+impl Session {
+    /// Process groups to signal, session leader first. The foreground job of
+    /// an interactive shell runs in its own group, so both need stopping —
+    /// otherwise the shell notices the stopped job and takes the terminal back.
+    fn process_groups(&self) -> Vec<u32> {
+        let mut groups: Vec<u32> = self.pid.into_iter().collect();
+        #[cfg(unix)]
+        if let Some(fg) = self.master.process_group_leader() {
+            if let Ok(fg) = u32::try_from(fg) {
+                if !groups.contains(&fg) {
+                    groups.push(fg);
+                }
+            }
+        }
+        groups
+    }
+
+    /// Process group in the foreground of the terminal, which is the
+    /// session's own process when nothing else has taken it.
+    fn foreground_group(&self) -> Option<u32> {
+        #[cfg(unix)]
+        if let Some(fg) = self.master.process_group_leader() {
+            if let Ok(fg) = u32::try_from(fg) {
+                return Some(fg);
+            }
+        }
+        self.pid
+    }
+
+    /// Deliver `signal` to the foreground process group, as the terminal
+    /// does for Ctrl-C or Ctrl-Z.
+    fn signal(&self, signal: Signal) -> Result<(), String> {
+        if !matches!(signal, Signal::Int | Signal::Tstp | Signal::Hup | Signal::Term) {
+            return Err(format!("{:?} can't be sent to a PTY session; use pause, resume or kill", signal));
+        }
+        let pgid = self.foreground_group().ok_or("PTY session has no process")?;
+        signals::signal_group(pgid, signal)
+    }
+
+    fn foreground(&self) -> Result<ForegroundProcess, String> {
+        let pgid = self.foreground_group().ok_or("PTY session has no process")?;
+        Ok(ForegroundProcess::of(pgid))
+    }
+
+    /// Continue a stopped session, foreground job first so the shell never
+    /// observes it as stopped.
+    fn cont(&self) -> Result<(), String> {
+        for pgid in self.process_groups().iter().rev() {
+            signals::signal_group(*pgid, Signal::Cont)?;
+        }
+        Ok(())
+    }
+
+    fn broadcast(&self, event: PtyEvent) {
+        self.shared.lock().unwrap().broadcast(event);
+    }
+
+    fn resize(&self, cols: u16, rows: u16) -> Result<(), String> {
+        self.master
+            .resize(PtySize {
+                rows,
+                cols,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(|e| format!("Resize failed: {}", e))?;
+        let mut shared = self.shared.lock().unwrap();
+        shared.meta.cols = cols;
+        shared.meta.rows = rows;
+        let (screen_rows, screen_cols) = screen_size(rows, cols);
+        shared.screen.screen_mut().set_size(screen_rows, screen_cols);
+        shared.screen_changed = true;
+        if let Some(recorder) = shared.recorder.as_mut() {
+            recorder.resize(cols, rows);
+        }
+        Ok(())
+    }
+
+    fn pause(&mut self) -> Result<(), String> {
+        if self.paused_since.is_some() {
+            return Err("PTY session is already paused".to_string());
+        }
+        let groups = self.process_groups();
+        if groups.is_empty() {
+            return Err("PTY session has no process".to_string());
+        }
+        for pgid in &groups {
+            signals::signal_group(*pgid, Signal::Stop)?;
+        }
+        self.paused_since = Some(Instant::now());
+        self.shared.lock().unwrap().paused = true;
+        self.broadcast(PtyEvent::Paused { id: self.id.clone() });
+        Ok(())
+    }
+
+    fn resume(&mut self) -> Result<(), String> {
+        let paused_since = self.paused_since.ok_or("PTY session is not paused")?;
+        self.cont()?;
+        self.paused_since = None;
+        self.shared.lock().unwrap().paused = false;
+        self.broadcast(PtyEvent::Resumed {
+            id: self.id.clone(),
+            paused_ms: paused_since.elapsed().as_millis() as u64,
+        });
+        Ok(())
+    }
+
+    fn hangup(&mut self) {
+        // A stopped process can't act on SIGHUP, so wake it first.
+        if self.paused_since.take().is_some() {
+            let _ = self.cont();
+        }
+        for pgid in self.process_groups() {
+            let _ = signals::signal_group(pgid, Signal::Hup);
+        }
+    }
+
+    fn kill(&mut self) -> Result<(), String> {
+        if self.paused_since.take().is_some() {
+            let _ = self.cont();
+        }
+        self.killed = true;
+        self.killer.kill().map_err(|e| format!("Kill failed: {}", e))
+    }
+
+    fn handle(&mut self, request: Request) {
+        match request {
+            Request::Resize { cols, rows, reply } => {
+                let _ = reply.send(self.resize(cols, rows));
+            }
+            Request::Pause(reply) => {
+                let _ = reply.send(self.pause());
+            }
+            Request::Resume(reply) => {
+                let _ = reply.send(self.resume());
+            }
+            Request::Signal { signal, reply } => {
+                let _ = reply.send(self.signal(signal));
+            }
+            Request::Foreground(reply) => {
+                let _ = reply.send(self.foreground());
+            }
+            Request::Hangup => self.hangup(),
+            Request::Kill(reply) => {
+                let _ = reply.send(self.kill());
+            }
+        }
+    }
+
+    fn check_attention(&self) {
+        let matchers = self.matchers.lock().unwrap().clone();
+        let mut shared = self.shared.lock().unwrap();
+        for reason in shared.check_attention(&matchers) {
+            shared.broadcast(PtyEvent::Attention {
+                id: self.id.clone(),
+                reason,
+            });
+        }
+    }
+
+    /// Look for cwd changes and foreground commands the shell didn't report.
+    fn poll_shell(&self) {
+        let Some(pid) = self.pid else { return };
+        #[cfg(unix)]
+        let foreground = self
+            .master
+            .process_group_leader()
+            .and_then(|pgid| u32::try_from(pgid).ok());
+        #[cfg(not(unix))]
+        let foreground = None;
+        let mut shared = self.shared.lock().unwrap();
+        if shared.paused {
+            return;
+        }
+        for event in shared.shell.poll(pid, foreground) {
+            shared.shell_event(event);
+        }
+    }
+
+    /// Serve requests until the process exits. Returns the exit code, or
+    /// `None` if the session was killed.
+    async fn run(
+        &mut self,
+        mut requests: mpsc::UnboundedReceiver<Request>,
+        mut exited: oneshot::Receiver<Option<i32>>,
+    ) -> Option<i32> {
+        let mut attention = tokio::time::interval(ATTENTION_POLL);
+        attention.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut shell = tokio::time::interval(SHELL_POLL);
+        shell.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                code = &mut exited => {
+                    let code = code.ok().flatten();
+                    return if self.killed { None } else { code };
+                }
+                Some(request) = requests.recv() => self.handle(request),
+                _ = attention.tick() => self.check_attention(),
+                _ = shell.tick() => self.poll_shell(),
+            }
+        }
+    }
 }
 
+/// Sessions not found locally are looked up in the `stagehand-ptyd` host,
+/// once connected, so callers don't need to know where a session lives.
 #[derive(Clone)]
 pub struct PtyManager {
-    pub sessions: Arc<Mutex<HashMap<String, PtyEntry>>>,
+    sessions: Arc<Mutex<HashMap<String, SessionHandle>>>,
+    shutting_down: Arc<AtomicBool>,
+    daemon: Arc<Mutex<Option<ptyd::Client>>>,
+    /// Event streams from the host, by session id and subscriber id.
+    remote_streams: RemoteStreams,
+    next_remote_subscriber: Arc<AtomicU64>,
+    matchers: Matchers,
 }
 
 impl PtyManager {
     pub fn new() -> Self {
         Self {
             sessions: Arc::new(Mutex::new(HashMap::new())),
+            shutting_down: Arc::new(AtomicBool::new(false)),
+            daemon: Arc::new(Mutex::new(None)),
+            remote_streams: Arc::new(Mutex::new(HashMap::new())),
+            next_remote_subscriber: Arc::new(AtomicU64::new(0)),
+            matchers: Arc::new(Mutex::new(Arc::new(Vec::new()))),
+        }
+    }
+
+    /// Replace the attention matchers for all local sessions, including
+    /// ones already running.
+    pub fn set_attention_matchers(&self, matchers: &[AttentionMatcher]) -> Result<(), String> {
+        let compiled = attention::compile(matchers)?;
+        *self.matchers.lock().unwrap() = Arc::new(compiled);
+        Ok(())
+    }
+
+    /// Pass new attention matchers on to the `stagehand-ptyd` host, if the
+    /// app is connected to one.
+    pub async fn send_attention_matchers_to_host(&self, matchers: &[AttentionMatcher]) -> Result<(), String> {
+        let daemon = self.daemon.lock().unwrap().clone();
+        match daemon {
+            Some(client) => client.set_attention_matchers(matchers).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Connect to a `stagehand-ptyd` host left running by an earlier app
+    /// instance, so its sessions show up in `list` and can be attached.
+    pub async fn connect_daemon(&self) {
+        let Some(client) = ptyd::Client::connect().await else { return };
+        match client.list().await {
+            Ok(sessions) => log::info!("Connected to stagehand-ptyd with {} session(s)", sessions.len()),
+            Err(e) => log::warn!("stagehand-ptyd did not list its sessions: {}", e),
         }
+        *self.daemon.lock().unwrap() = Some(client);
+    }
+
+    /// Spawn a session in the `stagehand-ptyd` host, launching it if needed,
+    /// with `events` as its first subscriber.
+    pub async fn start_persistent(
+        &self,
+        id: String,
+        args: SpawnPtyArgs,
+        env: Option<ResolvedEnv>,
+        events: Channel<PtyEvent>,
+    ) -> Result<(), String> {
+        let existing = self.daemon.lock().unwrap().clone();
+        let client = match existing {
+            Some(client) => client,
+            None => {
+                let client = ptyd::Client::launch().await?;
+                *self.daemon.lock().unwrap() = Some(client.clone());
+                client
+            }
+        };
+        let stream = client.spawn(id.clone(), args, env, events).await?;
+        self.track_remote(id, stream);
+        Ok(())
+    }
+
+    fn remote(&self) -> Result<ptyd::Client, String> {
+        self.daemon
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| "PTY session not found".to_string())
+    }
+
+    fn track_remote(&self, id: String, stream: JoinHandle<()>) -> u64 {
+        let subscriber = self.next_remote_subscriber.fetch_add(1, Ordering::SeqCst);
+        let mut streams = self.remote_streams.lock().unwrap();
+        streams.retain(|_, stream| !stream.is_finished());
+        streams.insert((id, subscriber), stream);
+        subscriber
+    }
+
+    fn is_local(&self, id: &str) -> bool {
+        self.sessions.lock().unwrap().contains_key(id)
     }
 
-    pub async fn register(&self, id: String, entry: PtyEntry) {
-        let mut sessions = self.sessions.lock().await;
-        sessions.insert(id, entry);
+    /// Set once `shutdown` starts; no new sessions are started after.
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
     }
 
-    pub async fn write(&self, id: &str, data: &[u8]) -> Result<(), String> {
-        let mut sessions = self.sessions.lock().await;
-        if let Some(entry) = sessions.get_mut(id) {
-            entry
-                .writer
-                .write_all(data)
-                .map_err(|e| format!("Write failed: {}", e))?;
-            entry
-                .writer
-                .flush()
-                .map_err(|e| format!("Flush failed: {}", e))?;
-            Ok(())
-        } else {
-            Err("PTY session not found".to_string())
+    /// Run a spawned PTY as its own session: a writer thread for input, a
+    /// reader thread streaming output, a thread blocked in `wait()` for the
+    /// exit, and an actor task serving requests until then.
+    pub fn start(&self, id: String, spawn: PtySpawn) -> Result<(), String> {
+        let PtySpawn {
+            master,
+            mut child,
+            events,
+            temp,
+            meta,
+            recorder,
+        } = spawn;
+
+        let streams = master
+            .take_writer()
+            .and_then(|writer| Ok((writer, master.try_clone_reader()?)));
+        let (mut writer, mut reader) = match streams {
+            Ok(streams) => streams,
+            Err(e) => {
+                let _ = child.kill();
+                return Err(format!("Failed to open PTY streams: {}", e));
+            }
+        };
+        let killer = child.clone_killer();
+        let pid = child.process_id();
+        let (rows, cols) = screen_size(meta.rows, meta.cols);
+        let shared = Arc::new(Mutex::new(Shared {
+            id: id.clone(),
+            scrollback: VecDeque::new(),
+            scrollback_bytes: 0,
+            decoder: (!meta.binary).then(Utf8Decoder::default),
+            recorder,
+            screen: vt100::Parser::new_with_callbacks(rows, cols, 0, ShellMarks::default()),
+            shell: ShellTracker::new(meta.working_directory.clone()),
+            screen_changed: false,
+            last_output: Instant::now(),
+            attention: AttentionState::default(),
+            subscribers: Vec::new(),
+            next_subscriber: 0,
+            meta,
+            pid,
+            paused: false,
+            started_at_ms: now_ms(),
+        }));
+        {
+            let mut shared = shared.lock().unwrap();
+            shared.subscribe(events);
+            // Before the reader starts, so `started` precedes any output.
+            shared.broadcast(PtyEvent::Started { id: id.clone() });
         }
+
+        let (input_tx, input_rx) = std::sync::mpsc::channel::<(Vec<u8>, Reply)>();
+        let input = shared.clone();
+        std::thread::Builder::new()
+            .name(format!("pty-writer-{}", id))
+            .spawn(move || {
+                for (data, reply) in input_rx {
+                    let result = writer
+                        .write_all(&data)
+                        .and_then(|_| writer.flush())
+                        .map_err(|e| format!("Write failed: {}", e));
+                    if result.is_ok() {
+                        if let Some(recorder) = input.lock().unwrap().recorder.as_mut() {
+                            recorder.input(&data);
+                        }
+                    }
+                    let _ = reply.send(result);
+                }
+            })
+            .map_err(|e| format!("Failed to start PTY writer: {}", e))?;
+
+        let (output_done_tx, output_done_rx) = oneshot::channel::<()>();
+        let output = shared.clone();
+        std::thread::Builder::new()
+            .name(format!("pty-reader-{}", id))
+            .spawn(move || {
+                let mut buf = [0u8; 4096];
+                loop {
+                    match reader.read(&mut buf) {
+                        Ok(0) => break,
+                        Ok(n) => {
+                            let finished = output.lock().unwrap().record(&buf[..n]);
+                            for (task_id, record) in finished {
+                                if let Err(e) = command_history::append(&task_id, &record) {
+                                    log::warn!("Failed to save command history: {}", e);
+                                }
+                            }
+                        }
+                        Err(_) => break,
+                    }
+                }
+                output.lock().unwrap().finish();
+                let _ = output_done_tx.send(());
+            })
+            .map_err(|e| format!("Failed to start PTY reader: {}", e))?;
+
+        let (exit_tx, exit_rx) = oneshot::channel::<Option<i32>>();
+        std::thread::Builder::new()
+            .name(format!("pty-waiter-{}", id))
+            .spawn(move || {
+                let code = child.wait().ok().map(|status| status.exit_code() as i32);
+                let _ = exit_tx.send(code);
+            })
+            .map_err(|e| format!("Failed to start PTY waiter: {}", e))?;
+
+        let (requests_tx, requests_rx) = mpsc::unbounded_channel();
+        self.sessions.lock().unwrap().insert(
+            id.clone(),
+            SessionHandle {
+                input: input_tx,
+                requests: requests_tx,
+                temp: temp.clone(),
+                shared: shared.clone(),
+            },
+        );
+
+        let mut session = Session {
+            id: id.clone(),
+            master,
+            killer,
+            pid,
+            shared,
+            matchers: self.matchers.clone(),
+            paused_since: None,
+            killed: false,
+        };
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let exit_code = session.run(requests_rx, exit_rx).await;
+            let _ = tokio::time::timeout(OUTPUT_DRAIN_TIMEOUT, output_done_rx).await;
+
+            // Clean up temp files after PTY exits
+            temp.cleanup_blocking().await;
+
+            session.broadcast(PtyEvent::Exited {
+                id: id.clone(),
+                exit_code,
+            });
+            manager.remove(&id);
+        });
+        Ok(())
+    }
+
+    fn session(&self, id: &str) -> Result<SessionHandle, String> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| "PTY session not found".to_string())
+    }
+
+    /// Send a request to the session's actor and wait for its reply.
+    async fn request<T>(
+        &self,
+        id: &str,
+        request: impl FnOnce(oneshot::Sender<Result<T, String>>) -> Request,
+    ) -> Result<T, String> {
+        let session = self.session(id)?;
+        let (tx, rx) = oneshot::channel();
+        session
+            .requests
+            .send(request(tx))
+            .map_err(|_| "PTY session not found".to_string())?;
+        rx.await.map_err(|_| "PTY session exited".to_string())?
+    }
+
+    pub async fn write(&self, id: &str, data: Vec<u8>) -> Result<(), String> {
+        if !self.is_local(id) {
+            return self.remote()?.write(id, &data).await;
+        }
+        let session = self.session(id)?;
+        let (tx, rx) = oneshot::channel();
+        session
+            .input
+            .send((data, tx))
+            .map_err(|_| "PTY session exited".to_string())?;
+        rx.await.map_err(|_| "PTY session exited".to_string())?
     }
 
     pub async fn resize(&self, id: &str, cols: u16, rows: u16) -> Result<(), String> {
-        let sessions = self.sessions.lock().await;
-        if let Some(entry) = sessions.get(id) {
-            entry
-                .master_pty
-                .resize(PtySize {
-                    rows,
-                    cols,
-                    pixel_width: 0,
-                    pixel_height: 0,
-                })
-                .map_err(|e| format!("Resize failed: {}", e))
-        } else {
-            Err("PTY session not found".to_string())
+        if !self.is_local(id) {
+            return self.remote()?.resize(id, cols, rows).await;
         }
+        self.request(id, |reply| Request::Resize { cols, rows, reply }).await
     }
 
     pub async fn kill(&self, id: &str) -> Result<(), String> {
-        let mut sessions = self.sessions.lock().await;
-        if let Some(entry) = sessions.get_mut(id) {
-            if let Some(tx) = entry.kill_tx.take() {
-                tx.send(()).map_err(|_| "PTY already exited".to_string())?;
-            }
-            Ok(())
-        } else {
-            Err("PTY session not found".to_string())
+        if !self.is_local(id) {
+            return self.remote()?.kill(id).await;
+        }
+        self.request(id, Request::Kill).await
+    }
+
+    /// Stop the session's process groups with SIGSTOP.
+    pub async fn pause(&self, id: &str) -> Result<(), String> {
+        if !self.is_local(id) {
+            return self.remote()?.pause(id).await;
+        }
+        self.request(id, Request::Pause).await
+    }
+
+    /// Continue a paused session with SIGCONT.
+    pub async fn resume(&self, id: &str) -> Result<(), String> {
+        if !self.is_local(id) {
+            return self.remote()?.resume(id).await;
         }
+        self.request(id, Request::Resume).await
     }
 
-    pub async fn remove(&self, id: &str) {
-        let mut sessions = self.sessions.lock().await;
-        sessions.remove(id);
+    /// Send `signal` (SIGINT, SIGTSTP, SIGHUP or SIGTERM) to the process
+    /// in the session's foreground.
+    pub async fn signal(&self, id: &str, signal: Signal) -> Result<(), String> {
+        if !self.is_local(id) {
+            return self.remote()?.signal(id, signal).await;
+        }
+        self.request(id, |reply| Request::Signal { signal, reply }).await
+    }
+
+    /// The process in the foreground of the session's terminal.
+    pub async fn foreground(&self, id: &str) -> Result<ForegroundProcess, String> {
+        if !self.is_local(id) {
+            return self.remote()?.foreground(id).await;
+        }
+        self.request(id, Request::Foreground).await
     }
 
-    pub async fn kill_all(&self) {
-        let mut sessions = self.sessions.lock().await;
-        for (_id, entry) in sessions.iter_mut() {
-            if let Some(tx) = entry.kill_tx.take() {
-                let _ = tx.send(());
+    /// Subscribe `events` to a session: the scrollback is replayed as one
+    /// `output` (or `output_bytes`) event, followed by live events. Returns the subscriber id.
+    pub async fn attach(&self, id: &str, events: Channel<PtyEvent>) -> Result<u64, String> {
+        if !self.is_local(id) {
+            let stream = self.remote()?.attach(id, events).await?;
+            return Ok(self.track_remote(id.to_string(), stream));
+        }
+        let session = self.session(id)?;
+        let mut shared = session.shared.lock().unwrap();
+        let _ = events.send(PtyEvent::Started { id: id.to_string() });
+        if let Some(replay) = shared.replay() {
+            let _ = events.send(replay);
+        }
+        if shared.paused {
+            let _ = events.send(PtyEvent::Paused { id: id.to_string() });
+        }
+        Ok(shared.subscribe(events))
+    }
+
+    /// The session's visible screen and cursor.
+    pub async fn snapshot(&self, id: &str) -> Result<ScreenSnapshot, String> {
+        if !self.is_local(id) {
+            return self.remote()?.snapshot(id).await;
+        }
+        let session = self.session(id)?;
+        let shared = session.shared.lock().unwrap();
+        Ok(ScreenSnapshot::of(shared.screen.screen()))
+    }
+
+    /// Stop sending a session's events to a subscriber.
+    pub fn detach(&self, id: &str, subscriber: u64) -> Result<(), String> {
+        if !self.is_local(id) {
+            let stream = self
+                .remote_streams
+                .lock()
+                .unwrap()
+                .remove(&(id.to_string(), subscriber))
+                .ok_or("Subscriber not found")?;
+            stream.abort();
+            return Ok(());
+        }
+        let session = self.session(id)?;
+        let mut shared = session.shared.lock().unwrap();
+        let before = shared.subscribers.len();
+        shared.subscribers.retain(|(s, _)| *s != subscriber);
+        if shared.subscribers.len() == before {
+            return Err("Subscriber not found".to_string());
+        }
+        Ok(())
+    }
+
+    /// Local sessions followed by the host's, if connected.
+    pub async fn list(&self) -> Vec<PtySummary> {
+        let mut sessions = self.list_local();
+        let Ok(client) = self.remote() else { return sessions };
+        match client.list().await {
+            Ok(remote) => sessions.extend(remote.into_iter().map(|s| PtySummary {
+                persistent: true,
+                ..s
+            })),
+            Err(e) => log::warn!("Failed to list stagehand-ptyd sessions: {}", e),
+        }
+        sessions
+    }
+
+    pub fn list_local(&self) -> Vec<PtySummary> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions
+            .iter()
+            .map(|(id, session)| {
+                let shared = session.shared.lock().unwrap();
+                PtySummary {
+                    id: id.clone(),
+                    meta: shared.meta.clone(),
+                    pid: shared.pid,
+                    paused: shared.paused,
+                    started_at_ms: shared.started_at_ms,
+                    subscribers: shared.subscribers.len(),
+                    persistent: false,
+                    cwd: shared.shell.cwd().map(str::to_string),
+                }
+            })
+            .collect()
+    }
+
+    fn remove(&self, id: &str) {
+        self.sessions.lock().unwrap().remove(id);
+    }
+
+    /// Stop every session for app exit. Each session gets SIGHUP, as if its
+    /// terminal had been closed; sessions still running after `grace` are
+    /// killed. Sessions whose task has not finished within a further `bound`
+    /// get their files removed here. Sessions in the `stagehand-ptyd` host
+    /// are only detached from.
+    pub async fn shutdown(&self, grace: Duration, bound: Duration) -> ShutdownReport {
+        self.shutting_down.store(true, Ordering::SeqCst);
+        let mut report = ShutdownReport::default();
+
+        for (_, stream) in self.remote_streams.lock().unwrap().drain() {
+            stream.abort();
+        }
+
+        let sessions: Vec<SessionHandle> = self.sessions.lock().unwrap().values().cloned().collect();
+        report.running = sessions.len();
+        for session in &sessions {
+            let _ = session.requests.send(Request::Hangup);
+        }
+        if report.running == 0 || self.wait_until_empty(grace).await {
+            return report;
+        }
+
+        let sessions: Vec<SessionHandle> = self.sessions.lock().unwrap().values().cloned().collect();
+        for session in &sessions {
+            let (tx, _rx) = oneshot::channel();
+            if session.requests.send(Request::Kill(tx)).is_ok() {
+                report.forced += 1;
+            }
+        }
+        if self.wait_until_empty(bound).await {
+            return report;
+        }
+
+        let sessions = self.sessions.lock().unwrap();
+        for (id, session) in sessions.iter() {
+            log::warn!("PTY session {} did not finish cleanup in time", id);
+            report.leftover_files += session.temp.pending_workdir_files();
+            if session.temp.cleanup() {
+                report.abandoned += 1;
+            }
+        }
+        report
+    }
+
+    async fn wait_until_empty(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.sessions.lock().unwrap().is_empty() {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
             }
+            tokio::time::sleep(shutdown::POLL_INTERVAL).await;
         }
-        sessions.clear();
     }
 }
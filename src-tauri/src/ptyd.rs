@@ -0,0 +1,537 @@
+use crate::attention::{AttentionMatcher, AttentionStore};
+use crate::commands::pty::{open_session_blocking, SpawnPtyArgs};
+use crate::env_profiles::ResolvedEnv;
+use crate::events::PtyEvent;
+use crate::pty_manager::{ForegroundProcess, PtyManager, PtySummary, ScreenSnapshot};
+use crate::signals::Signal;
+use crate::{instance, workdir_overlay};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::ipc::{Channel, InvokeResponseBody};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{mpsc, Mutex as AsyncMutex};
+use tokio::task::JoinHandle;
+
+/// Bumped on incompatible protocol changes. It is part of the socket name,
+/// so an updated app starts its own host and leaves sessions in an older
+/// one running until they exit.
+const PROTOCOL_VERSION: u32 = 2;
+
+/// How long the host keeps running with no sessions.
+const IDLE_EXIT: Duration = Duration::from_secs(60);
+
+/// How long to wait for a freshly launched host to accept connections.
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Grace period for sessions when the host itself is terminated.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(3);
+
+/// The `stagehand-ptyd` binary. Tauri bundles it as a sidecar
+/// (`bundle.externalBin`), which puts it next to the app's executable.
+fn host_binary() -> Result<PathBuf, String> {
+    tauri::utils::platform::current_exe()
+        .map(|exe| exe.with_file_name("stagehand-ptyd"))
+        .map_err(|e| format!("Failed to locate Stagehand executable: {}", e))
+}
+
+/// Whether this build can host persistent sessions. Builds that skipped
+/// scripts/build-ptyd.sh only have the empty placeholder.
+pub fn host_available() -> bool {
+    host_binary()
+        .and_then(|path| std::fs::metadata(path).map_err(|e| e.to_string()))
+        .is_ok_and(|m| m.is_file() && m.len() > 0)
+}
+
+pub fn socket_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|h| {
+        h.join(".stagehand")
+            .join(format!("ptyd-v{}.sock", PROTOCOL_VERSION))
+    })
+}
+
+/// One request per connection, as a line of JSON. `spawn` and `attach`
+/// turn the connection into a stream of `PtyEvent` lines after the reply;
+/// closing it detaches. `input` turns it into a stream of `Input` lines,
+/// each written to the session in order and answered with a `Reply`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Request {
+    Spawn {
+        id: String,
+        args: Box<SpawnPtyArgs>,
+        env: Option<ResolvedEnv>,
+    },
+    Attach { id: String },
+    List,
+    Input { id: String },
+    Resize { id: String, cols: u16, rows: u16 },
+    Pause { id: String },
+    Resume { id: String },
+    Kill { id: String },
+    Snapshot { id: String },
+    Signal { id: String, signal: Signal },
+    Foreground { id: String },
+    AttentionMatchers { matchers: Vec<AttentionMatcher> },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum Reply {
+    Ok,
+    Sessions { sessions: Vec<PtySummary> },
+    Snapshot { snapshot: ScreenSnapshot },
+    Foreground { process: ForegroundProcess },
+    Error { message: String },
+}
+
+/// A chunk of keyboard input on an `input` connection.
+#[derive(Debug, Serialize, Deserialize)]
+struct Input {
+    /// Base64, since input need not be UTF-8.
+    data: String,
+}
+
+impl From<Result<(), String>> for Reply {
+    fn from(result: Result<(), String>) -> Self {
+        match result {
+            Ok(()) => Reply::Ok,
+            Err(message) => Reply::Error { message },
+        }
+    }
+}
+
+async fn send_line<T: Serialize>(writer: &mut OwnedWriteHalf, value: &T) -> std::io::Result<()> {
+    let mut line = serde_json::to_string(value)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await
+}
+
+/// Run the session host until it has been idle for `IDLE_EXIT` or receives
+/// SIGTERM. Entry point of the `stagehand-ptyd` binary.
+pub async fn serve() -> Result<(), String> {
+    let path = socket_path().ok_or("Could not determine home directory")?;
+    if UnixStream::connect(&path).await.is_ok() {
+        return Err(format!("already running at {:?}", path));
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+    }
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).map_err(|e| format!("Failed to bind {:?}: {}", path, e))?;
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600));
+    }
+
+    instance::init();
+    workdir_overlay::recover_stale();
+
+    let ptys = PtyManager::new();
+    load_attention_matchers(&ptys);
+    let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .map_err(|e| format!("Failed to listen for SIGTERM: {}", e))?;
+    let mut last_busy = Instant::now();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                last_busy = Instant::now();
+                if let Ok((stream, _)) = accepted {
+                    tokio::spawn(handle(ptys.clone(), stream));
+                }
+            }
+            _ = sigterm.recv() => break,
+            _ = tokio::time::sleep(Duration::from_secs(1)) => {
+                if !ptys.list_local().is_empty() {
+                    last_busy = Instant::now();
+                } else if last_busy.elapsed() >= IDLE_EXIT {
+                    break;
+                }
+            }
+        }
+    }
+
+    let _ = std::fs::remove_file(&path);
+    ptys.shutdown(SHUTDOWN_GRACE, SHUTDOWN_GRACE).await;
+    instance::release();
+    Ok(())
+}
+
+/// The app saves matchers to the shared store and sends them over when they
+/// change; the host also reloads them for every new session.
+fn load_attention_matchers(ptys: &PtyManager) {
+    if let Err(e) = AttentionStore::new()
+        .load()
+        .and_then(|matchers| ptys.set_attention_matchers(&matchers))
+    {
+        log::warn!("Failed to load attention matchers: {}", e);
+    }
+}
+
+async fn handle(ptys: PtyManager, stream: UnixStream) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let Ok(Some(line)) = lines.next_line().await else { return };
+    let request = match serde_json::from_str::<Request>(&line) {
+        Ok(request) => request,
+        Err(e) => {
+            let message = format!("Invalid request: {}", e);
+            let _ = send_line(&mut writer, &Reply::Error { message }).await;
+            return;
+        }
+    };
+
+    let reply = match request {
+        Request::Spawn { id, args, env } => {
+            load_attention_matchers(&ptys);
+            let (events, rx) = event_stream();
+            let started = open_session_blocking(id.clone(), *args, env, events)
+                .await
+                .and_then(|spawn| {
+                    let temp = spawn.temp.clone();
+                    ptys.start(id, spawn).inspect_err(|_| {
+                        temp.cleanup();
+                    })
+                });
+            return stream_events(started, rx, lines, writer).await;
+        }
+        Request::Attach { id } => {
+            let (events, rx) = event_stream();
+            let attached = ptys.attach(&id, events).await.map(|_| ());
+            return stream_events(attached, rx, lines, writer).await;
+        }
+        Request::List => Reply::Sessions {
+            sessions: ptys.list_local(),
+        },
+        Request::Input { id } => {
+            if send_line(&mut writer, &Reply::Ok).await.is_ok() {
+                forward_input(&ptys, &id, lines, writer).await;
+            }
+            return;
+        }
+        Request::Resize { id, cols, rows } => ptys.resize(&id, cols, rows).await.into(),
+        Request::Pause { id } => ptys.pause(&id).await.into(),
+        Request::Resume { id } => ptys.resume(&id).await.into(),
+        Request::Kill { id } => ptys.kill(&id).await.into(),
+        Request::Snapshot { id } => match ptys.snapshot(&id).await {
+            Ok(snapshot) => Reply::Snapshot { snapshot },
+            Err(message) => Reply::Error { message },
+        },
+        Request::Signal { id, signal } => ptys.signal(&id, signal).await.into(),
+        Request::Foreground { id } => match ptys.foreground(&id).await {
+            Ok(process) => Reply::Foreground { process },
+            Err(message) => Reply::Error { message },
+        },
+        Request::AttentionMatchers { matchers } => ptys.set_attention_matchers(&matchers).into(),
+    };
+    let _ = send_line(&mut writer, &reply).await;
+}
+
+/// Write each `Input` line to the session as it arrives, so input keeps the
+/// order it was typed in.
+async fn forward_input(
+    ptys: &PtyManager,
+    id: &str,
+    mut lines: Lines<BufReader<OwnedReadHalf>>,
+    mut writer: OwnedWriteHalf,
+) {
+    while let Ok(Some(line)) = lines.next_line().await {
+        let reply = match serde_json::from_str::<Input>(&line)
+            .map_err(|e| e.to_string())
+            .and_then(|input| base64::engine::general_purpose::STANDARD.decode(input.data).map_err(|e| e.to_string()))
+        {
+            Ok(bytes) => ptys.write(id, bytes).await.into(),
+            Err(e) => Reply::Error {
+                message: format!("Invalid input: {}", e),
+            },
+        };
+        if send_line(&mut writer, &reply).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// A subscriber channel whose events come out of the receiver as JSON
+/// lines. Sends fail once the receiver is dropped, which unsubscribes it.
+fn event_stream() -> (Channel<PtyEvent>, mpsc::UnboundedReceiver<String>) {
+    let (tx, rx) = mpsc::unbounded_channel::<String>();
+    let events = Channel::new(move |body| match body {
+        InvokeResponseBody::Json(json) => tx.send(json).map_err(|_| {
+            tauri::Error::Io(std::io::Error::from(std::io::ErrorKind::BrokenPipe))
+        }),
+        InvokeResponseBody::Raw(_) => Ok(()),
+    });
+    (events, rx)
+}
+
+/// Reply to a `spawn` or `attach`, then forward the session's events until
+/// it exits or the client hangs up.
+async fn stream_events(
+    result: Result<(), String>,
+    mut rx: mpsc::UnboundedReceiver<String>,
+    mut lines: Lines<BufReader<OwnedReadHalf>>,
+    mut writer: OwnedWriteHalf,
+) {
+    let failed = result.is_err();
+    if send_line(&mut writer, &Reply::from(result)).await.is_err() || failed {
+        return;
+    }
+    loop {
+        tokio::select! {
+            event = rx.recv() => {
+                let Some(mut json) = event else { return };
+                json.push('\n');
+                if writer.write_all(json.as_bytes()).await.is_err() {
+                    return;
+                }
+            }
+            line = lines.next_line() => {
+                if !matches!(line, Ok(Some(_))) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Connection to the session host, used by the app's `PtyManager`.
+#[derive(Debug, Clone)]
+pub struct Client {
+    path: PathBuf,
+    /// One `input` connection per session, opened on its first write.
+    /// Writes take turns on it in the order they were made.
+    inputs: Arc<Mutex<HashMap<String, SharedInput>>>,
+}
+
+type SharedInput = Arc<AsyncMutex<Option<InputConnection>>>;
+
+#[derive(Debug)]
+struct InputConnection {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+}
+
+impl Client {
+    /// Connect to a host that is already running, if there is one.
+    pub async fn connect() -> Option<Client> {
+        let path = socket_path()?;
+        UnixStream::connect(&path).await.ok()?;
+        Some(Client {
+            path,
+            inputs: Default::default(),
+        })
+    }
+
+    /// Connect to the host, launching the bundled `stagehand-ptyd` binary
+    /// if it isn't running.
+    pub async fn launch() -> Result<Client, String> {
+        if let Some(client) = Self::connect().await {
+            return Ok(client);
+        }
+        if !host_available() {
+            return Err("This build of Stagehand doesn't include stagehand-ptyd".to_string());
+        }
+        let exe = host_binary()?;
+        let log = socket_path()
+            .map(|p| p.with_extension("log"))
+            .and_then(|p| std::fs::OpenOptions::new().create(true).append(true).open(p).ok());
+
+        let mut cmd = std::process::Command::new(&exe);
+        cmd.stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(log.map_or_else(std::process::Stdio::null, std::process::Stdio::from));
+        {
+            use std::os::unix::process::CommandExt;
+            // Its own session, so it survives the app and its terminal.
+            unsafe {
+                cmd.pre_exec(|| {
+                    libc::setsid();
+                    Ok(())
+                });
+            }
+        }
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| format!("Failed to start {:?}: {}", exe, e))?;
+        // Reap it in the background if it exits while we're still running.
+        std::thread::spawn(move || child.wait());
+
+        let deadline = Instant::now() + LAUNCH_TIMEOUT;
+        while Instant::now() < deadline {
+            if let Some(client) = Self::connect().await {
+                log::info!("Started stagehand-ptyd");
+                return Ok(client);
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        Err("stagehand-ptyd did not start".to_string())
+    }
+
+    /// Send a request and read its reply, returning the open connection.
+    async fn open(&self, request: &Request) -> Result<(Reply, Lines<BufReader<OwnedReadHalf>>, OwnedWriteHalf), String> {
+        let stream = UnixStream::connect(&self.path)
+            .await
+            .map_err(|e| format!("Failed to connect to stagehand-ptyd: {}", e))?;
+        let (reader, mut writer) = stream.into_split();
+        send_line(&mut writer, request)
+            .await
+            .map_err(|e| format!("Failed to send to stagehand-ptyd: {}", e))?;
+        let mut lines = BufReader::new(reader).lines();
+        let line = lines
+            .next_line()
+            .await
+            .map_err(|e| format!("Failed to read from stagehand-ptyd: {}", e))?
+            .ok_or("stagehand-ptyd closed the connection")?;
+        let reply = serde_json::from_str(&line).map_err(|e| format!("Invalid reply from stagehand-ptyd: {}", e))?;
+        Ok((reply, lines, writer))
+    }
+
+    async fn call(&self, request: Request) -> Result<(), String> {
+        match self.open(&request).await?.0 {
+            Reply::Error { message } => Err(message),
+            _ => Ok(()),
+        }
+    }
+
+    /// Open a `spawn` or `attach` stream and forward its events to `events`
+    /// until the session exits. Aborting the returned task detaches.
+    async fn stream(&self, id: &str, request: Request, events: Channel<PtyEvent>) -> Result<JoinHandle<()>, String> {
+        let (reply, mut lines, writer) = self.open(&request).await?;
+        if let Reply::Error { message } = reply {
+            return Err(message);
+        }
+        let (client, id) = (self.clone(), id.to_string());
+        Ok(tokio::spawn(async move {
+            // Held so the host sees the connection close only when we stop.
+            let _writer = writer;
+            while let Ok(Some(line)) = lines.next_line().await {
+                let Ok(event) = serde_json::from_str::<PtyEvent>(&line) else { continue };
+                let exited = matches!(event, PtyEvent::Exited { .. });
+                if exited {
+                    client.close_input(&id);
+                }
+                if events.send(event).is_err() || exited {
+                    return;
+                }
+            }
+        }))
+    }
+
+    /// Send one chunk over the session's `input` connection, opening it if
+    /// needed, and wait for the host to have written it.
+    async fn send_input(&self, connection: &mut Option<InputConnection>, id: &str, data: &[u8]) -> Result<(), String> {
+        if connection.is_none() {
+            let (reply, lines, writer) = self.open(&Request::Input { id: id.to_string() }).await?;
+            if let Reply::Error { message } = reply {
+                return Err(message);
+            }
+            *connection = Some(InputConnection { lines, writer });
+        }
+        let InputConnection { lines, writer } = connection.as_mut().expect("connection was just opened");
+        let input = Input {
+            data: base64::engine::general_purpose::STANDARD.encode(data),
+        };
+        send_line(writer, &input)
+            .await
+            .map_err(|e| format!("Failed to send to stagehand-ptyd: {}", e))?;
+        let line = lines
+            .next_line()
+            .await
+            .map_err(|e| format!("Failed to read from stagehand-ptyd: {}", e))?
+            .ok_or("stagehand-ptyd closed the connection")?;
+        match serde_json::from_str(&line).map_err(|e| format!("Invalid reply from stagehand-ptyd: {}", e))? {
+            Reply::Error { message } => Err(message),
+            _ => Ok(()),
+        }
+    }
+
+    fn close_input(&self, id: &str) {
+        self.inputs.lock().unwrap().remove(id);
+    }
+
+    pub async fn spawn(
+        &self,
+        id: String,
+        args: SpawnPtyArgs,
+        env: Option<ResolvedEnv>,
+        events: Channel<PtyEvent>,
+    ) -> Result<JoinHandle<()>, String> {
+        let (key, args) = (id.clone(), Box::new(args));
+        self.stream(&key, Request::Spawn { id, args, env }, events).await
+    }
+
+    pub async fn attach(&self, id: &str, events: Channel<PtyEvent>) -> Result<JoinHandle<()>, String> {
+        self.stream(id, Request::Attach { id: id.to_string() }, events).await
+    }
+
+    pub async fn list(&self) -> Result<Vec<PtySummary>, String> {
+        match self.open(&Request::List).await?.0 {
+            Reply::Sessions { sessions } => Ok(sessions),
+            Reply::Error { message } => Err(message),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    pub async fn snapshot(&self, id: &str) -> Result<ScreenSnapshot, String> {
+        match self.open(&Request::Snapshot { id: id.to_string() }).await?.0 {
+            Reply::Snapshot { snapshot } => Ok(snapshot),
+            Reply::Error { message } => Err(message),
+            _ => Err("Unexpected reply from stagehand-ptyd".to_string()),
+        }
+    }
+
+    pub async fn foreground(&self, id: &str) -> Result<ForegroundProcess, String> {
+        match self.open(&Request::Foreground { id: id.to_string() }).await?.0 {
+            Reply::Foreground { process } => Ok(process),
+            Reply::Error { message } => Err(message),
+            _ => Err("Unexpected reply from stagehand-ptyd".to_string()),
+        }
+    }
+
+    pub async fn signal(&self, id: &str, signal: Signal) -> Result<(), String> {
+        self.call(Request::Signal { id: id.to_string(), signal }).await
+    }
+
+    /// Writes to a session reach it in the order they were made.
+    pub async fn write(&self, id: &str, data: &[u8]) -> Result<(), String> {
+        let input = self.inputs.lock().unwrap().entry(id.to_string()).or_default().clone();
+        let mut connection = input.lock().await;
+        let result = self.send_input(&mut connection, id, data).await;
+        if result.is_err() {
+            // Reconnect on the next write rather than reuse a connection
+            // that may be out of step.
+            *connection = None;
+        }
+        result
+    }
+
+    pub async fn resize(&self, id: &str, cols: u16, rows: u16) -> Result<(), String> {
+        self.call(Request::Resize { id: id.to_string(), cols, rows }).await
+    }
+
+    pub async fn pause(&self, id: &str) -> Result<(), String> {
+        self.call(Request::Pause { id: id.to_string() }).await
+    }
+
+    pub async fn resume(&self, id: &str) -> Result<(), String> {
+        self.call(Request::Resume { id: id.to_string() }).await
+    }
+
+    pub async fn kill(&self, id: &str) -> Result<(), String> {
+        self.close_input(id);
+        self.call(Request::Kill { id: id.to_string() }).await
+    }
+
+    /// Replace the attention matchers for the host's running sessions.
+    pub async fn set_attention_matchers(&self, matchers: &[AttentionMatcher]) -> Result<(), String> {
+        self.call(Request::AttentionMatchers {
+            matchers: matchers.to_vec(),
+        })
+        .await
+    }
+}
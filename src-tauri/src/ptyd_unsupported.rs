@@ -0,0 +1,86 @@
+use crate::attention::AttentionMatcher;
+use crate::commands::pty::SpawnPtyArgs;
+use crate::env_profiles::ResolvedEnv;
+use crate::events::PtyEvent;
+use crate::pty_manager::{ForegroundProcess, PtySummary, ScreenSnapshot};
+use crate::signals::Signal;
+use tauri::ipc::Channel;
+use tokio::task::JoinHandle;
+
+const UNSUPPORTED: &str = "Persistent terminal sessions are not supported on this platform";
+
+pub fn host_available() -> bool {
+    false
+}
+
+pub async fn serve() -> Result<(), String> {
+    Err(UNSUPPORTED.to_string())
+}
+
+/// Stand-in for the Unix-socket client; there is never a host to connect to.
+#[derive(Debug, Clone)]
+pub struct Client;
+
+impl Client {
+    pub async fn connect() -> Option<Client> {
+        None
+    }
+
+    pub async fn launch() -> Result<Client, String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub async fn spawn(
+        &self,
+        _id: String,
+        _args: SpawnPtyArgs,
+        _env: Option<ResolvedEnv>,
+        _events: Channel<PtyEvent>,
+    ) -> Result<JoinHandle<()>, String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub async fn attach(&self, _id: &str, _events: Channel<PtyEvent>) -> Result<JoinHandle<()>, String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub async fn list(&self) -> Result<Vec<PtySummary>, String> {
+        Ok(Vec::new())
+    }
+
+    pub async fn write(&self, _id: &str, _data: &[u8]) -> Result<(), String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub async fn resize(&self, _id: &str, _cols: u16, _rows: u16) -> Result<(), String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub async fn pause(&self, _id: &str) -> Result<(), String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub async fn resume(&self, _id: &str) -> Result<(), String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub async fn kill(&self, _id: &str) -> Result<(), String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub async fn snapshot(&self, _id: &str) -> Result<ScreenSnapshot, String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub async fn signal(&self, _id: &str, _signal: Signal) -> Result<(), String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub async fn foreground(&self, _id: &str) -> Result<ForegroundProcess, String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub async fn set_attention_matchers(&self, _matchers: &[AttentionMatcher]) -> Result<(), String> {
+        Err(UNSUPPORTED.to_string())
+    }
+}
@@ -0,0 +1,313 @@
+use crate::events::PtyEvent;
+use crate::process_manager::now_ms;
+use crate::pty_manager::PtyMeta;
+use crate::store;
+use crate::utf8_decoder::Utf8Decoder;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{LineWriter, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::ipc::Channel;
+use tokio::io::AsyncBufReadExt;
+use tokio::task::AbortHandle;
+
+/// How far from the end of a recording to look for its last event.
+const TAIL_BYTES: u64 = 64 * 1024;
+
+fn recordings_dir() -> Result<PathBuf, String> {
+    store::data_file("recordings").ok_or_else(|| "Could not determine home directory".to_string())
+}
+
+/// Recording ids are session ids; anything else could escape the directory.
+fn recording_path(id: &str) -> Result<PathBuf, String> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(format!("Invalid recording id: {}", id));
+    }
+    Ok(recordings_dir()?.join(format!("{}.cast", id)))
+}
+
+/// Stagehand's own header fields. Asciicast players ignore keys they
+/// don't know.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StagehandInfo {
+    task_id: Option<String>,
+    agent: Option<String>,
+    working_directory: Option<String>,
+}
+
+/// Asciicast v2 header, the first line of the file.
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    width: u16,
+    height: u16,
+    timestamp: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(default)]
+    env: HashMap<String, String>,
+    #[serde(default)]
+    stagehand: StagehandInfo,
+}
+
+/// Writes a session's output, input and resizes to
+/// `~/.stagehand/data/recordings/<session id>.cast` as asciicast v2.
+pub struct Recorder {
+    file: LineWriter<File>,
+    started: Instant,
+    output: Utf8Decoder,
+    input: Utf8Decoder,
+    failed: bool,
+}
+
+impl Recorder {
+    pub fn create(session_id: &str, meta: &PtyMeta) -> Result<Recorder, String> {
+        let path = recording_path(session_id)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create dir {:?}: {}", parent, e))?;
+        }
+        let file = File::create(&path).map_err(|e| format!("Failed to create {:?}: {}", path, e))?;
+        let header = Header {
+            version: 2,
+            width: meta.cols,
+            height: meta.rows,
+            timestamp: now_ms() / 1000,
+            title: Some(
+                meta.agent
+                    .clone()
+                    .or_else(|| meta.command.clone())
+                    .unwrap_or_else(|| "shell".to_string()),
+            ),
+            env: ["SHELL", "TERM"]
+                .into_iter()
+                .filter_map(|k| std::env::var(k).ok().map(|v| (k.to_string(), v)))
+                .collect(),
+            stagehand: StagehandInfo {
+                task_id: meta.task_id.clone(),
+                agent: meta.agent.clone(),
+                working_directory: meta.working_directory.clone(),
+            },
+        };
+        let mut recorder = Recorder {
+            file: LineWriter::new(file),
+            started: Instant::now(),
+            output: Utf8Decoder::default(),
+            input: Utf8Decoder::default(),
+            failed: false,
+        };
+        let line = serde_json::to_string(&header).map_err(|e| e.to_string())?;
+        writeln!(recorder.file, "{}", line).map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+        Ok(recorder)
+    }
+
+    fn event(&mut self, code: &str, data: &str) {
+        if self.failed || data.is_empty() {
+            return;
+        }
+        // Microsecond precision, like asciinema's own recorder.
+        let time = (self.started.elapsed().as_secs_f64() * 1e6).round() / 1e6;
+        let line = serde_json::to_string(&(time, code, data)).unwrap_or_default();
+        if let Err(e) = writeln!(self.file, "{}", line) {
+            log::warn!("Stopped recording PTY session: {}", e);
+            self.failed = true;
+        }
+    }
+
+    pub fn output(&mut self, bytes: &[u8]) {
+        let data = self.output.decode(bytes);
+        self.event("o", &data);
+    }
+
+    pub fn input(&mut self, bytes: &[u8]) {
+        let data = self.input.decode(bytes);
+        self.event("i", &data);
+    }
+
+    pub fn resize(&mut self, cols: u16, rows: u16) {
+        self.event("r", &format!("{}x{}", cols, rows));
+    }
+
+    pub fn finish(&mut self) {
+        let data = self.output.finish();
+        self.event("o", &data);
+        let _ = self.file.flush();
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingSummary {
+    pub id: String,
+    pub task_id: Option<String>,
+    pub agent: Option<String>,
+    pub working_directory: Option<String>,
+    pub started_at_ms: u64,
+    pub cols: u16,
+    pub rows: u16,
+    pub duration_ms: u64,
+    pub size_bytes: u64,
+}
+
+/// Time of the last event, read from the end of the file.
+fn last_event_time(file: &mut File, len: u64) -> Option<f64> {
+    file.seek(SeekFrom::Start(len.saturating_sub(TAIL_BYTES))).ok()?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail).ok()?;
+    let tail = String::from_utf8_lossy(&tail);
+    let last = tail.lines().rev().find(|l| !l.trim().is_empty())?;
+    serde_json::from_str::<(f64, String, String)>(last).ok().map(|e| e.0)
+}
+
+fn summarize(path: &std::path::Path) -> Option<RecordingSummary> {
+    let id = path.file_stem()?.to_str()?.to_string();
+    let mut file = File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    let mut first = String::new();
+    std::io::BufRead::read_line(&mut std::io::BufReader::new(&mut file), &mut first).ok()?;
+    let header: Header = serde_json::from_str(&first).ok()?;
+    let duration = last_event_time(&mut file, len).unwrap_or(0.0);
+    Some(RecordingSummary {
+        id,
+        task_id: header.stagehand.task_id,
+        agent: header.stagehand.agent,
+        working_directory: header.stagehand.working_directory,
+        started_at_ms: header.timestamp * 1000,
+        cols: header.width,
+        rows: header.height,
+        duration_ms: (duration * 1000.0) as u64,
+        size_bytes: len,
+    })
+}
+
+/// Recordings on disk, newest first.
+pub fn list() -> Result<Vec<RecordingSummary>, String> {
+    let dir = recordings_dir()?;
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {:?}: {}", dir, e)),
+    };
+    let mut recordings: Vec<RecordingSummary> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("cast"))
+        .filter_map(|p| summarize(&p))
+        .collect();
+    recordings.sort_by_key(|r| std::cmp::Reverse(r.started_at_ms));
+    Ok(recordings)
+}
+
+/// Stream a recording to `events` as a PTY session would: `started`, a
+/// `resized` for the initial size, its output and resizes with their
+/// original timing divided by `speed`, then `exited`. Pauses longer than
+/// `idle_limit` are shortened to it. Input events are skipped; the
+/// terminal's echo is already in the output.
+async fn play(
+    id: String,
+    speed: f64,
+    idle_limit: Option<Duration>,
+    events: Channel<PtyEvent>,
+) -> Result<(), String> {
+    let path = recording_path(&id)?;
+    let read_err = |e: std::io::Error| format!("Failed to read {:?}: {}", path, e);
+    // Read line by line as playback goes; recordings can be large.
+    let file = tokio::fs::File::open(&path).await.map_err(read_err)?;
+    let mut lines = tokio::io::BufReader::new(file).lines();
+    let header: Header = lines
+        .next_line()
+        .await
+        .map_err(read_err)?
+        .and_then(|l| serde_json::from_str(&l).ok())
+        .ok_or_else(|| format!("{:?} is not an asciicast v2 recording", path))?;
+
+    let send = |event: PtyEvent| events.send(event).map_err(|e| e.to_string());
+    send(PtyEvent::Started { id: id.clone() })?;
+    send(PtyEvent::Resized {
+        id: id.clone(),
+        cols: header.width,
+        rows: header.height,
+    })?;
+
+    let started = tokio::time::Instant::now();
+    let mut previous = 0.0;
+    let mut elapsed = Duration::ZERO;
+    while let Some(line) = lines.next_line().await.map_err(read_err)? {
+        let Ok((time, code, data)) = serde_json::from_str::<(f64, String, String)>(&line) else {
+            continue;
+        };
+        let mut gap = Duration::from_secs_f64((time - previous).max(0.0) / speed);
+        previous = time;
+        if let Some(limit) = idle_limit {
+            gap = gap.min(limit);
+        }
+        elapsed += gap;
+        tokio::time::sleep_until(started + elapsed).await;
+
+        match code.as_str() {
+            "o" => send(PtyEvent::Output { data })?,
+            "r" => {
+                let size = data.split_once('x').and_then(|(c, r)| Some((c.parse().ok()?, r.parse().ok()?)));
+                if let Some((cols, rows)) = size {
+                    send(PtyEvent::Resized { id: id.clone(), cols, rows })?;
+                }
+            }
+            _ => {}
+        }
+    }
+    send(PtyEvent::Exited { id, exit_code: None })
+}
+
+/// Playbacks in progress, so they can be stopped early.
+#[derive(Clone, Default)]
+pub struct Playbacks {
+    running: Arc<Mutex<HashMap<String, AbortHandle>>>,
+}
+
+impl Playbacks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start playing a recording. Returns a playback id for `stop`.
+    pub fn start(
+        &self,
+        id: String,
+        speed: f64,
+        idle_limit: Option<Duration>,
+        events: Channel<PtyEvent>,
+    ) -> Result<String, String> {
+        if !speed.is_finite() || speed <= 0.0 {
+            return Err(format!("Invalid playback speed: {}", speed));
+        }
+        recording_path(&id)?;
+        let playback_id = uuid::Uuid::new_v4().to_string();
+        let running = self.running.clone();
+        let key = playback_id.clone();
+        // Held until the task is registered, so it can't unregister first.
+        let mut playbacks = self.running.lock().unwrap();
+        let task = tokio::spawn(async move {
+            if let Err(e) = play(id.clone(), speed, idle_limit, events.clone()).await {
+                log::warn!("Playback of recording {} failed: {}", id, e);
+                let _ = events.send(PtyEvent::Error { id, message: e });
+            }
+            running.lock().unwrap().remove(&key);
+        });
+        playbacks.insert(playback_id.clone(), task.abort_handle());
+        Ok(playback_id)
+    }
+
+    pub fn stop(&self, playback_id: &str) -> Result<(), String> {
+        let task = self
+            .running
+            .lock()
+            .unwrap()
+            .remove(playback_id)
+            .ok_or("Playback not found")?;
+        task.abort();
+        Ok(())
+    }
+}
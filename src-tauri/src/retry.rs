@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+
+const DEFAULT_BASE_DELAY_MS: u64 = 2_000;
+const DEFAULT_MAX_DELAY_MS: u64 = 60_000;
+
+/// Why an agent attempt failed, as far as the backend can tell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    RateLimit,
+    Network,
+    CrashNoOutput,
+}
+
+/// Optional retry policy for `spawn_agent`. Retries run under the same
+/// logical process id; the frontend only sees `retrying` events.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryPolicy {
+    /// Total attempts including the first one.
+    pub max_attempts: u32,
+    pub base_delay_ms: Option<u64>,
+    pub max_delay_ms: Option<u64>,
+    /// Failure kinds worth retrying. Defaults to all of them.
+    pub retry_on: Option<Vec<FailureKind>>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            base_delay_ms: None,
+            max_delay_ms: None,
+            retry_on: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether a failure of `kind` on attempt number `attempt` (1-based)
+    /// should be followed by another attempt.
+    pub fn should_retry(&self, attempt: u32, kind: FailureKind) -> bool {
+        attempt < self.max_attempts
+            && self
+                .retry_on
+                .as_ref()
+                .map_or(true, |kinds| kinds.contains(&kind))
+    }
+
+    /// Exponential backoff before the attempt following `attempt`.
+    pub fn delay_after(&self, attempt: u32) -> Duration {
+        let base = self.base_delay_ms.unwrap_or(DEFAULT_BASE_DELAY_MS);
+        let max = self.max_delay_ms.unwrap_or(DEFAULT_MAX_DELAY_MS);
+        let factor = 1u64 << attempt.saturating_sub(1).min(16);
+        Duration::from_millis(base.saturating_mul(factor).min(max))
+    }
+}
+
+/// What a finished attempt looked like, for classification.
+pub struct AttemptOutcome {
+    pub exit_code: Option<i32>,
+    pub stdout_lines: usize,
+    /// The last few stderr lines, and the error text of failure events the
+    /// agent reported on stdout (see `structured_error`). Ordinary stdout is
+    /// left out: the agent may well be printing code about rate limits.
+    pub tail: Vec<String>,
+}
+
+const RATE_LIMIT_MARKERS: &[&str] = &[
+    "rate limit",
+    "rate_limit",
+    "ratelimit",
+    "too many requests",
+    "status 429",
+    "status code 429",
+    "\"api_error_status\":429",
+    "overloaded",
+    "quota exceeded",
+];
+
+const NETWORK_MARKERS: &[&str] = &[
+    "econnreset",
+    "econnrefused",
+    "etimedout",
+    "enotfound",
+    "eai_again",
+    "socket hang up",
+    "fetch failed",
+    "network error",
+    "connection error",
+    "connection reset",
+];
+
+/// Error text from a structured stdout event reporting a failure: an
+/// errored `result` (Claude), an `error` event (Codex, Gemini), a
+/// `turn.failed` (Codex), or a message carrying an `error` field. `None` for
+/// everything else, including the agent's own output.
+pub fn structured_error(line: &str) -> Option<String> {
+    if !line.trim_start().starts_with('{') {
+        return None;
+    }
+    let event: Value = serde_json::from_str(line).ok()?;
+    let text = |v: &Value| match v {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    let mut parts = Vec::new();
+    match event.get("type").and_then(Value::as_str)? {
+        "result" => {
+            let failed = event.get("is_error").and_then(Value::as_bool) == Some(true)
+                || event
+                    .get("subtype")
+                    .and_then(Value::as_str)
+                    .is_some_and(|s| s.starts_with("error"));
+            if !failed {
+                return None;
+            }
+            if let Some(status) = event.get("api_error_status").filter(|v| !v.is_null()) {
+                parts.push(format!("status {}", text(status)));
+            }
+            parts.extend(["error", "result"].iter().filter_map(|k| event.get(*k)).map(text));
+        }
+        "error" | "turn.failed" => {
+            parts.extend(["error", "message"].iter().filter_map(|k| event.get(*k)).map(text));
+        }
+        _ => parts.push(text(event.get("error").filter(|v| !v.is_null())?)),
+    }
+    Some(parts.join(" "))
+}
+
+/// Classify a failed attempt. Returns `None` for failures that are not
+/// transient (bad arguments, the agent giving up, ...).
+pub fn classify(outcome: &AttemptOutcome) -> Option<FailureKind> {
+    if outcome.exit_code == Some(0) {
+        return None;
+    }
+    let tail = outcome.tail.join("\n").to_lowercase();
+    if RATE_LIMIT_MARKERS.iter().any(|m| tail.contains(m)) {
+        Some(FailureKind::RateLimit)
+    } else if NETWORK_MARKERS.iter().any(|m| tail.contains(m)) {
+        Some(FailureKind::Network)
+    } else if outcome.stdout_lines == 0 {
+        Some(FailureKind::CrashNoOutput)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(exit_code: i32, stdout_lines: usize, tail: &[&str]) -> AttemptOutcome {
+        AttemptOutcome {
+            exit_code: Some(exit_code),
+            stdout_lines,
+            tail: tail.iter().map(|l| l.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn classifies_stderr_and_error_events() {
+        assert_eq!(classify(&outcome(1, 3, &["Error: 429 Too Many Requests"])), Some(FailureKind::RateLimit));
+        assert_eq!(classify(&outcome(1, 3, &["read ECONNRESET"])), Some(FailureKind::Network));
+        assert_eq!(classify(&outcome(1, 0, &["segfault"])), Some(FailureKind::CrashNoOutput));
+        assert_eq!(classify(&outcome(1, 3, &["Error: invalid flag"])), None);
+        assert_eq!(classify(&outcome(0, 0, &["rate limit"])), None);
+    }
+
+    #[test]
+    fn extracts_failure_events_only() {
+        let claude = r#"{"type":"result","subtype":"success","is_error":true,"api_error_status":429,"result":"API Error"}"#;
+        let status = structured_error(claude).unwrap();
+        assert!(status.contains("status 429"), "{}", status);
+        assert_eq!(classify(&outcome(1, 5, &[&status])), Some(FailureKind::RateLimit));
+
+        let codex = r#"{"type":"turn.failed","error":{"message":"stream error: connection reset"}}"#;
+        assert_eq!(classify(&outcome(1, 5, &[&structured_error(codex).unwrap()])), Some(FailureKind::Network));
+        assert!(structured_error(r#"{"type":"error","message":"Rate limit reached"}"#).is_some());
+
+        // The agent talking about rate limits is not a failure.
+        let ok = r#"{"type":"result","subtype":"success","is_error":false,"result":"Added rate limit handling"}"#;
+        assert_eq!(structured_error(ok), None);
+        let assistant = r#"{"type":"assistant","message":{"content":[{"type":"text","text":"429 too many requests"}]}}"#;
+        assert_eq!(structured_error(assistant), None);
+        assert_eq!(structured_error("fn handle_rate_limit() {}"), None);
+        let flagged = r#"{"type":"assistant","error":"rate_limit","message":{"content":[]}}"#;
+        assert_eq!(structured_error(flagged).as_deref(), Some("rate_limit"));
+    }
+}
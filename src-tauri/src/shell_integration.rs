@@ -0,0 +1,511 @@
+use crate::process_manager::now_ms;
+use crate::pty_manager;
+use crate::temp_context::TempContext;
+use portable_pty::CommandBuilder;
+use std::path::Path;
+use std::time::Instant;
+
+/// Sourced by bash after the user's startup files. Marks the prompt and
+/// input with OSC 133 A/B, the command's start with C (from `PS0`, so
+/// bash 4.4 or later), and its exit status and the cwd (OSC 7) before
+/// each prompt.
+const BASH_SNIPPET: &str = r#"
+if [[ -z $__stagehand_shell_integration ]]; then
+  __stagehand_shell_integration=1
+  __stagehand_precmd() {
+    local status=$?
+    printf '\033]133;D;%s\007\033]7;file://%s%s\007' "$status" "$HOSTNAME" "$PWD"
+    return $status
+  }
+  __stagehand_marks() {
+    [[ $PS1 == *'133;B'* ]] || PS1='\[\033]133;A\007\]'"$PS1"'\[\033]133;B\007\]'
+  }
+  PROMPT_COMMAND="__stagehand_precmd;${PROMPT_COMMAND:+$PROMPT_COMMAND;}__stagehand_marks"
+  PS0="$PS0"'\033]133;C\007'
+fi
+"#;
+
+/// Sourced by zsh after the user's `.zshrc`. Prompt themes often rebuild
+/// `PS1` before each prompt, so the markers are re-added by the last
+/// precmd hook.
+const ZSH_SNIPPET: &str = r#"
+if [[ -z $__stagehand_shell_integration ]]; then
+  __stagehand_shell_integration=1
+  __stagehand_precmd() {
+    local ret=$?
+    printf '\033]133;D;%s\007\033]7;file://%s%s\007' "$ret" "$HOST" "$PWD"
+  }
+  __stagehand_marks() {
+    [[ $PS1 == *'133;B'* ]] || PS1=$'%{\e]133;A\a%}'"$PS1"$'%{\e]133;B\a%}'
+  }
+  __stagehand_preexec() {
+    printf '\033]133;C\007'
+  }
+  precmd_functions=(__stagehand_precmd $precmd_functions __stagehand_marks)
+  preexec_functions+=(__stagehand_preexec)
+fi
+"#;
+
+/// Run by fish after its config. fish hands over the command line, so it is
+/// sent with the C marker as kitty's `cmdline_url`.
+const FISH_SNIPPET: &str = r#"
+if not set -q __stagehand_shell_integration
+    set -g __stagehand_shell_integration 1
+    function __stagehand_preexec --on-event fish_preexec
+        printf '\e]133;C;cmdline_url=%s\a' (string escape --style=url -- $argv[1])
+    end
+    function __stagehand_postexec --on-event fish_postexec
+        printf '\e]133;D;%s\a' $status
+    end
+    function __stagehand_cwd --on-event fish_prompt
+        printf '\e]7;file://%s%s\a' $hostname $PWD
+    end
+    functions -c fish_prompt __stagehand_fish_prompt
+    function fish_prompt
+        printf '\e]133;A\a'
+        __stagehand_fish_prompt
+        printf '\e]133;B\a'
+    end
+end
+"#;
+
+/// Loads the user's own zsh startup files from their `ZDOTDIR` (passed in
+/// `STAGEHAND_ZDOTDIR`, or `$HOME`) while ours points at the temp dir, and
+/// hands `ZDOTDIR` back before `.zshrc` so `.zlogin` is theirs.
+const ZSHENV: &str = r#"
+__stagehand_zdotdir=$ZDOTDIR
+ZDOTDIR=${STAGEHAND_ZDOTDIR:-$HOME}
+unset STAGEHAND_ZDOTDIR
+[[ -f $ZDOTDIR/.zshenv ]] && source $ZDOTDIR/.zshenv
+__stagehand_user_zdotdir=$ZDOTDIR
+ZDOTDIR=$__stagehand_zdotdir
+"#;
+
+const ZPROFILE: &str = r#"
+ZDOTDIR=$__stagehand_user_zdotdir
+[[ -f $ZDOTDIR/.zprofile ]] && source $ZDOTDIR/.zprofile
+ZDOTDIR=$__stagehand_zdotdir
+"#;
+
+const ZSHRC: &str = r#"
+ZDOTDIR=$__stagehand_user_zdotdir
+[[ -f $ZDOTDIR/.zshrc ]] && source $ZDOTDIR/.zshrc
+"#;
+
+const BASH_LOGIN: &str = r#"
+[ -f /etc/profile ] && . /etc/profile
+for __stagehand_profile in ~/.bash_profile ~/.bash_login ~/.profile; do
+  [ -f "$__stagehand_profile" ] && { . "$__stagehand_profile"; break; }
+done
+unset __stagehand_profile
+"#;
+
+const BASH_INTERACTIVE: &str = r#"
+[ -f /etc/bash.bashrc ] && . /etc/bash.bashrc
+[ -f ~/.bashrc ] && . ~/.bashrc
+"#;
+
+/// Shells with an init snippet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    /// Recognise a shell by its program name, e.g. `/bin/zsh`.
+    pub fn from_path(path: &str) -> Option<Shell> {
+        match Path::new(path).file_name()?.to_str()? {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            _ => None,
+        }
+    }
+}
+
+/// Make `cmd`, a new `shell`, load its usual startup files followed by our
+/// snippet, written to `temp`. Login shells keep their login files; bash
+/// ignores `--rcfile` in a login shell, so they are sourced explicitly.
+pub fn inject(
+    shell: Shell,
+    cmd: &mut CommandBuilder,
+    temp: &TempContext,
+    login: bool,
+    user_zdotdir: Option<&str>,
+) -> Result<(), String> {
+    match shell {
+        Shell::Bash => {
+            let startup = if login { BASH_LOGIN } else { BASH_INTERACTIVE };
+            let rc = temp.write_temp_file("bashrc", &format!("{}{}", startup, BASH_SNIPPET))?;
+            cmd.arg("--rcfile");
+            cmd.arg(rc);
+        }
+        Shell::Zsh => {
+            temp.write_temp_file(".zshenv", ZSHENV)?;
+            temp.write_temp_file(".zprofile", ZPROFILE)?;
+            let rc = temp.write_temp_file(".zshrc", &format!("{}{}", ZSHRC, ZSH_SNIPPET))?;
+            if let Some(dir) = rc.parent() {
+                cmd.env("ZDOTDIR", dir);
+            }
+            if let Some(user) = user_zdotdir {
+                cmd.env("STAGEHAND_ZDOTDIR", user);
+            }
+            if login {
+                cmd.arg("-l");
+            }
+        }
+        Shell::Fish => {
+            let init = temp.write_temp_file("integration.fish", FISH_SNIPPET)?;
+            if login {
+                cmd.arg("-l");
+            }
+            cmd.arg("--init-command");
+            cmd.arg(format!("source '{}'", init.display()));
+        }
+    }
+    Ok(())
+}
+
+/// What the shell reported through OSC 133 and OSC 7.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mark {
+    /// OSC 133 C, with the command line if the shell sent it.
+    CommandStart { command: String },
+    /// OSC 133 D, with the command's exit status.
+    CommandEnd { exit_code: Option<i32> },
+    /// OSC 7 `file://host/path`.
+    Cwd(String),
+}
+
+/// Collects marks as the screen parser meets them. The command line is
+/// read off the screen between the end of the prompt (B) and where the
+/// cursor is when the command starts (C), unless the shell sent it.
+#[derive(Default)]
+pub struct ShellMarks {
+    input_start: Option<(u16, u16)>,
+    marks: Vec<Mark>,
+}
+
+impl ShellMarks {
+    pub fn take(&mut self) -> Vec<Mark> {
+        std::mem::take(&mut self.marks)
+    }
+
+    fn command_line(&mut self, screen: &vt100::Screen) -> String {
+        let Some((row, col)) = self.input_start.take() else {
+            return String::new();
+        };
+        let (cursor_row, _) = screen.cursor_position();
+        // Enter has moved the cursor down a row. If it is no lower than
+        // the prompt was, the screen scrolled and the input is the row
+        // just above.
+        let row = if cursor_row > row { row } else { cursor_row.saturating_sub(1) };
+        screen
+            .contents_between(row, col, cursor_row, 0)
+            .trim()
+            .to_string()
+    }
+}
+
+impl vt100::Callbacks for ShellMarks {
+    fn unhandled_osc(&mut self, screen: &mut vt100::Screen, params: &[&[u8]]) {
+        match params {
+            [b"133", kind, rest @ ..] => match *kind {
+                b"A" => self.input_start = None,
+                b"B" => self.input_start = Some(screen.cursor_position()),
+                b"C" => {
+                    let sent = rest.iter().find_map(|p| p.strip_prefix(b"cmdline_url="));
+                    let command = match sent {
+                        Some(url) => percent_decode(url),
+                        None => self.command_line(screen),
+                    };
+                    self.marks.push(Mark::CommandStart { command });
+                }
+                b"D" => {
+                    let exit_code = rest
+                        .first()
+                        .and_then(|c| std::str::from_utf8(c).ok())
+                        .and_then(|c| c.parse().ok());
+                    self.marks.push(Mark::CommandEnd { exit_code });
+                }
+                _ => {}
+            },
+            // A path containing `;` arrives split into several params.
+            [b"7", url @ ..] if !url.is_empty() => {
+                let url = url.join(&b';');
+                if let Some(path) = file_url_path(&url) {
+                    self.marks.push(Mark::Cwd(path));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The path of a `file://host/path` URL.
+fn file_url_path(url: &[u8]) -> Option<String> {
+    let rest = url.strip_prefix(b"file://")?;
+    let path = &rest[rest.iter().position(|&b| b == b'/')?..];
+    Some(percent_decode(path))
+}
+
+fn percent_decode(bytes: &[u8]) -> String {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Command boundaries and cwd changes, as sent to subscribers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShellEvent {
+    CommandStarted {
+        command: String,
+    },
+    CommandFinished {
+        command: String,
+        cwd: Option<String>,
+        exit_code: Option<i32>,
+        started_at_ms: u64,
+        duration_ms: u64,
+    },
+    CwdChanged {
+        cwd: String,
+    },
+}
+
+struct Running {
+    command: String,
+    cwd: Option<String>,
+    started: Instant,
+    started_at_ms: u64,
+    /// Process group of a command found by polling rather than reported.
+    pgid: Option<u32>,
+}
+
+/// Tracks a session's running command and cwd, from marks when the shell
+/// reports them and otherwise from `/proc`.
+#[derive(Default)]
+pub struct ShellTracker {
+    running: Option<Running>,
+    cwd: Option<String>,
+    /// The shell reports command starts, so polling is not needed.
+    reports_commands: bool,
+    reports_cwd: bool,
+}
+
+impl ShellTracker {
+    pub fn new(cwd: Option<String>) -> Self {
+        ShellTracker {
+            cwd,
+            ..ShellTracker::default()
+        }
+    }
+
+    pub fn cwd(&self) -> Option<&str> {
+        self.cwd.as_deref()
+    }
+
+    fn start(&mut self, command: String, pgid: Option<u32>) -> ShellEvent {
+        self.running = Some(Running {
+            command: command.clone(),
+            cwd: self.cwd.clone(),
+            started: Instant::now(),
+            started_at_ms: now_ms(),
+            pgid,
+        });
+        ShellEvent::CommandStarted { command }
+    }
+
+    fn finish(&mut self, exit_code: Option<i32>) -> Option<ShellEvent> {
+        let running = self.running.take()?;
+        Some(ShellEvent::CommandFinished {
+            command: running.command,
+            cwd: running.cwd,
+            exit_code,
+            started_at_ms: running.started_at_ms,
+            duration_ms: running.started.elapsed().as_millis() as u64,
+        })
+    }
+
+    fn set_cwd(&mut self, cwd: String) -> Option<ShellEvent> {
+        if self.cwd.as_deref() == Some(cwd.as_str()) {
+            return None;
+        }
+        self.cwd = Some(cwd.clone());
+        Some(ShellEvent::CwdChanged { cwd })
+    }
+
+    pub fn apply(&mut self, mark: Mark) -> Vec<ShellEvent> {
+        match mark {
+            Mark::CommandStart { command } => {
+                self.reports_commands = true;
+                // A start without an end means the previous end was lost.
+                let mut events: Vec<ShellEvent> = self.finish(None).into_iter().collect();
+                events.push(self.start(command, None));
+                events
+            }
+            // Shells send D before every prompt, including after an empty
+            // line, so only an end with a command running counts.
+            Mark::CommandEnd { exit_code } => self.finish(exit_code).into_iter().collect(),
+            Mark::Cwd(cwd) => {
+                self.reports_cwd = true;
+                self.set_cwd(cwd).into_iter().collect()
+            }
+        }
+    }
+
+    /// Fallback for shells without integration: the shell's cwd from
+    /// `/proc/<pid>/cwd`, and the foreground process group, which is a
+    /// command whenever it isn't the shell's own. Exit codes aren't known
+    /// this way. Does nothing where `/proc` doesn't exist.
+    pub fn poll(&mut self, shell_pid: u32, foreground: Option<u32>) -> Vec<ShellEvent> {
+        let mut events = Vec::new();
+        if !self.reports_cwd {
+            if let Ok(cwd) = std::fs::read_link(format!("/proc/{}/cwd", shell_pid)) {
+                events.extend(self.set_cwd(cwd.to_string_lossy().into_owned()));
+            }
+        }
+        let Some(foreground) = foreground else { return events };
+        if self.reports_commands {
+            return events;
+        }
+        let running = self.running.as_ref().and_then(|r| r.pgid);
+        if running == Some(foreground) {
+            return events;
+        }
+        if running.is_some() {
+            events.extend(self.finish(None));
+        }
+        if foreground != shell_pid {
+            let command = pty_manager::process_argv(foreground)
+                .map(|argv| argv.join(" "))
+                .unwrap_or_default();
+            events.push(self.start(command, Some(foreground)));
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marks(output: &str) -> Vec<Mark> {
+        let mut parser = vt100::Parser::new_with_callbacks(5, 40, 0, ShellMarks::default());
+        parser.process(output.as_bytes());
+        parser.callbacks_mut().take()
+    }
+
+    #[test]
+    fn command_line_is_read_off_the_screen() {
+        let output = "\x1b]133;A\x07$ \x1b]133;B\x07ls -la\r\n\x1b]133;C\x07total 0\r\n\x1b]133;D;2\x07";
+        assert_eq!(
+            marks(output),
+            [
+                Mark::CommandStart { command: "ls -la".to_string() },
+                Mark::CommandEnd { exit_code: Some(2) },
+            ]
+        );
+    }
+
+    #[test]
+    fn command_line_survives_a_scroll() {
+        // The prompt is on the last row, so Enter scrolls the screen.
+        let output = "1\r\n2\r\n3\r\n4\r\n\x1b]133;B\x07make\r\n\x1b]133;C\x07";
+        assert_eq!(marks(output), [Mark::CommandStart { command: "make".to_string() }]);
+    }
+
+    #[test]
+    fn sent_command_line_and_missing_exit_code() {
+        let output = "\x1b]133;C;cmdline_url=echo%20%22hi%22\x07\x1b]133;D\x07";
+        assert_eq!(
+            marks(output),
+            [
+                Mark::CommandStart { command: "echo \"hi\"".to_string() },
+                Mark::CommandEnd { exit_code: None },
+            ]
+        );
+    }
+
+    #[test]
+    fn cwd_from_osc_7() {
+        assert_eq!(
+            marks("\x1b]7;file://host/tmp/a%20b;c\x07\x1b]7;http://host/x\x07"),
+            [Mark::Cwd("/tmp/a b;c".to_string())]
+        );
+    }
+
+    #[test]
+    fn file_urls_and_percent_escapes() {
+        assert_eq!(file_url_path(b"file://host/home/me"), Some("/home/me".to_string()));
+        assert_eq!(file_url_path(b"file:///srv/%E4%B8%AD"), Some("/srv/\u{4e2d}".to_string()));
+        assert_eq!(file_url_path(b"file://host"), None);
+        assert_eq!(file_url_path(b"/home/me"), None);
+        assert_eq!(percent_decode(b"100%"), "100%");
+        assert_eq!(percent_decode(b"%zz%4"), "%zz%4");
+        assert_eq!(percent_decode(b"a%2fb"), "a/b");
+    }
+
+    #[test]
+    fn tracker_pairs_starts_with_ends() {
+        let mut tracker = ShellTracker::new(Some("/repo".to_string()));
+        // An end with nothing running is a prompt after an empty line.
+        assert!(tracker.apply(Mark::CommandEnd { exit_code: Some(0) }).is_empty());
+
+        let events = tracker.apply(Mark::CommandStart { command: "cargo test".to_string() });
+        assert_eq!(events, [ShellEvent::CommandStarted { command: "cargo test".to_string() }]);
+        // The cwd a command ran in is the one when it started.
+        tracker.apply(Mark::Cwd("/elsewhere".to_string()));
+        match tracker.apply(Mark::CommandEnd { exit_code: Some(101) }).as_slice() {
+            [ShellEvent::CommandFinished { command, cwd, exit_code, .. }] => {
+                assert_eq!(command, "cargo test");
+                assert_eq!(cwd.as_deref(), Some("/repo"));
+                assert_eq!(*exit_code, Some(101));
+            }
+            other => panic!("unexpected events {:?}", other),
+        }
+    }
+
+    #[test]
+    fn tracker_finishes_a_command_whose_end_was_lost() {
+        let mut tracker = ShellTracker::default();
+        tracker.apply(Mark::CommandStart { command: "vim".to_string() });
+        let events = tracker.apply(Mark::CommandStart { command: "ls".to_string() });
+        assert!(matches!(
+            events.as_slice(),
+            [
+                ShellEvent::CommandFinished { exit_code: None, .. },
+                ShellEvent::CommandStarted { .. },
+            ]
+        ));
+    }
+
+    #[test]
+    fn tracker_reports_cwd_changes_only() {
+        let mut tracker = ShellTracker::new(Some("/repo".to_string()));
+        assert!(tracker.apply(Mark::Cwd("/repo".to_string())).is_empty());
+        assert_eq!(
+            tracker.apply(Mark::Cwd("/tmp".to_string())),
+            [ShellEvent::CwdChanged { cwd: "/tmp".to_string() }]
+        );
+        assert_eq!(tracker.cwd(), Some("/tmp"));
+    }
+}
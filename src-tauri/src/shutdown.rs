@@ -0,0 +1,86 @@
+use crate::instance;
+use crate::process_manager::ProcessManager;
+use crate::pty_manager::PtyManager;
+use crate::task_overlap::OverlapWatches;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+/// How long agents and shells get to exit on their own before being killed.
+const GRACE_PERIOD: Duration = Duration::from_secs(3);
+
+/// How long to wait for killed processes' tasks to finish their cleanup.
+const CLEANUP_BOUND: Duration = Duration::from_secs(2);
+
+pub const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// What one manager's shutdown did.
+#[derive(Debug, Default)]
+pub struct ShutdownReport {
+    /// Processes running when shutdown started.
+    pub running: usize,
+    /// Processes still running after the grace period, killed outright.
+    pub forced: usize,
+    /// Processes whose task never finished; their files were removed by
+    /// the shutdown sequence instead.
+    pub abandoned: usize,
+    /// Working-directory files removed for abandoned processes.
+    pub leftover_files: usize,
+}
+
+/// Stop all agents, PTY sessions and overlap watches and remove the files
+/// they wrote. Runs once; later calls (window close followed by app exit,
+/// say) return immediately.
+pub async fn shutdown(app: &AppHandle, reason: &str) {
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let started = Instant::now();
+    let processes = app.state::<ProcessManager>().inner().clone();
+    let ptys = app.state::<PtyManager>().inner().clone();
+    log::info!("Shutting down ({})", reason);
+    app.state::<OverlapWatches>().stop_all();
+
+    let (agents, sessions) = tokio::join!(
+        processes.shutdown(GRACE_PERIOD, CLEANUP_BOUND),
+        ptys.shutdown(GRACE_PERIOD, CLEANUP_BOUND),
+    );
+    instance::release();
+
+    log::info!(
+        "Shutdown finished in {}ms: agents {:?}, PTY sessions {:?}",
+        started.elapsed().as_millis(),
+        agents,
+        sessions
+    );
+}
+
+/// `shutdown` for event handlers that run outside the async runtime.
+pub fn shutdown_blocking(app: &AppHandle, reason: &str) {
+    tauri::async_runtime::block_on(shutdown(app, reason));
+}
+
+/// Run the shutdown sequence and exit when the process receives SIGTERM.
+#[cfg(unix)]
+pub fn listen_for_sigterm(app: AppHandle) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    tauri::async_runtime::spawn(async move {
+        let mut sigterm = match signal(SignalKind::terminate()) {
+            Ok(sigterm) => sigterm,
+            Err(e) => {
+                log::warn!("Failed to listen for SIGTERM: {}", e);
+                return;
+            }
+        };
+        if sigterm.recv().await.is_some() {
+            shutdown(&app, "SIGTERM").await;
+            app.exit(0);
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn listen_for_sigterm(_app: AppHandle) {}
@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+
+/// Signals delivered to agent and PTY process groups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Signal {
+    #[serde(rename = "SIGSTOP")]
+    Stop,
+    #[serde(rename = "SIGCONT")]
+    Cont,
+    #[serde(rename = "SIGTERM")]
+    Term,
+    #[serde(rename = "SIGHUP")]
+    Hup,
+    #[serde(rename = "SIGKILL")]
+    Kill,
+    #[serde(rename = "SIGINT")]
+    Int,
+    #[serde(rename = "SIGTSTP")]
+    Tstp,
+}
+
+#[cfg(unix)]
+impl Signal {
+    fn raw(self) -> libc::c_int {
+        match self {
+            Signal::Stop => libc::SIGSTOP,
+            Signal::Cont => libc::SIGCONT,
+            Signal::Term => libc::SIGTERM,
+            Signal::Hup => libc::SIGHUP,
+            Signal::Kill => libc::SIGKILL,
+            Signal::Int => libc::SIGINT,
+            Signal::Tstp => libc::SIGTSTP,
+        }
+    }
+}
+
+/// Send `signal` to every process in the process group `pgid`.
+#[cfg(unix)]
+pub fn signal_group(pgid: u32, signal: Signal) -> Result<(), String> {
+    let pgid = libc::pid_t::try_from(pgid).map_err(|_| format!("Invalid process group {}", pgid))?;
+    if pgid <= 1 {
+        return Err(format!("Refusing to signal process group {}", pgid));
+    }
+    // SAFETY: kill(2) has no memory-safety preconditions; a negative pid
+    // addresses the whole process group.
+    let rc = unsafe { libc::kill(-pgid, signal.raw()) };
+    if rc == 0 {
+        Ok(())
+    } else {
+        Err(format!(
+            "Failed to signal process group {}: {}",
+            pgid,
+            std::io::Error::last_os_error()
+        ))
+    }
+}
+
+#[cfg(not(unix))]
+pub fn signal_group(_pgid: u32, _signal: Signal) -> Result<(), String> {
+    Err("Process signals are unsupported on this platform".to_string())
+}
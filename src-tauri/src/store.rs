@@ -0,0 +1,96 @@
+use crate::file_lock::FileLock;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Path of a JSON settings file under `~/.stagehand/data/`.
+pub fn data_file(name: &str) -> Option<PathBuf> {
+    dirs::home_dir().map(|h| h.join(".stagehand").join("data").join(name))
+}
+
+/// Read a JSON settings file, returning the default value if it doesn't exist.
+pub fn read_json<T: DeserializeOwned + Default>(path: &Path) -> Result<T, String> {
+    match std::fs::read_to_string(path) {
+        Ok(raw) => serde_json::from_str(&raw).map_err(|e| format!("Invalid settings file {:?}: {}", path, e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(format!("Failed to read {:?}: {}", path, e)),
+    }
+}
+
+/// Lock a JSON settings file against other Stagehand instances for a
+/// read-modify-write, so concurrent saves can't drop each other's changes.
+/// Plain reads don't need it since writes replace the file atomically.
+pub fn lock_json(path: &Path) -> Result<FileLock, String> {
+    FileLock::acquire(&path.with_extension("json.lock"))
+}
+
+/// `lock_json` for async callers.
+pub async fn lock_json_async(path: &Path) -> Result<FileLock, String> {
+    let owned = path.to_path_buf();
+    tokio::task::spawn_blocking(move || lock_json(&owned))
+        .await
+        .map_err(|e| format!("Failed to lock {:?}: {}", path, e))?
+}
+
+/// Atomically replace a JSON settings file. The file is only readable by the
+/// current user since settings may contain secrets.
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create dir {:?}: {}", parent, e))?;
+    }
+    let raw = serde_json::to_string_pretty(value)
+        .map_err(|e| format!("Failed to serialize {:?}: {}", path, e))?;
+    // Per-process name so two instances saving at once can't interleave
+    // writes to the same temp file.
+    let tmp = path.with_extension(format!("json.{}.tmp", std::process::id()));
+    if let Err(e) = write_private(&tmp, raw.as_bytes()) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(format!("Failed to write {:?}: {}", tmp, e));
+    }
+    std::fs::rename(&tmp, path).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+/// Write a new file that is created owner-only, so no byte of it is ever
+/// readable by others. A leftover file at `path` is replaced.
+fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let mut opts = std::fs::OpenOptions::new();
+    opts.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        opts.mode(0o600);
+    }
+    let mut file = opts.open(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn settings_are_owner_only() {
+        let dir = std::env::temp_dir().join(format!("stagehand-store-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("secrets.json");
+        write_json(&path, &vec!["token"]).unwrap();
+        // A stale temp file with loose permissions is replaced, not reused.
+        let tmp = path.with_extension(format!("json.{}.tmp", std::process::id()));
+        std::fs::write(&tmp, "").unwrap();
+        std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o644)).unwrap();
+        write_json(&path, &vec!["other"]).unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(read_json::<Vec<String>>(&path).unwrap(), vec!["other"]);
+        assert!(!tmp.exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,536 @@
+use crate::events::OverlapEvent;
+use crate::git_repo::{self, git_err, DiffSpec};
+use git2::{Oid, Repository};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::ipc::Channel;
+use tokio::task::AbortHandle;
+
+/// How often watched branches are checked for new commits.
+const OVERLAP_POLL: Duration = Duration::from_secs(5);
+
+/// An active task and the branch its work is on.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskBranch {
+    pub task_id: String,
+    pub branch: String,
+}
+
+/// Lines of the merge base a hunk replaces, inclusive. An insertion covers
+/// the line it follows, so edits next to each other count as overlapping,
+/// as they do for git.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LineRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl LineRange {
+    fn overlaps(&self, other: &LineRange) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HunkOverlap {
+    pub a: LineRange,
+    pub b: LineRange,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileOverlap {
+    pub path: String,
+    /// Hunks of the two tasks that touch the same lines. Empty when they
+    /// edit different parts of the file, it is binary, or the tasks have
+    /// different merge bases.
+    pub hunks: Vec<HunkOverlap>,
+}
+
+/// Files two tasks both change. Line ranges are in the tasks' merge base.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskOverlap {
+    pub task_a: String,
+    pub task_b: String,
+    /// Both tasks diverged from the base at the same commit. Otherwise
+    /// their line numbers refer to different versions of each file, so
+    /// only the files are compared.
+    pub same_merge_base: bool,
+    pub files: Vec<FileOverlap>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedTask {
+    pub task_id: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OverlapReport {
+    pub overlaps: Vec<TaskOverlap>,
+    /// Task ids in the suggested merge order.
+    pub merge_order: Vec<String>,
+    /// Tasks whose branch couldn't be compared, e.g. because it was deleted.
+    pub skipped: Vec<SkippedTask>,
+}
+
+/// A task's changed files against its merge base with the target branch,
+/// kept until its branch or the merge base moves.
+pub struct Changes {
+    head: Oid,
+    merge_base: Oid,
+    files: BTreeMap<String, Vec<LineRange>>,
+}
+
+pub type ChangesCache = HashMap<String, Changes>;
+
+fn hunk_range(old_start: u32, old_lines: u32) -> LineRange {
+    LineRange {
+        start: old_start,
+        end: old_start + old_lines.saturating_sub(1),
+    }
+}
+
+/// Refresh `cache` for `task` unless its branch and merge base are where
+/// they were last time.
+fn update_changes(
+    repo: &Repository,
+    working_directory: &str,
+    base: Oid,
+    task: &TaskBranch,
+    cache: &mut ChangesCache,
+) -> Result<(), String> {
+    let head = repo
+        .revparse_single(&task.branch)
+        .and_then(|o| o.peel_to_commit())
+        .map_err(|e| format!("Unknown branch {}: {}", task.branch, e.message()))?
+        .id();
+    let merge_base = repo.merge_base(base, head).map_err(git_err)?;
+    if cache
+        .get(&task.task_id)
+        .is_some_and(|c| c.head == head && c.merge_base == merge_base)
+    {
+        return Ok(());
+    }
+
+    let (from, to) = (merge_base.to_string(), head.to_string());
+    let spec = DiffSpec {
+        base: Some(&from),
+        head: Some(&to),
+        context_lines: Some(0),
+        ..Default::default()
+    };
+    let mut files = BTreeMap::new();
+    for file in git_repo::diff(working_directory, spec)? {
+        let ranges: Vec<LineRange> = file
+            .hunks
+            .iter()
+            .map(|h| hunk_range(h.old_start, h.old_lines))
+            .collect();
+        // A rename also touches the old path, which the other task may
+        // still be editing.
+        if let Some(old_path) = file.old_path {
+            files.insert(old_path, ranges.clone());
+        }
+        files.insert(file.path, ranges);
+    }
+    cache.insert(task.task_id.clone(), Changes { head, merge_base, files });
+    Ok(())
+}
+
+fn overlap(task_a: &str, a: &Changes, task_b: &str, b: &Changes) -> Option<TaskOverlap> {
+    let same_merge_base = a.merge_base == b.merge_base;
+    let files: Vec<FileOverlap> = a
+        .files
+        .iter()
+        .filter_map(|(path, ranges_a)| {
+            let ranges_b = b.files.get(path)?;
+            let hunks = if same_merge_base {
+                ranges_a
+                    .iter()
+                    .flat_map(|ra| {
+                        ranges_b
+                            .iter()
+                            .filter(|rb| ra.overlaps(rb))
+                            .map(|rb| HunkOverlap { a: *ra, b: *rb })
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            Some(FileOverlap { path: path.clone(), hunks })
+        })
+        .collect();
+    (!files.is_empty()).then(|| TaskOverlap {
+        task_a: task_a.to_string(),
+        task_b: task_b.to_string(),
+        same_merge_base,
+        files,
+    })
+}
+
+/// Repeatedly merge whichever remaining task overlaps least with the
+/// others still waiting, so each merge leaves the fewest conflicts for the
+/// rest. Ties keep the given order.
+fn merge_order(task_ids: &[&str], overlaps: &[TaskOverlap]) -> Vec<String> {
+    let weight = |a: &str, b: &str| {
+        overlaps
+            .iter()
+            .find(|o| (o.task_a == a && o.task_b == b) || (o.task_a == b && o.task_b == a))
+            .map(|o| (o.files.iter().map(|f| f.hunks.len()).sum::<usize>(), o.files.len()))
+            .unwrap_or((0, 0))
+    };
+    let mut remaining = task_ids.to_vec();
+    let mut order = Vec::with_capacity(remaining.len());
+    while !remaining.is_empty() {
+        let next = (0..remaining.len())
+            .min_by_key(|&i| {
+                remaining
+                    .iter()
+                    .filter(|b| **b != remaining[i])
+                    .map(|b| weight(remaining[i], b))
+                    .fold((0, 0), |acc, w| (acc.0 + w.0, acc.1 + w.1))
+            })
+            .unwrap_or(0);
+        order.push(remaining.remove(next).to_string());
+    }
+    order
+}
+
+/// Compare every pair of `tasks`, each against its merge base with `base`.
+/// `cache` carries unchanged tasks over from the last call.
+pub fn compute(
+    working_directory: &str,
+    base: &str,
+    tasks: &[TaskBranch],
+    cache: &mut ChangesCache,
+) -> Result<OverlapReport, String> {
+    let repo = git_repo::open(working_directory)?;
+    let base_oid = repo
+        .revparse_single(base)
+        .and_then(|o| o.peel_to_commit())
+        .map_err(|e| format!("Unknown revision {}: {}", base, e.message()))?
+        .id();
+    // A task listed twice would overlap itself.
+    let mut seen = HashSet::new();
+    let tasks: Vec<&TaskBranch> = tasks.iter().filter(|t| seen.insert(&t.task_id)).collect();
+    cache.retain(|id, _| tasks.iter().any(|t| &t.task_id == id));
+
+    let mut skipped = Vec::new();
+    for task in &tasks {
+        if let Err(error) = update_changes(&repo, working_directory, base_oid, task, cache) {
+            cache.remove(&task.task_id);
+            skipped.push(SkippedTask { task_id: task.task_id.clone(), error });
+        }
+    }
+    let active: Vec<&str> = tasks
+        .iter()
+        .map(|t| t.task_id.as_str())
+        .filter(|id| cache.contains_key(*id))
+        .collect();
+
+    let mut overlaps = Vec::new();
+    for (i, a) in active.iter().enumerate() {
+        for b in &active[i + 1..] {
+            overlaps.extend(overlap(a, &cache[*a], b, &cache[*b]));
+        }
+    }
+    Ok(OverlapReport {
+        merge_order: merge_order(&active, &overlaps),
+        overlaps,
+        skipped,
+    })
+}
+
+/// Task pair and file of each overlap, and whether any hunks collide.
+fn overlap_keys(report: &OverlapReport) -> BTreeMap<(String, String, String), bool> {
+    let mut keys = BTreeMap::new();
+    for o in &report.overlaps {
+        let (a, b) = if o.task_a <= o.task_b { (&o.task_a, &o.task_b) } else { (&o.task_b, &o.task_a) };
+        for f in &o.files {
+            keys.insert((a.clone(), b.clone(), f.path.clone()), !f.hunks.is_empty());
+        }
+    }
+    keys
+}
+
+/// Overlaps in `next` that weren't in `prev`, or whose hunks started
+/// colliding.
+fn new_overlaps(prev: &OverlapReport, next: &OverlapReport) -> Vec<OverlapEvent> {
+    let before = overlap_keys(prev);
+    overlap_keys(next)
+        .into_iter()
+        .filter(|(key, conflicting)| match before.get(key) {
+            None => true,
+            Some(was) => *conflicting && !was,
+        })
+        .map(|((task_a, task_b, path), conflicting)| OverlapEvent::NewOverlap {
+            task_a,
+            task_b,
+            path,
+            conflicting,
+        })
+        .collect()
+}
+
+struct Watch {
+    tasks: Arc<Mutex<Vec<TaskBranch>>>,
+    task: AbortHandle,
+}
+
+/// Background comparisons of a project's active task branches.
+#[derive(Default)]
+pub struct OverlapWatches {
+    running: Arc<Mutex<HashMap<String, Watch>>>,
+}
+
+impl OverlapWatches {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compare `tasks` now and whenever a branch moves, sending a report on
+    /// every change. The first report is the baseline; `new_overlap` events
+    /// follow for overlaps that show up after it. Returns a watch id.
+    pub fn start(
+        &self,
+        working_directory: String,
+        base: String,
+        tasks: Vec<TaskBranch>,
+        events: Channel<OverlapEvent>,
+    ) -> String {
+        let watch_id = uuid::Uuid::new_v4().to_string();
+        let tasks = Arc::new(Mutex::new(tasks));
+        let shared = tasks.clone();
+        let running = self.running.clone();
+        let key = watch_id.clone();
+        // Held until the task is registered, so it can't unregister first.
+        let mut watches = self.running.lock().unwrap();
+        let task = tokio::spawn(async move {
+            watch(working_directory, base, shared, events).await;
+            running.lock().unwrap().remove(&key);
+        });
+        watches.insert(watch_id.clone(), Watch { tasks, task: task.abort_handle() });
+        watch_id
+    }
+
+    /// Replace the watched tasks; unchanged ones aren't recomputed.
+    pub fn update(&self, watch_id: &str, tasks: Vec<TaskBranch>) -> Result<(), String> {
+        let watches = self.running.lock().unwrap();
+        let watch = watches.get(watch_id).ok_or("Overlap watch not found")?;
+        *watch.tasks.lock().unwrap() = tasks;
+        Ok(())
+    }
+
+    pub fn stop(&self, watch_id: &str) -> Result<(), String> {
+        let watch = self
+            .running
+            .lock()
+            .unwrap()
+            .remove(watch_id)
+            .ok_or("Overlap watch not found")?;
+        watch.task.abort();
+        Ok(())
+    }
+
+    /// Stop every watch; their channels die with the page that opened them.
+    pub fn stop_all(&self) {
+        for (_, watch) in self.running.lock().unwrap().drain() {
+            watch.task.abort();
+        }
+    }
+}
+
+/// Runs until the channel is closed or the watch is stopped.
+async fn watch(
+    working_directory: String,
+    base: String,
+    tasks: Arc<Mutex<Vec<TaskBranch>>>,
+    events: Channel<OverlapEvent>,
+) {
+    let mut cache = ChangesCache::new();
+    let mut last: Option<OverlapReport> = None;
+    let mut last_error: Option<String> = None;
+    let mut ticker = tokio::time::interval(OVERLAP_POLL);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        let snapshot = tasks.lock().unwrap().clone();
+        let (dir, rev) = (working_directory.clone(), base.clone());
+        let joined = tokio::task::spawn_blocking(move || {
+            let result = compute(&dir, &rev, &snapshot, &mut cache);
+            (cache, result)
+        })
+        .await;
+        let Ok((returned, result)) = joined else {
+            return;
+        };
+        cache = returned;
+
+        let report = match result {
+            Ok(report) => report,
+            Err(message) => {
+                if last_error.as_ref() != Some(&message) {
+                    last_error = Some(message.clone());
+                    if events.send(OverlapEvent::Error { message }).is_err() {
+                        return;
+                    }
+                }
+                continue;
+            }
+        };
+        last_error = None;
+        if last.as_ref() == Some(&report) {
+            continue;
+        }
+        if let Some(prev) = &last {
+            for event in new_overlaps(prev, &report) {
+                if events.send(event).is_err() {
+                    return;
+                }
+            }
+        }
+        if events.send(OverlapEvent::Report { report: report.clone() }).is_err() {
+            return;
+        }
+        last = Some(report);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes(merge_base: &str, path: &str, ranges: &[(u32, u32)]) -> Changes {
+        let ranges = ranges.iter().map(|&(start, end)| LineRange { start, end }).collect();
+        Changes {
+            head: Oid::zero(),
+            merge_base: Oid::from_str(merge_base).unwrap(),
+            files: BTreeMap::from([(path.to_string(), ranges)]),
+        }
+    }
+
+    #[test]
+    fn hunks_are_compared_only_from_the_same_merge_base() {
+        let a = changes("aaaa", "src/lib.rs", &[(10, 12)]);
+        let b = changes("aaaa", "src/lib.rs", &[(12, 14), (30, 30)]);
+        let same = overlap("a", &a, "b", &b).unwrap();
+        assert!(same.same_merge_base);
+        assert_eq!(
+            same.files[0].hunks,
+            [HunkOverlap {
+                a: LineRange { start: 10, end: 12 },
+                b: LineRange { start: 12, end: 14 },
+            }]
+        );
+
+        // Line 12 of one base needn't be line 12 of the other.
+        let moved = changes("bbbb", "src/lib.rs", &[(12, 14)]);
+        let different = overlap("a", &a, "c", &moved).unwrap();
+        assert!(!different.same_merge_base);
+        assert_eq!(different.files.len(), 1);
+        assert!(different.files[0].hunks.is_empty());
+
+        let elsewhere = changes("aaaa", "README.md", &[(1, 1)]);
+        assert!(overlap("a", &a, "d", &elsewhere).is_none());
+    }
+
+    fn pair(task_a: &str, task_b: &str, files: &[(&str, usize)]) -> TaskOverlap {
+        let range = LineRange { start: 1, end: 1 };
+        TaskOverlap {
+            task_a: task_a.to_string(),
+            task_b: task_b.to_string(),
+            same_merge_base: true,
+            files: files
+                .iter()
+                .map(|&(path, hunks)| FileOverlap {
+                    path: path.to_string(),
+                    hunks: vec![HunkOverlap { a: range, b: range }; hunks],
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn merge_order_puts_the_least_overlapping_tasks_first() {
+        let overlaps = [pair("a", "b", &[("x", 2)]), pair("c", "a", &[("y", 0)])];
+        // d overlaps nothing; c only a file; a and b tie once c is merged.
+        assert_eq!(merge_order(&["a", "b", "c", "d"], &overlaps), ["d", "c", "a", "b"]);
+        assert_eq!(merge_order(&["b", "a", "c", "d"], &overlaps), ["d", "c", "b", "a"]);
+        assert_eq!(merge_order(&["c", "b", "a"], &[]), ["c", "b", "a"]);
+        assert!(merge_order(&[], &[]).is_empty());
+    }
+
+    fn report(overlaps: Vec<TaskOverlap>) -> OverlapReport {
+        OverlapReport { overlaps, merge_order: Vec::new(), skipped: Vec::new() }
+    }
+
+    fn events(prev: &OverlapReport, next: &OverlapReport) -> Vec<(String, String, String, bool)> {
+        new_overlaps(prev, next)
+            .into_iter()
+            .map(|e| match e {
+                OverlapEvent::NewOverlap { task_a, task_b, path, conflicting } => {
+                    (task_a, task_b, path, conflicting)
+                }
+                other => panic!("unexpected event {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn new_overlaps_fire_for_new_or_newly_conflicting_files() {
+        let prev = report(vec![pair("a", "b", &[("same.rs", 0), ("hot.rs", 1), ("now.rs", 0)])]);
+        assert!(events(&prev, &prev).is_empty());
+
+        // The pair's order in the report doesn't matter.
+        let next = report(vec![
+            pair("b", "a", &[("same.rs", 0), ("hot.rs", 0), ("now.rs", 1), ("new.rs", 0)]),
+            pair("a", "c", &[("same.rs", 0)]),
+        ]);
+        let key = |a: &str, b: &str, path: &str, conflicting| {
+            (a.to_string(), b.to_string(), path.to_string(), conflicting)
+        };
+        assert_eq!(
+            events(&prev, &next),
+            [
+                key("a", "b", "new.rs", false),
+                key("a", "b", "now.rs", true),
+                key("a", "c", "same.rs", false),
+            ]
+        );
+        // Overlaps that went away or stopped conflicting are quiet.
+        assert!(events(&next, &report(Vec::new())).is_empty());
+        assert!(events(&prev, &report(vec![pair("a", "b", &[("hot.rs", 0)])])).is_empty());
+    }
+
+    #[test]
+    fn compute_ignores_a_task_listed_twice() {
+        use git2::{RepositoryInitOptions, Signature};
+
+        let dir = std::env::temp_dir().join(format!("stagehand-overlap-{}", uuid::Uuid::new_v4()));
+        let repo = Repository::init_opts(&dir, RepositoryInitOptions::new().initial_head("main")).unwrap();
+        let sig = Signature::now("Test", "test@example.com").unwrap();
+        let commit = |refname: &str, content: &str, parents: &[&git2::Commit]| {
+            std::fs::write(dir.join("a.txt"), content).unwrap();
+            let mut index = repo.index().unwrap();
+            index.add_path(std::path::Path::new("a.txt")).unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            repo.commit(Some(refname), &sig, &sig, "commit", &tree, parents).unwrap()
+        };
+        let base = repo.find_commit(commit("HEAD", "one\ntwo\n", &[])).unwrap();
+        commit("refs/heads/task", "one\n2\n", &[&base]);
+
+        let task = TaskBranch { task_id: "t".to_string(), branch: "task".to_string() };
+        let tasks = [task.clone(), task];
+        let report = compute(dir.to_str().unwrap(), "main", &tasks, &mut ChangesCache::new()).unwrap();
+        assert!(report.overlaps.is_empty());
+        assert_eq!(report.merge_order, ["t"]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,102 @@
+use crate::instance;
+use crate::workdir_overlay;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Temporary directory context for a spawned agent or PTY session.
+/// Creates `~/.stagehand/tmp/<pid>/<name>/` for temp files (system prompt files,
+/// schema files, config files) and tracks working-directory files that need
+/// cleanup after the process exits.
+pub struct TempContext {
+    /// Identifies this context's layers in shared working-directory files.
+    owner: String,
+    /// The per-process temp directory under the instance's temp root
+    dir: PathBuf,
+    /// Working directory and relative path of files layered into the
+    /// agent's working directory, released on cleanup.
+    workdir_files: Vec<(PathBuf, String)>,
+}
+
+impl TempContext {
+    pub fn new(name: &str) -> Result<Self, String> {
+        let dir = instance::temp_root()
+            .ok_or("Could not find home directory")?
+            .join(name);
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create temp dir {:?}: {}", dir, e))?;
+        Ok(Self {
+            owner: name.to_string(),
+            dir,
+            workdir_files: Vec::new(),
+        })
+    }
+
+    /// Write a file into the per-process temp directory. Returns the full path.
+    pub fn write_temp_file(&self, name: &str, content: &str) -> Result<PathBuf, String> {
+        let path = self.dir.join(name);
+        std::fs::write(&path, content)
+            .map_err(|e| format!("Failed to write temp file {:?}: {}", path, e))?;
+        Ok(path)
+    }
+
+    /// Layer a file into the working directory, merged with any existing
+    /// version, and track it for cleanup. See `workdir_overlay`.
+    pub fn write_workdir_file(&mut self, workdir: &Path, relative_path: &str, content: &str) -> Result<PathBuf, String> {
+        let path = workdir_overlay::apply(&self.owner, workdir, relative_path, content)?;
+        self.workdir_files.push((workdir.to_path_buf(), relative_path.to_string()));
+        Ok(path)
+    }
+
+    /// Clean up all temp files and directories.
+    pub fn cleanup(self) {
+        // Restore working directory files
+        for (workdir, relative_path) in &self.workdir_files {
+            workdir_overlay::release(&self.owner, workdir, relative_path);
+        }
+        // Remove the per-process temp directory
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A `TempContext` shared between the task supervising a process and the
+/// manager holding its entry, so shutdown can clean up after a task that
+/// never got to finish. Whichever side cleans up first wins.
+#[derive(Clone)]
+pub struct SharedTempContext(Arc<Mutex<Option<TempContext>>>);
+
+impl SharedTempContext {
+    pub fn new(ctx: TempContext) -> Self {
+        Self(Arc::new(Mutex::new(Some(ctx))))
+    }
+
+    pub fn write_temp_file(&self, name: &str, content: &str) -> Result<PathBuf, String> {
+        let ctx = self.0.lock().unwrap();
+        ctx.as_ref()
+            .ok_or("Temp files were already cleaned up")?
+            .write_temp_file(name, content)
+    }
+
+    /// Number of working-directory files still to be removed.
+    pub fn pending_workdir_files(&self) -> usize {
+        self.0
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map_or(0, |ctx| ctx.workdir_files.len())
+    }
+
+    /// Clean up unless already done. Returns whether this call did the work.
+    pub fn cleanup(&self) -> bool {
+        let ctx = self.0.lock().unwrap().take();
+        ctx.map(TempContext::cleanup).is_some()
+    }
+
+    /// `cleanup` for async callers: releasing working-directory files waits
+    /// on the backups lock and may run git.
+    pub async fn cleanup_blocking(&self) -> bool {
+        let ctx = self.clone();
+        tokio::task::spawn_blocking(move || ctx.cleanup())
+            .await
+            .unwrap_or(false)
+    }
+}
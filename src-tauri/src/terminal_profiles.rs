@@ -0,0 +1,92 @@
+use crate::file_lock::FileLock;
+use crate::store;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tokio::sync::Mutex;
+
+/// A named terminal setup that `spawn_pty` can start by name, such as a
+/// dev server or a database console.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalProfile {
+    pub name: String,
+    /// Program to run instead of the user's shell.
+    pub command: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Start the shell, or the command through the shell, as a login shell.
+    #[serde(default)]
+    pub login_shell: bool,
+    /// Value for `TERM`.
+    pub term: Option<String>,
+}
+
+/// `~/.stagehand/data/terminal_profiles.json`, shared by all projects.
+pub struct TerminalProfileStore {
+    path: Option<PathBuf>,
+    lock: Mutex<()>,
+}
+
+impl TerminalProfileStore {
+    pub fn new() -> Self {
+        Self {
+            path: store::data_file("terminal_profiles.json"),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn load(&self) -> Result<Vec<TerminalProfile>, String> {
+        let path = self.path.as_deref().ok_or("Could not find home directory")?;
+        store::read_json(path)
+    }
+
+    /// Held across a load and save; see `store::lock_json`.
+    async fn lock_file(&self) -> Result<FileLock, String> {
+        let path = self.path.as_deref().ok_or("Could not find home directory")?;
+        store::lock_json_async(path).await
+    }
+
+    fn save(&self, profiles: &[TerminalProfile]) -> Result<(), String> {
+        let path = self.path.as_deref().ok_or("Could not find home directory")?;
+        store::write_json(path, &profiles)
+    }
+
+    pub async fn list(&self) -> Result<Vec<TerminalProfile>, String> {
+        let _guard = self.lock.lock().await;
+        self.load()
+    }
+
+    pub async fn get(&self, name: &str) -> Result<TerminalProfile, String> {
+        let _guard = self.lock.lock().await;
+        self.load()?
+            .into_iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| format!("Terminal profile '{}' not found", name))
+    }
+
+    /// Insert or replace a profile by name.
+    pub async fn upsert(&self, profile: TerminalProfile) -> Result<(), String> {
+        if profile.name.trim().is_empty() {
+            return Err("Profile name must not be empty".to_string());
+        }
+        let _guard = self.lock.lock().await;
+        let _file = self.lock_file().await?;
+        let mut profiles = self.load()?;
+        match profiles.iter().position(|p| p.name == profile.name) {
+            Some(i) => profiles[i] = profile,
+            None => profiles.push(profile),
+        }
+        self.save(&profiles)
+    }
+
+    pub async fn delete(&self, name: &str) -> Result<(), String> {
+        let _guard = self.lock.lock().await;
+        let _file = self.lock_file().await?;
+        let mut profiles = self.load()?;
+        profiles.retain(|p| p.name != name);
+        self.save(&profiles)
+    }
+}
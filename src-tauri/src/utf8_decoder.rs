@@ -0,0 +1,136 @@
+/// Longest UTF-8 sequence; an incomplete one is at most this minus one.
+const MAX_SEQUENCE: usize = 4;
+
+/// Decodes a byte stream read in arbitrary chunks. A multibyte character
+/// split across two reads is held back until the rest of it arrives instead
+/// of being replaced with U+FFFD; genuinely invalid bytes still are.
+#[derive(Debug, Default)]
+pub struct Utf8Decoder {
+    pending: Vec<u8>,
+}
+
+impl Utf8Decoder {
+    /// Bytes of an incomplete character carried over to the next chunk.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn decode(&mut self, chunk: &[u8]) -> String {
+        let mut bytes = std::mem::take(&mut self.pending);
+        bytes.extend_from_slice(chunk);
+
+        let mut out = String::with_capacity(bytes.len());
+        let mut rest = &bytes[..];
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    out.push_str(valid);
+                    break;
+                }
+                Err(e) => {
+                    let (valid, after) = rest.split_at(e.valid_up_to());
+                    // Valid up to here by construction.
+                    out.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                    match e.error_len() {
+                        Some(len) => {
+                            out.push(char::REPLACEMENT_CHARACTER);
+                            rest = &after[len..];
+                        }
+                        None => {
+                            // A truncated sequence at the end of the chunk.
+                            debug_assert!(after.len() < MAX_SEQUENCE);
+                            self.pending = after.to_vec();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// Decode whatever is still pending, once the stream has ended.
+    pub fn finish(&mut self) -> String {
+        let pending = std::mem::take(&mut self.pending);
+        String::from_utf8_lossy(&pending).into_owned()
+    }
+}
+
+/// Decode a slice cut out of a UTF-8 stream at arbitrary byte offsets,
+/// dropping a partial character at the start rather than showing it as
+/// U+FFFD.
+pub fn decode_window(bytes: &[u8]) -> String {
+    let start = bytes
+        .iter()
+        .take(MAX_SEQUENCE - 1)
+        .position(|b| b & 0b1100_0000 != 0b1000_0000)
+        // Three continuation bytes: only the tail of a 4-byte character.
+        .unwrap_or(bytes.len().min(MAX_SEQUENCE - 1));
+    String::from_utf8_lossy(&bytes[start..]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_split(bytes: &[u8], at: usize) -> String {
+        let mut decoder = Utf8Decoder::default();
+        let mut out = decoder.decode(&bytes[..at]);
+        out += &decoder.decode(&bytes[at..]);
+        assert_eq!(decoder.pending(), 0);
+        out += &decoder.finish();
+        out
+    }
+
+    #[test]
+    fn multibyte_characters_split_at_every_offset() {
+        for text in ["a\u{e9}b", "a\u{4e2d}b", "a\u{1f600}b"] {
+            let bytes = text.as_bytes();
+            for at in 0..=bytes.len() {
+                assert_eq!(decode_split(bytes, at), text, "split at {}", at);
+            }
+        }
+    }
+
+    #[test]
+    fn character_split_across_three_reads() {
+        let bytes = "\u{1f600}".as_bytes();
+        let mut decoder = Utf8Decoder::default();
+        assert_eq!(decoder.decode(&bytes[..1]), "");
+        assert_eq!(decoder.decode(&bytes[1..3]), "");
+        assert_eq!(decoder.pending(), 3);
+        assert_eq!(decoder.decode(&bytes[3..]), "\u{1f600}");
+        assert_eq!(decoder.pending(), 0);
+    }
+
+    #[test]
+    fn invalid_bytes_are_replaced() {
+        let mut decoder = Utf8Decoder::default();
+        assert_eq!(decoder.decode(b"a\xffb\x80c"), "a\u{fffd}b\u{fffd}c");
+        // A lead byte followed by something that can't continue it.
+        assert_eq!(decoder.decode(b"\xe4x"), "\u{fffd}x");
+        assert_eq!(decoder.pending(), 0);
+    }
+
+    #[test]
+    fn unfinished_character_is_replaced_at_finish() {
+        let mut decoder = Utf8Decoder::default();
+        assert_eq!(decoder.decode(b"ok\xf0\x9f"), "ok");
+        assert_eq!(decoder.pending(), 2);
+        assert_eq!(decoder.finish(), "\u{fffd}");
+        assert_eq!(decoder.pending(), 0);
+        assert_eq!(decoder.finish(), "");
+    }
+
+    #[test]
+    fn window_drops_leading_partial_character() {
+        let bytes = "\u{1f600}x\u{e9}".as_bytes();
+        for start in 1..4 {
+            assert_eq!(decode_window(&bytes[start..]), "x\u{e9}");
+        }
+        assert_eq!(decode_window(bytes), "\u{1f600}x\u{e9}");
+        // Cut off at the end too: only the tail becomes U+FFFD.
+        assert_eq!(decode_window(&bytes[..bytes.len() - 1]), "\u{1f600}x\u{fffd}");
+        assert_eq!(decode_window(b""), "");
+    }
+}
@@ -0,0 +1,608 @@
+use crate::file_lock::FileLock;
+use crate::instance;
+use crate::store;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const EXCLUDE_BEGIN: &str = "# >>> stagehand: temporary agent config files";
+const EXCLUDE_END: &str = "# <<< stagehand";
+
+/// Held while overlays are read or changed, by this and every other
+/// Stagehand process sharing `~/.stagehand`.
+const LOCK_FILE: &str = ".lock";
+
+/// How agent layers combine with the file already in the directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MergeKind {
+    /// Markdown instructions: layers are appended after the existing text.
+    Append,
+    /// TOML tables are deep-merged, layers winning over existing keys.
+    Toml,
+    /// JSON objects are deep-merged, layers winning over existing keys.
+    Json,
+    /// Anything else: the newest layer replaces the file.
+    Replace,
+}
+
+impl MergeKind {
+    fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("md") => MergeKind::Append,
+            Some("toml") => MergeKind::Toml,
+            Some("json") => MergeKind::Json,
+            _ => MergeKind::Replace,
+        }
+    }
+}
+
+/// One agent's content for a file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Layer {
+    owner: String,
+    /// Pid of the Stagehand process that applied it.
+    pid: u32,
+    content: String,
+}
+
+/// On-disk record of an overlaid file, kept in `~/.stagehand/backups/`. It
+/// is the only copy of the overlay's state, so every Stagehand process
+/// sees the layers of the others, and the changes of one that crashed can
+/// be undone later. The original bytes, if any, sit next to it in
+/// `<id>.orig`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BackupManifest {
+    path: PathBuf,
+    existed: bool,
+    /// Directories created for the file, innermost first.
+    created_dirs: Vec<PathBuf>,
+    /// The file is tracked by git and was marked `--skip-worktree`.
+    skip_worktree: bool,
+    /// Exclude file and pattern added to hide a new file from `git status`.
+    exclude: Option<(PathBuf, String)>,
+    /// In the order they were applied.
+    #[serde(default)]
+    layers: Vec<Layer>,
+}
+
+/// A file in a working directory that one or more agents have layered
+/// their config onto.
+struct Overlay {
+    id: String,
+    manifest: BackupManifest,
+    original: Option<Vec<u8>>,
+}
+
+fn backups_dir() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Could not find home directory")?;
+    Ok(home.join(".stagehand").join("backups"))
+}
+
+fn canonical(workdir: &Path) -> PathBuf {
+    workdir.canonicalize().unwrap_or_else(|_| workdir.to_path_buf())
+}
+
+/// Layer `content` from `owner` onto `workdir/relative_path`. The first
+/// layer backs up whatever is there; later layers from other agents, in
+/// this or another Stagehand process, are merged in. Returns the full path.
+/// Blocks on the backups lock and runs git.
+pub fn apply(owner: &str, workdir: &Path, relative_path: &str, content: &str) -> Result<PathBuf, String> {
+    apply_in(&backups_dir()?, owner, workdir, relative_path, content)
+}
+
+/// Remove `owner`'s layer. The last layer out restores the original file.
+/// Blocks on the backups lock and may run git.
+pub fn release(owner: &str, workdir: &Path, relative_path: &str) {
+    match backups_dir() {
+        Ok(backups) => release_in(&backups, owner, workdir, relative_path),
+        Err(e) => log::warn!("Failed to release {}: {}", relative_path, e),
+    }
+}
+
+/// Drop the layers of Stagehand processes that are no longer running,
+/// restoring files none are left on.
+pub fn recover_stale() {
+    if let Ok(backups) = backups_dir() {
+        recover_stale_in(&backups, instance::running);
+    }
+}
+
+fn apply_in(backups: &Path, owner: &str, workdir: &Path, relative_path: &str, content: &str) -> Result<PathBuf, String> {
+    let workdir = canonical(workdir);
+    let path = workdir.join(relative_path);
+    let _lock = FileLock::acquire(&backups.join(LOCK_FILE))?;
+
+    let mut overlay = match Overlay::find(backups, &path) {
+        Some(overlay) => overlay,
+        None => Overlay::begin(backups, &workdir, relative_path, &path)?,
+    };
+    overlay.manifest.layers.retain(|l| l.owner != owner);
+    overlay.manifest.layers.push(Layer {
+        owner: owner.to_string(),
+        pid: std::process::id(),
+        content: content.to_string(),
+    });
+
+    // The layer is on record before the file changes, so a crash in
+    // between is undone by `recover_stale`.
+    if let Err(e) = overlay.save(backups).and_then(|_| overlay.write()) {
+        overlay.manifest.layers.retain(|l| l.owner != owner);
+        overlay.settle(backups);
+        return Err(e);
+    }
+    Ok(path)
+}
+
+fn release_in(backups: &Path, owner: &str, workdir: &Path, relative_path: &str) {
+    let path = canonical(workdir).join(relative_path);
+    let _lock = match FileLock::acquire(&backups.join(LOCK_FILE)) {
+        Ok(lock) => lock,
+        Err(e) => {
+            log::warn!("Failed to release {:?}: {}", path, e);
+            return;
+        }
+    };
+    let Some(mut overlay) = Overlay::find(backups, &path) else { return };
+    if !overlay.manifest.layers.iter().any(|l| l.owner == owner) {
+        return;
+    }
+    overlay.warn_if_edited();
+    overlay.manifest.layers.retain(|l| l.owner != owner);
+    overlay.settle(backups);
+}
+
+fn recover_stale_in(backups: &Path, running: impl Fn(u32) -> bool) {
+    let _lock = match FileLock::acquire(&backups.join(LOCK_FILE)) {
+        Ok(lock) => lock,
+        Err(e) => {
+            log::warn!("Skipping backup recovery: {}", e);
+            return;
+        }
+    };
+    for mut overlay in Overlay::all(backups) {
+        let before = overlay.manifest.layers.len();
+        overlay
+            .manifest
+            .layers
+            .retain(|l| l.pid != std::process::id() && running(l.pid));
+        if overlay.manifest.layers.len() == before && before > 0 {
+            continue;
+        }
+        log::info!(
+            "Dropping {} layer(s) on {:?} left behind by exited instances",
+            before - overlay.manifest.layers.len(),
+            overlay.manifest.path
+        );
+        overlay.settle(backups);
+    }
+}
+
+impl Overlay {
+    /// Every overlay on record. Manifests whose original can't be read are
+    /// skipped, so the file is never "restored" to nothing.
+    fn all(backups: &Path) -> Vec<Overlay> {
+        let Ok(entries) = std::fs::read_dir(backups) else { return Vec::new() };
+        let mut overlays = Vec::new();
+        for entry in entries.flatten() {
+            let file = entry.path();
+            if file.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(id) = file.file_stem().and_then(|s| s.to_str()).map(str::to_string) else {
+                continue;
+            };
+            let manifest: BackupManifest = match store::read_json(&file) {
+                Ok(m) => m,
+                Err(e) => {
+                    log::warn!("Skipping unreadable backup manifest: {}", e);
+                    continue;
+                }
+            };
+            let original = if manifest.existed {
+                match std::fs::read(backups.join(format!("{}.orig", id))) {
+                    Ok(bytes) => Some(bytes),
+                    Err(e) => {
+                        log::warn!("Backup of {:?} is missing its original: {}", manifest.path, e);
+                        continue;
+                    }
+                }
+            } else {
+                None
+            };
+            overlays.push(Overlay { id, manifest, original });
+        }
+        overlays
+    }
+
+    fn find(backups: &Path, path: &Path) -> Option<Overlay> {
+        Self::all(backups).into_iter().find(|o| o.manifest.path == path)
+    }
+
+    /// Back up the file and hide it from git before the first layer lands.
+    fn begin(backups: &Path, workdir: &Path, relative_path: &str, path: &Path) -> Result<Self, String> {
+        let original = match std::fs::read(path) {
+            Ok(bytes) => Some(bytes),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(format!("Failed to read {:?}: {}", path, e)),
+        };
+
+        let mut created_dirs = Vec::new();
+        let mut dir = path.parent();
+        while let Some(d) = dir {
+            if d == workdir || d.exists() {
+                break;
+            }
+            created_dirs.push(d.to_path_buf());
+            dir = d.parent();
+        }
+
+        let tracked = git(workdir, &["ls-files", "--error-unmatch", "--", relative_path]).is_some();
+        let exclude = if !tracked && original.is_none() {
+            exclude_target(workdir, path)
+        } else {
+            None
+        };
+
+        let overlay = Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            manifest: BackupManifest {
+                path: path.to_path_buf(),
+                existed: original.is_some(),
+                created_dirs,
+                skip_worktree: tracked,
+                exclude,
+                layers: Vec::new(),
+            },
+            original,
+        };
+
+        // Record everything before touching the directory, so a crash at
+        // any point after this can be undone.
+        if let Some(ref bytes) = overlay.original {
+            let orig = backups.join(format!("{}.orig", overlay.id));
+            std::fs::write(&orig, bytes).map_err(|e| format!("Failed to back up {:?}: {}", path, e))?;
+        }
+        overlay.save(backups)?;
+
+        if let Some(dir) = overlay.manifest.created_dirs.first().cloned() {
+            if let Err(e) = std::fs::create_dir_all(&dir) {
+                overlay.restore(backups);
+                return Err(format!("Failed to create dir {:?}: {}", dir, e));
+            }
+        }
+        if overlay.manifest.skip_worktree {
+            git(workdir, &["update-index", "--skip-worktree", "--", relative_path]);
+        }
+        if let Some((ref file, ref pattern)) = overlay.manifest.exclude {
+            edit_exclude(file, pattern, true);
+        }
+        Ok(overlay)
+    }
+
+    fn save(&self, backups: &Path) -> Result<(), String> {
+        store::write_json(&backups.join(format!("{}.json", self.id)), &self.manifest)
+    }
+
+    /// The original content with every layer merged in.
+    fn merged(&self) -> Result<Vec<u8>, String> {
+        merge(
+            &self.manifest.path,
+            self.original.as_deref().unwrap_or_default(),
+            self.manifest.layers.iter().map(|l| l.content.as_str()),
+        )
+    }
+
+    fn write(&self) -> Result<(), String> {
+        let path = &self.manifest.path;
+        let content = self.merged()?;
+        std::fs::write(path, content).map_err(|e| format!("Failed to write workdir file {:?}: {}", path, e))
+    }
+
+    /// After layers were removed: restore the file if none are left,
+    /// otherwise rewrite it and the manifest with the rest.
+    fn settle(self, backups: &Path) {
+        if self.manifest.layers.is_empty() {
+            self.restore(backups);
+            return;
+        }
+        if let Err(e) = self.write().and_then(|_| self.save(backups)) {
+            log::warn!("Failed to rewrite {:?}: {}", self.manifest.path, e);
+        }
+    }
+
+    /// What is on disk should be what the layers produce; anything else was
+    /// edited during the run and is about to be overwritten.
+    fn warn_if_edited(&self) {
+        let path = &self.manifest.path;
+        if let (Ok(expected), Ok(actual)) = (self.merged(), std::fs::read(path)) {
+            if expected != actual {
+                log::warn!("{:?} was changed during the run; restoring the original anyway", path);
+            }
+        }
+    }
+
+    /// Put the file, its directories and git's view of it back as they were.
+    fn restore(self, backups: &Path) {
+        let manifest = &self.manifest;
+        match self.original {
+            Some(ref bytes) => {
+                if let Err(e) = std::fs::write(&manifest.path, bytes) {
+                    log::warn!("Failed to restore {:?}: {}", manifest.path, e);
+                    return;
+                }
+            }
+            None => {
+                let _ = std::fs::remove_file(&manifest.path);
+                for dir in &manifest.created_dirs {
+                    let _ = std::fs::remove_dir(dir);
+                }
+            }
+        }
+        if manifest.skip_worktree {
+            if let (Some(dir), Some(name)) = (manifest.path.parent(), manifest.path.file_name()) {
+                if dir.exists() {
+                    git(dir, &["update-index", "--no-skip-worktree", "--", &name.to_string_lossy()]);
+                }
+            }
+        }
+        if let Some((ref file, ref pattern)) = manifest.exclude {
+            edit_exclude(file, pattern, false);
+        }
+        let _ = std::fs::remove_file(backups.join(format!("{}.orig", self.id)));
+        let _ = std::fs::remove_file(backups.join(format!("{}.json", self.id)));
+    }
+}
+
+/// `original` with `layers` merged in, the way `MergeKind::for_path` says.
+fn merge<'a>(path: &Path, original: &[u8], layers: impl Iterator<Item = &'a str>) -> Result<Vec<u8>, String> {
+    match MergeKind::for_path(path) {
+        MergeKind::Append => {
+            let mut out = original.to_vec();
+            for layer in layers {
+                if !out.is_empty() {
+                    if !out.ends_with(b"\n") {
+                        out.push(b'\n');
+                    }
+                    out.push(b'\n');
+                }
+                out.extend_from_slice(layer.as_bytes());
+            }
+            Ok(out)
+        }
+        MergeKind::Toml => {
+            let text = std::str::from_utf8(original)
+                .map_err(|_| format!("Can't merge into {:?}: not UTF-8", path))?;
+            let mut table: toml::Table = toml::from_str(text)
+                .map_err(|e| format!("Can't merge into {:?}: {}", path, e))?;
+            for layer in layers {
+                let over: toml::Table = toml::from_str(layer)
+                    .map_err(|e| format!("Invalid TOML for {:?}: {}", path, e))?;
+                merge_toml(&mut table, over);
+            }
+            toml::to_string(&table)
+                .map(String::into_bytes)
+                .map_err(|e| format!("Failed to serialize {:?}: {}", path, e))
+        }
+        MergeKind::Json => {
+            let mut value = if original.is_empty() {
+                serde_json::Value::Object(Default::default())
+            } else {
+                serde_json::from_slice(original)
+                    .map_err(|e| format!("Can't merge into {:?}: {}", path, e))?
+            };
+            for layer in layers {
+                let over: serde_json::Value = serde_json::from_str(layer)
+                    .map_err(|e| format!("Invalid JSON for {:?}: {}", path, e))?;
+                merge_json(&mut value, over);
+            }
+            serde_json::to_vec_pretty(&value)
+                .map_err(|e| format!("Failed to serialize {:?}: {}", path, e))
+        }
+        MergeKind::Replace => Ok(layers.last().map(|l| l.as_bytes().to_vec()).unwrap_or_default()),
+    }
+}
+
+fn merge_toml(base: &mut toml::Table, over: toml::Table) {
+    for (key, value) in over {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(b)), toml::Value::Table(o)) => merge_toml(b, o),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn merge_json(base: &mut serde_json::Value, over: serde_json::Value) {
+    match (base, over) {
+        (serde_json::Value::Object(b), serde_json::Value::Object(o)) => {
+            for (key, value) in o {
+                match b.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        b.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, over) => *base = over,
+    }
+}
+
+/// Run git in `dir`, returning trimmed stdout on success.
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).current_dir(dir).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The repo's `info/exclude` file and the pattern that matches `path`.
+fn exclude_target(workdir: &Path, path: &Path) -> Option<(PathBuf, String)> {
+    let out = git(workdir, &["rev-parse", "--show-toplevel", "--git-path", "info/exclude"])?;
+    let mut lines = out.lines();
+    let toplevel = canonical(Path::new(lines.next()?));
+    let exclude = workdir.join(lines.next()?);
+    let relative = path.strip_prefix(&toplevel).ok()?;
+    let pattern = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    Some((exclude, format!("/{}", pattern)))
+}
+
+/// Add or remove one occurrence of `pattern` in Stagehand's block of the
+/// exclude file. Worktrees share the file, so the same pattern may be
+/// listed once per worktree using it. Callers hold the backups lock.
+fn edit_exclude(file: &Path, pattern: &str, add: bool) {
+    let existing = std::fs::read_to_string(file).unwrap_or_default();
+    let out = edited_exclude(&existing, pattern, add);
+    if let Some(parent) = file.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    if let Err(e) = std::fs::write(file, out) {
+        log::warn!("Failed to update {:?}: {}", file, e);
+    }
+}
+
+/// `existing` exclude file text with `pattern` added to or removed from
+/// Stagehand's block. The block is dropped once empty.
+fn edited_exclude(existing: &str, pattern: &str, add: bool) -> String {
+    let mut outside = Vec::new();
+    let mut block = Vec::new();
+    let mut in_block = false;
+    for line in existing.lines() {
+        match line {
+            EXCLUDE_BEGIN => in_block = true,
+            EXCLUDE_END => in_block = false,
+            _ if in_block => block.push(line),
+            _ => outside.push(line),
+        }
+    }
+    if add {
+        block.push(pattern);
+    } else if let Some(i) = block.iter().position(|p| *p == pattern) {
+        block.remove(i);
+    }
+
+    let mut out = outside.join("\n");
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    if !block.is_empty() {
+        out.push_str(EXCLUDE_BEGIN);
+        out.push('\n');
+        for line in &block {
+            out.push_str(line);
+            out.push('\n');
+        }
+        out.push_str(EXCLUDE_END);
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merged(name: &str, original: &str, layers: &[&str]) -> String {
+        let out = merge(Path::new(name), original.as_bytes(), layers.iter().copied()).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn markdown_layers_are_appended() {
+        assert_eq!(merged("AGENTS.md", "", &["# A", "# B"]), "# A\n\n# B");
+        assert_eq!(merged("AGENTS.md", "Existing", &["# A"]), "Existing\n\n# A");
+        assert_eq!(merged("AGENTS.md", "Existing\n", &["# A"]), "Existing\n\n# A");
+    }
+
+    #[test]
+    fn toml_tables_are_deep_merged() {
+        let original = "model = \"o3\"\n[mcp_servers.repo]\ncommand = \"repo-mcp\"\n";
+        let layer = "[mcp_servers.stagehand]\ncommand = \"node\"\n";
+        let out: toml::Table = toml::from_str(&merged("config.toml", original, &[layer])).unwrap();
+        assert_eq!(out["model"].as_str(), Some("o3"));
+        assert_eq!(out["mcp_servers"]["repo"]["command"].as_str(), Some("repo-mcp"));
+        assert_eq!(out["mcp_servers"]["stagehand"]["command"].as_str(), Some("node"));
+
+        // Later layers win over existing keys.
+        let out: toml::Table = toml::from_str(&merged("config.toml", original, &["model = \"gpt-5\""])).unwrap();
+        assert_eq!(out["model"].as_str(), Some("gpt-5"));
+        assert!(merge(Path::new("config.toml"), b"not = = toml", std::iter::once("a = 1")).is_err());
+    }
+
+    #[test]
+    fn json_objects_are_deep_merged() {
+        let original = r#"{"theme":"dark","mcpServers":{"repo":{"command":"repo-mcp"}}}"#;
+        let a = r#"{"mcpServers":{"stagehand":{"command":"node"}}}"#;
+        let b = r#"{"theme":"light","mcpServers":{"repo":{"args":["-v"]}}}"#;
+        let out: serde_json::Value = serde_json::from_str(&merged("settings.json", original, &[a, b])).unwrap();
+        assert_eq!(
+            out,
+            serde_json::json!({
+                "theme": "light",
+                "mcpServers": {
+                    "repo": { "command": "repo-mcp", "args": ["-v"] },
+                    "stagehand": { "command": "node" },
+                },
+            })
+        );
+        assert_eq!(merged("settings.json", "", &[r#"{"a":1}"#]), "{\n  \"a\": 1\n}");
+    }
+
+    #[test]
+    fn other_files_take_the_newest_layer() {
+        assert_eq!(merged("prompt.txt", "old", &["a", "b"]), "b");
+    }
+
+    #[test]
+    fn exclude_block_is_added_and_removed() {
+        let user = "*.log\n";
+        let one = edited_exclude(user, "/AGENTS.md", true);
+        assert_eq!(one, format!("*.log\n{}\n/AGENTS.md\n{}\n", EXCLUDE_BEGIN, EXCLUDE_END));
+
+        // A second worktree adds the same pattern again; it stays until
+        // both have removed it.
+        let two = edited_exclude(&one, "/AGENTS.md", true);
+        let back = edited_exclude(&two, "/AGENTS.md", false);
+        assert_eq!(back, one);
+        assert_eq!(edited_exclude(&back, "/AGENTS.md", false), user);
+
+        assert_eq!(edited_exclude("", "/x", false), "");
+        assert_eq!(edited_exclude("a\nb", "/x", false), "a\nb\n");
+    }
+
+    #[test]
+    fn layers_of_other_processes_survive_release_and_recovery() {
+        let root = std::env::temp_dir().join(format!("stagehand-overlay-{}", uuid::Uuid::new_v4()));
+        let (backups, workdir) = (root.join("backups"), root.join("work"));
+        std::fs::create_dir_all(&workdir).unwrap();
+        let file = workdir.join("settings.json");
+        std::fs::write(&file, r#"{"user":true}"#).unwrap();
+
+        apply_in(&backups, "a", &workdir, "settings.json", r#"{"a":1}"#).unwrap();
+        apply_in(&backups, "b", &workdir, "settings.json", r#"{"b":2}"#).unwrap();
+        let read = || serde_json::from_str::<serde_json::Value>(&std::fs::read_to_string(&file).unwrap()).unwrap();
+        assert_eq!(read(), serde_json::json!({"user": true, "a": 1, "b": 2}));
+
+        // A layer left by an instance that has exited is dropped on recovery.
+        let mut overlay = Overlay::find(&backups, &canonical(&workdir).join("settings.json")).unwrap();
+        overlay.manifest.layers[0].pid = 1;
+        overlay.manifest.layers[1].pid = 2;
+        overlay.save(&backups).unwrap();
+        recover_stale_in(&backups, |pid| pid == 1);
+        assert_eq!(read(), serde_json::json!({"user": true, "a": 1}));
+
+        release_in(&backups, "a", &workdir, "settings.json");
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), r#"{"user":true}"#);
+        assert!(Overlay::all(&backups).is_empty());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
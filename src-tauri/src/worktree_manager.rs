@@ -0,0 +1,541 @@
+use crate::file_lock::FileLock;
+use crate::git_repo::{self, git_err};
+use crate::store;
+use git2::{
+    Branch, BranchType, Repository, StatusOptions, Worktree, WorktreeAddOptions, WorktreeLockStatus,
+    WorktreePruneOptions,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Default home for task worktrees, inside the main checkout.
+const WORKTREES_DIR: &str = ".stagehand-worktrees";
+
+/// Which task a worktree was created for.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WorktreeRecord {
+    path: String,
+    task_id: String,
+    branch: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorktreeInfo {
+    /// git's name for the worktree, under `.git/worktrees`.
+    pub name: String,
+    pub path: String,
+    /// `None` on a detached HEAD.
+    pub branch: Option<String>,
+    pub head: Option<String>,
+    pub task_id: Option<String>,
+    pub locked: bool,
+    pub lock_reason: Option<String>,
+    /// Uncommitted changes, untracked files included.
+    pub dirty: bool,
+    /// The directory is gone; `prune` will drop the entry unless locked.
+    pub missing: bool,
+}
+
+/// Linked worktrees of the project repos, with the task each belongs to in
+/// `~/.stagehand/data/worktrees.json`.
+#[derive(Clone)]
+pub struct WorktreeManager {
+    path: Option<PathBuf>,
+    /// Held for whole operations so two creates can't race for a name.
+    /// Operations that change the records also take `lock_records`, which
+    /// excludes other Stagehand instances.
+    lock: Arc<Mutex<()>>,
+}
+
+/// What a `create` has done so far, to undo if a later step fails.
+struct Pending<'a> {
+    name: &'a str,
+    path: &'a Path,
+    parent: &'a Path,
+    branch: &'a str,
+    created_branch: bool,
+    created_parent: bool,
+}
+
+impl Pending<'_> {
+    fn rollback(&self, repo: &Repository) {
+        if let Ok(wt) = repo.find_worktree(self.name) {
+            let _ = wt.prune(Some(
+                WorktreePruneOptions::new().valid(true).locked(true).working_tree(true),
+            ));
+        }
+        let _ = std::fs::remove_dir_all(self.path);
+        if self.created_branch {
+            if let Ok(mut branch) = repo.find_branch(self.branch, BranchType::Local) {
+                let _ = branch.delete();
+            }
+        }
+        if self.created_parent {
+            // Only succeeds if nothing else was put there meanwhile.
+            let _ = std::fs::remove_dir(self.parent);
+        }
+    }
+}
+
+fn validate_task_id(task_id: &str) -> Result<(), String> {
+    if task_id.is_empty() || !task_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(format!("Invalid task id: {}", task_id));
+    }
+    Ok(())
+}
+
+/// The main checkout's repository, even when `dir` is a linked worktree.
+fn main_repo(dir: &str) -> Result<Repository, String> {
+    let repo = git_repo::open(dir)?;
+    if !repo.is_worktree() {
+        return Ok(repo);
+    }
+    Repository::open(repo.commondir()).map_err(git_err)
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    a == b || canonical(a) == canonical(b)
+}
+
+fn is_dirty(repo: &Repository) -> Result<bool, String> {
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(true).exclude_submodules(true);
+    repo.statuses(Some(&mut opts))
+        .map(|s| !s.is_empty())
+        .map_err(git_err)
+}
+
+/// Keep the default worktrees directory out of the main checkout's status.
+fn exclude_worktrees_dir(repo: &Repository) {
+    let file = repo.commondir().join("info").join("exclude");
+    let pattern = format!("/{}/", WORKTREES_DIR);
+    let existing = std::fs::read_to_string(&file).unwrap_or_default();
+    if existing.lines().any(|l| l.trim() == pattern) {
+        return;
+    }
+    let mut out = existing;
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out.push_str(&pattern);
+    out.push('\n');
+    if let Some(parent) = file.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    if let Err(e) = std::fs::write(&file, out) {
+        log::warn!("Failed to update {:?}: {}", file, e);
+    }
+}
+
+fn linked_worktrees(repo: &Repository) -> Result<Vec<Worktree>, String> {
+    let names = repo.worktrees().map_err(git_err)?;
+    Ok(names
+        .iter()
+        .flatten()
+        .filter_map(|name| repo.find_worktree(name).ok())
+        .collect())
+}
+
+fn find_worktree(repo: &Repository, path: &str) -> Result<Worktree, String> {
+    linked_worktrees(repo)?
+        .into_iter()
+        .find(|wt| same_path(wt.path(), Path::new(path)))
+        .ok_or_else(|| format!("No worktree at {}", path))
+}
+
+/// Drop entries whose directory is gone, unless they are locked. Returns
+/// their paths.
+fn prune_missing(repo: &Repository) -> Result<Vec<String>, String> {
+    let mut pruned = Vec::new();
+    for wt in linked_worktrees(repo)? {
+        if wt.is_prunable(None).unwrap_or(false) {
+            wt.prune(None).map_err(git_err)?;
+            pruned.push(wt.path().to_string_lossy().into_owned());
+        }
+    }
+    Ok(pruned)
+}
+
+fn lock_status(wt: &Worktree) -> (bool, Option<String>) {
+    match wt.is_locked() {
+        Ok(WorktreeLockStatus::Locked(reason)) => (true, reason.filter(|r| !r.is_empty())),
+        _ => (false, None),
+    }
+}
+
+fn info(wt: &Worktree, records: &[WorktreeRecord]) -> WorktreeInfo {
+    let missing = !wt.path().exists();
+    let (locked, lock_reason) = lock_status(wt);
+    let repo = if missing { None } else { Repository::open_from_worktree(wt).ok() };
+    let head = repo.as_ref().and_then(|r| r.head().ok());
+    WorktreeInfo {
+        name: wt.name().unwrap_or_default().to_string(),
+        path: wt.path().to_string_lossy().into_owned(),
+        branch: head
+            .as_ref()
+            .filter(|h| h.is_branch())
+            .and_then(|h| h.shorthand().map(str::to_string)),
+        head: head.as_ref().and_then(|h| h.target()).map(|oid| oid.to_string()),
+        task_id: records
+            .iter()
+            .find(|r| same_path(Path::new(&r.path), wt.path()))
+            .map(|r| r.task_id.clone()),
+        locked,
+        lock_reason,
+        // A worktree that can't be opened, or whose status can't be read,
+        // counts as dirty.
+        dirty: !missing && repo.as_ref().map_or(true, |r| is_dirty(r).unwrap_or(true)),
+        missing,
+    }
+}
+
+impl WorktreeManager {
+    pub fn new() -> Self {
+        Self {
+            path: store::data_file("worktrees.json"),
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    fn load(&self) -> Result<Vec<WorktreeRecord>, String> {
+        let path = self.path.as_deref().ok_or("Could not find home directory")?;
+        store::read_json(path)
+    }
+
+    fn lock_records(&self) -> Result<FileLock, String> {
+        let path = self.path.as_deref().ok_or("Could not find home directory")?;
+        store::lock_json(path)
+    }
+
+    fn save(&self, records: &[WorktreeRecord]) -> Result<(), String> {
+        let path = self.path.as_deref().ok_or("Could not find home directory")?;
+        store::write_json(path, &records)
+    }
+
+    fn forget(&self, paths: &[String]) -> Result<(), String> {
+        if paths.is_empty() {
+            return Ok(());
+        }
+        let mut records = self.load()?;
+        let before = records.len();
+        records.retain(|r| !paths.iter().any(|p| same_path(Path::new(&r.path), Path::new(p))));
+        if records.len() == before {
+            return Ok(());
+        }
+        self.save(&records)
+    }
+
+    /// Check out `branch` (default `stagehand/<task id>`) in a new worktree
+    /// at `<base_dir>/<branch>--<task id>`. `base_dir` defaults to
+    /// `.stagehand-worktrees` in the main checkout. A missing branch is
+    /// created from `base` (default HEAD). Anything done is undone if a
+    /// later step fails.
+    pub fn create(
+        &self,
+        working_directory: &str,
+        task_id: &str,
+        branch: Option<&str>,
+        base: Option<&str>,
+        base_dir: Option<&str>,
+    ) -> Result<WorktreeInfo, String> {
+        validate_task_id(task_id)?;
+        let _guard = self.lock.lock().unwrap();
+        let _file = self.lock_records()?;
+        let repo = main_repo(working_directory)?;
+        let root = repo.workdir().ok_or("Bare repositories are not supported")?.to_path_buf();
+
+        let branch = branch.map(str::to_string).unwrap_or_else(|| format!("stagehand/{}", task_id));
+        if !Branch::name_is_valid(&branch).map_err(git_err)? {
+            return Err(format!("Invalid branch name: {}", branch));
+        }
+        let name = format!("{}--{}", branch.replace('/', "--"), task_id);
+        let parent = base_dir.map(PathBuf::from).unwrap_or_else(|| root.join(WORKTREES_DIR));
+        let path = parent.join(&name);
+        if path.exists() {
+            return Err(format!("{} already exists", path.display()));
+        }
+        // A worktree whose directory was deleted by hand still holds its
+        // name and branch until pruned.
+        let pruned = prune_missing(&repo)?;
+        self.forget(&pruned)?;
+        if repo.find_worktree(&name).is_ok() {
+            return Err(format!("A worktree named {} is already registered", name));
+        }
+        if base_dir.is_none() {
+            exclude_worktrees_dir(&repo);
+        }
+
+        let created_branch = match repo.find_branch(&branch, BranchType::Local) {
+            Ok(_) => false,
+            Err(_) => {
+                let base = base.unwrap_or("HEAD");
+                let start = repo
+                    .revparse_single(base)
+                    .and_then(|o| o.peel_to_commit())
+                    .map_err(|e| format!("Unknown base {}: {}", base, e.message()))?;
+                repo.branch(&branch, &start, false).map_err(git_err)?;
+                true
+            }
+        };
+        let pending = Pending {
+            name: &name,
+            path: &path,
+            parent: &parent,
+            branch: &branch,
+            created_branch,
+            created_parent: !parent.exists(),
+        };
+
+        let added = std::fs::create_dir_all(&parent)
+            .map_err(|e| format!("Failed to create dir {:?}: {}", parent, e))
+            .and_then(|_| {
+                let reference = repo
+                    .find_reference(&format!("refs/heads/{}", branch))
+                    .map_err(git_err)?;
+                let mut opts = WorktreeAddOptions::new();
+                opts.reference(Some(&reference));
+                repo.worktree(&name, &path, Some(&opts)).map_err(git_err)
+            })
+            .and_then(|wt| {
+                let mut records = self.load()?;
+                records.retain(|r| !same_path(Path::new(&r.path), wt.path()));
+                records.push(WorktreeRecord {
+                    path: canonical(wt.path()).to_string_lossy().into_owned(),
+                    task_id: task_id.to_string(),
+                    branch: branch.clone(),
+                });
+                self.save(&records)?;
+                Ok(info(&wt, &records))
+            });
+        if added.is_err() {
+            pending.rollback(&repo);
+        }
+        added
+    }
+
+    /// The repo's linked worktrees, sorted by path.
+    pub fn list(&self, working_directory: &str) -> Result<Vec<WorktreeInfo>, String> {
+        let _guard = self.lock.lock().unwrap();
+        let repo = main_repo(working_directory)?;
+        let records = self.load()?;
+        let mut list: Vec<WorktreeInfo> = linked_worktrees(&repo)?
+            .iter()
+            .map(|wt| info(wt, &records))
+            .collect();
+        list.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(list)
+    }
+
+    /// Protect a worktree from `prune` and `remove`, e.g. while an agent is
+    /// running in it or it lives on a drive that isn't always mounted.
+    pub fn lock(&self, working_directory: &str, path: &str, reason: Option<&str>) -> Result<(), String> {
+        let _guard = self.lock.lock().unwrap();
+        let repo = main_repo(working_directory)?;
+        let wt = find_worktree(&repo, path)?;
+        if lock_status(&wt).0 {
+            return Err(format!("Worktree {} is already locked", path));
+        }
+        wt.lock(reason).map_err(git_err)
+    }
+
+    pub fn unlock(&self, working_directory: &str, path: &str) -> Result<(), String> {
+        let _guard = self.lock.lock().unwrap();
+        let repo = main_repo(working_directory)?;
+        let wt = find_worktree(&repo, path)?;
+        if !lock_status(&wt).0 {
+            return Err(format!("Worktree {} is not locked", path));
+        }
+        wt.unlock().map_err(git_err)
+    }
+
+    /// Drop worktrees whose directory no longer exists, like
+    /// `git worktree prune`. Returns their paths.
+    pub fn prune(&self, working_directory: &str) -> Result<Vec<String>, String> {
+        let _guard = self.lock.lock().unwrap();
+        let _file = self.lock_records()?;
+        let repo = main_repo(working_directory)?;
+        let pruned = prune_missing(&repo)?;
+        self.forget(&pruned)?;
+        Ok(pruned)
+    }
+
+    /// Delete a worktree and its directory, and optionally its branch.
+    /// Locked worktrees and ones with uncommitted changes are refused
+    /// unless `force` is set.
+    pub fn remove(
+        &self,
+        working_directory: &str,
+        path: &str,
+        force: bool,
+        delete_branch: bool,
+    ) -> Result<(), String> {
+        let _guard = self.lock.lock().unwrap();
+        let _file = self.lock_records()?;
+        let repo = main_repo(working_directory)?;
+        let wt = find_worktree(&repo, path)?;
+        let details = info(&wt, &[]);
+        if !force {
+            if details.locked {
+                return Err(match details.lock_reason {
+                    Some(reason) => format!("Worktree {} is locked: {}", path, reason),
+                    None => format!("Worktree {} is locked", path),
+                });
+            }
+            // Only a directory that is really gone skips the check; one
+            // git can't validate may still hold work.
+            if !details.missing || Path::new(path).exists() {
+                let dirty = Repository::open_from_worktree(&wt)
+                    .map_err(git_err)
+                    .and_then(|r| is_dirty(&r))
+                    .map_err(|e| format!("Could not check worktree {} for uncommitted changes: {}", path, e))?;
+                if dirty {
+                    return Err(format!("Worktree {} has uncommitted changes", path));
+                }
+            }
+        }
+        wt.prune(Some(
+            WorktreePruneOptions::new().valid(true).locked(force).working_tree(true),
+        ))
+        .map_err(git_err)?;
+        // Pruning removes the directory git has on record; make sure
+        // nothing is left at the path the caller knows.
+        if Path::new(path).exists() {
+            std::fs::remove_dir_all(path).map_err(|e| format!("Failed to remove {}: {}", path, e))?;
+        }
+        if delete_branch {
+            if let Some(branch) = &details.branch {
+                repo.find_branch(branch, BranchType::Local)
+                    .and_then(|mut b| b.delete())
+                    .map_err(git_err)?;
+            }
+        }
+        self.forget(&[details.path])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Signature;
+
+    /// A repo with one commit, and a manager keeping its records beside it.
+    fn setup() -> (PathBuf, String, WorktreeManager) {
+        let root = std::env::temp_dir().join(format!("stagehand-worktrees-{}", uuid::Uuid::new_v4()));
+        let dir = root.join("repo");
+        let repo = Repository::init(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "a\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("a.txt")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = Signature::now("Test", "test@example.com").unwrap();
+        repo.commit(Some("HEAD"), &sig, &sig, "first", &tree, &[]).unwrap();
+        let manager = WorktreeManager {
+            path: Some(root.join("worktrees.json")),
+            lock: Arc::default(),
+        };
+        (root, dir.to_string_lossy().into_owned(), manager)
+    }
+
+    fn has_branch(dir: &str, branch: &str) -> bool {
+        Repository::open(dir).unwrap().find_branch(branch, BranchType::Local).is_ok()
+    }
+
+    #[test]
+    fn create_checks_out_a_new_branch() {
+        let (root, dir, manager) = setup();
+        let wt = manager.create(&dir, "task-1", None, None, None).unwrap();
+        assert_eq!(wt.branch.as_deref(), Some("stagehand/task-1"));
+        assert_eq!(wt.task_id.as_deref(), Some("task-1"));
+        assert!(wt.path.ends_with(".stagehand-worktrees/stagehand--task-1--task-1"));
+        assert!(!wt.dirty && !wt.missing && !wt.locked);
+        assert!(Path::new(&wt.path).join("a.txt").exists());
+        // The worktrees directory stays out of the main checkout's status.
+        assert!(!is_dirty(&Repository::open(&dir).unwrap()).unwrap());
+
+        let listed = manager.list(&dir).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].task_id.as_deref(), Some("task-1"));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn failed_create_rolls_back() {
+        let (root, dir, manager) = setup();
+        let parent = Path::new(&dir).join(WORKTREES_DIR);
+
+        // A leftover admin directory holds the name, so adding the worktree
+        // fails after the branch and parent directory were made.
+        let name = "stagehand--task-1--task-1";
+        std::fs::create_dir_all(Path::new(&dir).join(".git/worktrees").join(name)).unwrap();
+        assert!(manager.create(&dir, "task-1", None, None, None).is_err());
+        assert!(!has_branch(&dir, "stagehand/task-1"));
+        assert!(!parent.join(name).exists());
+        assert!(!parent.exists());
+
+        let err = manager.create(&dir, "task-2", None, Some("nope"), None).unwrap_err();
+        assert!(err.contains("Unknown base nope"), "{}", err);
+        assert!(!has_branch(&dir, "stagehand/task-2"));
+
+        // A branch that was already there is kept.
+        manager.create(&dir, "task-3", Some("shared"), None, None).unwrap();
+        assert!(manager.create(&dir, "task-4", Some("shared"), None, None).is_err());
+        assert!(has_branch(&dir, "shared"));
+        assert!(!parent.join("shared--task-4").exists());
+        assert_eq!(manager.list(&dir).unwrap().len(), 1);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn remove_refuses_dirty_locked_and_damaged_worktrees() {
+        let (root, dir, manager) = setup();
+        let dirty = manager.create(&dir, "task-1", None, None, None).unwrap();
+        std::fs::write(Path::new(&dirty.path).join("new.txt"), "x").unwrap();
+        let err = manager.remove(&dir, &dirty.path, false, true).unwrap_err();
+        assert!(err.contains("uncommitted changes"), "{}", err);
+        assert!(Path::new(&dirty.path).join("new.txt").exists());
+
+        let locked = manager.create(&dir, "task-2", None, None, None).unwrap();
+        manager.lock(&dir, &locked.path, Some("agent running")).unwrap();
+        let err = manager.remove(&dir, &locked.path, false, false).unwrap_err();
+        assert!(err.contains("agent running"), "{}", err);
+        assert!(Path::new(&locked.path).exists());
+
+        // git can't validate this one, but its directory and changes remain.
+        let admin = Path::new(&dir).join(".git/worktrees").join(&dirty.name);
+        std::fs::write(admin.join("commondir"), "/nonexistent\n").unwrap();
+        let info = manager.list(&dir).unwrap().into_iter().find(|w| w.path == dirty.path).unwrap();
+        assert!(!info.missing && info.dirty);
+        assert!(manager.remove(&dir, &dirty.path, false, false).is_err());
+        assert!(Path::new(&dirty.path).join("new.txt").exists());
+
+        manager.remove(&dir, &locked.path, true, true).unwrap();
+        assert!(!Path::new(&locked.path).exists());
+        assert!(!has_branch(&dir, "stagehand/task-2"));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn prune_drops_worktrees_whose_directory_was_deleted() {
+        let (root, dir, manager) = setup();
+        let gone = manager.create(&dir, "task-1", None, None, None).unwrap();
+        let kept = manager.create(&dir, "task-2", None, None, None).unwrap();
+        std::fs::remove_dir_all(&gone.path).unwrap();
+        assert!(manager.list(&dir).unwrap().iter().any(|w| w.path == gone.path && w.missing));
+
+        assert_eq!(manager.prune(&dir).unwrap(), vec![gone.path.clone()]);
+        let listed = manager.list(&dir).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].path, kept.path);
+        let records: Vec<WorktreeRecord> = manager.load().unwrap();
+        assert_eq!(records.len(), 1);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
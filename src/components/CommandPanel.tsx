@@ -152,6 +152,22 @@ export function CommandPanel({
           sublabel: "Default agent, models",
           projectSection: "agents",
         },
+        {
+          type: "action",
+          id: "project-environment",
+          actionId: "project-settings",
+          label: "Environment",
+          sublabel: "Env profiles for agents and terminals",
+          projectSection: "environment",
+        },
+        {
+          type: "action",
+          id: "project-hooks",
+          actionId: "project-settings",
+          label: "Hooks",
+          sublabel: "Commands before and after agent runs",
+          projectSection: "hooks",
+        },
         {
           type: "action",
           id: "project-conventions",
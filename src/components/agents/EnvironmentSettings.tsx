@@ -0,0 +1,275 @@
+import { useState, useEffect, useCallback } from "react";
+import { listEnvProfiles, saveEnvProfile, deleteEnvProfile } from "../../lib/agent";
+import * as repo from "../../lib/repositories";
+import { sendNotification } from "../../lib/notifications";
+import { logger } from "../../lib/logger";
+import type { EnvProfile, EnvVar } from "../../lib/types";
+import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from "@/components/ui/select";
+import { Input } from "@/components/ui/input";
+import { Button } from "@/components/ui/button";
+import { Checkbox } from "@/components/ui/checkbox";
+
+const NO_PROFILE = "__none__";
+
+function emptyProfile(): EnvProfile {
+  return { name: "", envFiles: [], secretKeys: [], vars: [], clearEnv: false };
+}
+
+function ProfileEditor({
+  projectId,
+  profile,
+  isNew,
+  onSaved,
+  onDeleted,
+}: {
+  projectId: string;
+  profile: EnvProfile;
+  isNew: boolean;
+  onSaved: (name: string) => void;
+  onDeleted: () => void;
+}) {
+  const [draft, setDraft] = useState<EnvProfile>(profile);
+  const [envFiles, setEnvFiles] = useState((profile.envFiles ?? []).join("\n"));
+  const [secretKeys, setSecretKeys] = useState((profile.secretKeys ?? []).join(", "));
+  const [saving, setSaving] = useState(false);
+
+  const vars = draft.vars ?? [];
+  const updateVar = (i: number, patch: Partial<EnvVar>) =>
+    setDraft({ ...draft, vars: vars.map((v, j) => (j === i ? { ...v, ...patch } : v)) });
+
+  const handleSave = async () => {
+    const name = draft.name.trim();
+    if (!name) return;
+    setSaving(true);
+    try {
+      await saveEnvProfile(projectId, {
+        ...draft,
+        name,
+        envFiles: envFiles.split("\n").map((f) => f.trim()).filter(Boolean),
+        secretKeys: secretKeys.split(",").map((k) => k.trim()).filter(Boolean),
+        vars: vars.filter((v) => v.key.trim()).map((v) => ({ ...v, key: v.key.trim() })),
+      });
+      onSaved(name);
+    } catch (err) {
+      logger.error("Failed to save environment profile", err);
+      sendNotification("Failed to save profile", String(err), "error", { projectId });
+    } finally {
+      setSaving(false);
+    }
+  };
+
+  const handleDelete = async () => {
+    try {
+      await deleteEnvProfile(projectId, profile.name);
+      onDeleted();
+    } catch (err) {
+      logger.error("Failed to delete environment profile", err);
+    }
+  };
+
+  return (
+    <div className="border border-border rounded-md p-4 space-y-4">
+      <div>
+        <label className="text-sm font-medium text-foreground">Name</label>
+        <Input
+          value={draft.name}
+          onChange={(e) => setDraft({ ...draft, name: e.target.value })}
+          disabled={!isNew}
+          placeholder="e.g. staging"
+          className="mt-1.5 w-64 h-8 text-sm"
+        />
+      </div>
+
+      <div>
+        <label className="text-sm font-medium text-foreground">.env Files</label>
+        <p className="text-xs text-muted-foreground mt-0.5 mb-1.5">
+          One per line, loaded in order. Relative paths are resolved against the task's working directory.
+        </p>
+        <textarea
+          value={envFiles}
+          onChange={(e) => setEnvFiles(e.target.value)}
+          rows={2}
+          spellCheck={false}
+          placeholder=".env.local"
+          className="w-full rounded-md border border-input bg-background px-3 py-2 text-xs font-mono focus-visible:outline-none focus-visible:ring-1 focus-visible:ring-ring"
+        />
+        <Input
+          value={secretKeys}
+          onChange={(e) => setSecretKeys(e.target.value)}
+          placeholder="Secret keys from these files, comma-separated"
+          className="mt-2 font-mono text-xs h-8"
+        />
+      </div>
+
+      <div>
+        <label className="text-sm font-medium text-foreground">Variables</label>
+        <p className="text-xs text-muted-foreground mt-0.5 mb-1.5">
+          Override values from the .env files. Secret values are never shown again; leave one empty to keep it.
+        </p>
+        <div className="space-y-2">
+          {vars.map((v, i) => (
+            <div key={i} className="flex items-center gap-2">
+              <Input
+                value={v.key}
+                onChange={(e) => updateVar(i, { key: e.target.value })}
+                placeholder="KEY"
+                className="w-48 font-mono text-xs h-8"
+              />
+              <Input
+                type={v.secret ? "password" : "text"}
+                value={v.value ?? ""}
+                onChange={(e) => updateVar(i, { value: v.secret && !e.target.value ? null : e.target.value })}
+                placeholder={v.secret && v.value === null ? "(stored)" : "value"}
+                className="flex-1 font-mono text-xs h-8"
+              />
+              <label className="flex items-center gap-1.5 text-xs text-muted-foreground">
+                <Checkbox
+                  checked={!!v.secret}
+                  onCheckedChange={(checked) => updateVar(i, { secret: checked === true })}
+                />
+                Secret
+              </label>
+              <button
+                onClick={() => setDraft({ ...draft, vars: vars.filter((_, j) => j !== i) })}
+                className="text-muted-foreground hover:text-destructive px-1"
+              >
+                &times;
+              </button>
+            </div>
+          ))}
+        </div>
+        <Button
+          variant="outline"
+          size="sm"
+          className="mt-2"
+          onClick={() => setDraft({ ...draft, vars: [...vars, { key: "", value: "", secret: false }] })}
+        >
+          Add variable
+        </Button>
+      </div>
+
+      <label className="flex items-center gap-2 text-sm text-foreground">
+        <Checkbox
+          checked={!!draft.clearEnv}
+          onCheckedChange={(checked) => setDraft({ ...draft, clearEnv: checked === true })}
+        />
+        Start from a clean environment
+        <span className="text-xs text-muted-foreground">(keeps PATH, HOME and a few essentials)</span>
+      </label>
+
+      <div className="flex items-center gap-2">
+        <Button size="sm" onClick={handleSave} disabled={saving || !draft.name.trim()}>
+          Save profile
+        </Button>
+        {!isNew && (
+          <Button variant="ghost" size="sm" className="text-destructive hover:text-destructive" onClick={handleDelete}>
+            Delete
+          </Button>
+        )}
+      </div>
+    </div>
+  );
+}
+
+export function EnvironmentSettingsContent({ projectId }: { projectId: string }) {
+  const [profiles, setProfiles] = useState<EnvProfile[]>([]);
+  const [selected, setSelected] = useState<string>("");
+  const [editing, setEditing] = useState<string | null>(null);
+  const [loading, setLoading] = useState(true);
+
+  const reload = useCallback(async () => {
+    const [list, current] = await Promise.all([
+      listEnvProfiles(projectId),
+      repo.getEnvProfile(projectId),
+    ]);
+    setProfiles(list);
+    setSelected(current ?? "");
+    setLoading(false);
+  }, [projectId]);
+
+  useEffect(() => {
+    setEditing(null);
+    reload().catch((err) => logger.error("Failed to load environment profiles", err));
+  }, [reload]);
+
+  const handleSelect = async (value: string) => {
+    if (value === NO_PROFILE) {
+      setSelected("");
+      await repo.deleteProjectSetting(projectId, "env_profile");
+    } else {
+      setSelected(value);
+      await repo.setProjectSetting(projectId, "env_profile", value);
+    }
+  };
+
+  const handleDeleted = async (name: string) => {
+    if (selected === name) await handleSelect(NO_PROFILE);
+    setEditing(null);
+    await reload();
+  };
+
+  if (loading) return null;
+
+  const editingProfile = editing === "" ? emptyProfile() : profiles.find((p) => p.name === editing);
+
+  return (
+    <div>
+      <h2 className="text-lg font-semibold text-foreground">Environment</h2>
+      <p className="text-sm text-muted-foreground mt-1 mb-6">
+        Named sets of environment variables for agent runs and terminals, e.g. API keys or a staging database URL.
+      </p>
+
+      <div className="border border-border rounded-md p-4 mb-4">
+        <label className="text-sm font-medium text-foreground">Active Profile</label>
+        <p className="text-xs text-muted-foreground mt-0.5 mb-2">
+          Applied to every stage run and terminal in this project.
+        </p>
+        <Select value={selected || NO_PROFILE} onValueChange={handleSelect}>
+          <SelectTrigger className="w-64">
+            <SelectValue />
+          </SelectTrigger>
+          <SelectContent>
+            <SelectItem value={NO_PROFILE}>
+              <span className="text-muted-foreground">None (inherit Stagehand's environment)</span>
+            </SelectItem>
+            {profiles.map((p) => (
+              <SelectItem key={p.name} value={p.name}>
+                {p.name}
+              </SelectItem>
+            ))}
+          </SelectContent>
+        </Select>
+      </div>
+
+      <div className="flex items-center gap-1.5 flex-wrap mb-4">
+        {profiles.map((p) => (
+          <Button
+            key={p.name}
+            variant={editing === p.name ? "secondary" : "outline"}
+            size="sm"
+            onClick={() => setEditing(p.name)}
+          >
+            {p.name}
+          </Button>
+        ))}
+        <Button variant="ghost" size="sm" onClick={() => setEditing("")}>
+          + New profile
+        </Button>
+      </div>
+
+      {editingProfile && (
+        <ProfileEditor
+          key={editing}
+          projectId={projectId}
+          profile={editingProfile}
+          isNew={editing === ""}
+          onSaved={async (name) => {
+            await reload();
+            setEditing(name);
+          }}
+          onDeleted={() => handleDeleted(editingProfile.name)}
+        />
+      )}
+    </div>
+  );
+}
@@ -0,0 +1,210 @@
+import { useState, useEffect } from "react";
+import { getProjectHooks, saveProjectHooks } from "../../lib/agent";
+import * as repo from "../../lib/repositories";
+import { sendNotification } from "../../lib/notifications";
+import { logger } from "../../lib/logger";
+import type { HookCommand, HookFailurePolicy, HookPhase, HookSet, ProjectHooks, StageTemplate } from "../../lib/types";
+import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from "@/components/ui/select";
+import { Input } from "@/components/ui/input";
+import { Button } from "@/components/ui/button";
+
+const PROJECT_SCOPE = "__project__";
+
+const FAILURE_LABELS: Record<HookFailurePolicy, string> = {
+  abort: "Fail the run",
+  warn: "Warn and continue",
+  feedback: "Send output to the agent",
+};
+
+const PHASES: { phase: HookPhase; label: string; hint: string }[] = [
+  { phase: "pre", label: "Before the agent runs", hint: "e.g. npm ci" },
+  { phase: "post", label: "After the agent finishes", hint: "e.g. cargo fmt && cargo clippy" },
+];
+
+function HookList({
+  hooks,
+  placeholder,
+  onChange,
+}: {
+  hooks: HookCommand[];
+  placeholder: string;
+  onChange: (hooks: HookCommand[]) => void;
+}) {
+  const update = (i: number, patch: Partial<HookCommand>) =>
+    onChange(hooks.map((h, j) => (j === i ? { ...h, ...patch } : h)));
+
+  return (
+    <div className="space-y-2">
+      {hooks.map((hook, i) => (
+        <div key={i} className="flex items-center gap-2">
+          <Input
+            value={hook.command}
+            onChange={(e) => update(i, { command: e.target.value })}
+            placeholder={placeholder}
+            spellCheck={false}
+            className="flex-1 font-mono text-xs h-8"
+          />
+          <Select
+            value={hook.onFailure ?? "abort"}
+            onValueChange={(v) => update(i, { onFailure: v as HookFailurePolicy })}
+          >
+            <SelectTrigger className="w-52 h-8 text-xs">
+              <SelectValue />
+            </SelectTrigger>
+            <SelectContent>
+              {(Object.keys(FAILURE_LABELS) as HookFailurePolicy[]).map((p) => (
+                <SelectItem key={p} value={p}>
+                  {FAILURE_LABELS[p]}
+                </SelectItem>
+              ))}
+            </SelectContent>
+          </Select>
+          <Input
+            type="number"
+            min={1}
+            value={hook.timeoutSecs ?? ""}
+            onChange={(e) => update(i, { timeoutSecs: e.target.value ? Number(e.target.value) : undefined })}
+            placeholder="Timeout (s)"
+            className="w-28 text-xs h-8"
+          />
+          <button
+            onClick={() => onChange(hooks.filter((_, j) => j !== i))}
+            className="text-muted-foreground hover:text-destructive px-1"
+          >
+            &times;
+          </button>
+        </div>
+      ))}
+      <Button
+        variant="outline"
+        size="sm"
+        onClick={() => onChange([...hooks, { command: "", onFailure: "abort" }])}
+      >
+        Add command
+      </Button>
+    </div>
+  );
+}
+
+export function HookSettingsContent({ projectId }: { projectId: string }) {
+  const [hooks, setHooks] = useState<ProjectHooks>({});
+  const [templates, setTemplates] = useState<StageTemplate[]>([]);
+  const [scope, setScope] = useState(PROJECT_SCOPE);
+  const [dirty, setDirty] = useState(false);
+  const [loading, setLoading] = useState(true);
+
+  useEffect(() => {
+    let cancelled = false;
+    setLoading(true);
+    Promise.all([getProjectHooks(projectId), repo.listStageTemplates(projectId)])
+      .then(([h, t]) => {
+        if (cancelled) return;
+        setHooks(h);
+        setTemplates(t);
+        setScope(PROJECT_SCOPE);
+        setDirty(false);
+        setLoading(false);
+      })
+      .catch((err) => logger.error("Failed to load hooks", err));
+    return () => { cancelled = true; };
+  }, [projectId]);
+
+  const current: HookSet =
+    (scope === PROJECT_SCOPE ? hooks.project : hooks.stages?.[scope]) ?? {};
+
+  const setCurrent = (set: HookSet) => {
+    setHooks(
+      scope === PROJECT_SCOPE
+        ? { ...hooks, project: set }
+        : { ...hooks, stages: { ...hooks.stages, [scope]: set } },
+    );
+    setDirty(true);
+  };
+
+  const handleSave = async () => {
+    const clean = (set?: HookSet): HookSet => ({
+      pre: (set?.pre ?? []).filter((h) => h.command.trim()),
+      post: (set?.post ?? []).filter((h) => h.command.trim()),
+    });
+    const stages = Object.fromEntries(
+      Object.entries(hooks.stages ?? {})
+        .map(([id, set]) => [id, clean(set)] as const)
+        .filter(([, set]) => set.pre!.length > 0 || set.post!.length > 0),
+    );
+    const next = { ...hooks, project: clean(hooks.project), stages };
+    try {
+      await saveProjectHooks(projectId, next);
+      setHooks(next);
+      setDirty(false);
+    } catch (err) {
+      logger.error("Failed to save hooks", err);
+      sendNotification("Failed to save hooks", String(err), "error", { projectId });
+    }
+  };
+
+  if (loading) return null;
+
+  return (
+    <div>
+      <h2 className="text-lg font-semibold text-foreground">Hooks</h2>
+      <p className="text-sm text-muted-foreground mt-1 mb-6">
+        Shell commands run in the task's working directory around every agent run. Stage hooks run after the project's.
+      </p>
+
+      <div className="border border-border rounded-md p-4 space-y-4">
+        <div>
+          <label className="text-sm font-medium text-foreground">Applies to</label>
+          <Select value={scope} onValueChange={setScope}>
+            <SelectTrigger className="w-64 mt-1.5">
+              <SelectValue />
+            </SelectTrigger>
+            <SelectContent>
+              <SelectItem value={PROJECT_SCOPE}>All stages</SelectItem>
+              {templates.map((t) => (
+                <SelectItem key={t.id} value={t.id}>
+                  {t.name}
+                  {(hooks.stages?.[t.id]?.pre?.length || hooks.stages?.[t.id]?.post?.length) ? " •" : ""}
+                </SelectItem>
+              ))}
+            </SelectContent>
+          </Select>
+        </div>
+
+        {PHASES.map(({ phase, label, hint }) => (
+          <div key={phase}>
+            <label className="text-sm font-medium text-foreground">{label}</label>
+            <div className="mt-1.5">
+              <HookList
+                hooks={current[phase] ?? []}
+                placeholder={hint}
+                onChange={(list) => setCurrent({ ...current, [phase]: list })}
+              />
+            </div>
+          </div>
+        ))}
+
+        <div>
+          <label className="text-sm font-medium text-foreground">Feedback Rounds</label>
+          <p className="text-xs text-muted-foreground mt-0.5 mb-2">
+            How many times failing after-run commands may send the agent back to fix them.
+          </p>
+          <Input
+            type="number"
+            min={0}
+            value={hooks.maxFeedbackRounds ?? ""}
+            onChange={(e) => {
+              setHooks({ ...hooks, maxFeedbackRounds: e.target.value ? Number(e.target.value) : undefined });
+              setDirty(true);
+            }}
+            placeholder="2"
+            className="w-24 text-xs h-8"
+          />
+        </div>
+
+        <Button size="sm" onClick={handleSave} disabled={!dirty}>
+          Save hooks
+        </Button>
+      </div>
+    </div>
+  );
+}
@@ -2,9 +2,10 @@ import { useState, useCallback, useEffect, useRef, useMemo } from "react";
 import { useProjectStore } from "../../stores/projectStore";
 import { useTaskStore } from "../../stores/taskStore";
 import { useProcessStore, type TerminalTab } from "../../stores/processStore";
-import { spawnPty, killPty, checkAgentAvailable } from "../../lib/agent";
+import { useSettingsStore } from "../../stores/settingsStore";
+import { spawnPty, killPty, checkAgentAvailable, listPtys, attachPty } from "../../lib/agent";
 import { getTaskWorkingDir } from "../../lib/worktree";
-import { routePtyOutput, clearPtyBuffer } from "../../lib/ptyRouter";
+import { routePtyOutput, clearPtyBuffer, decodePtyBytes } from "../../lib/ptyRouter";
 import * as repo from "../../lib/repositories";
 import { sendNotification } from "../../lib/notifications";
 import { logger } from "../../lib/logger";
@@ -21,6 +22,9 @@ interface Props {
 
 const AGENTS_TO_CHECK = ["claude", "codex"] as const;
 
+/** PTY ids already reattached to a tab in this page load. */
+const reattachedPtys = new Set<string>();
+
 function tabLabel(agent: string): string {
   if (agent === "shell") return "Terminal";
   return agent.charAt(0).toUpperCase() + agent.slice(1);
@@ -70,6 +74,79 @@ export function IntegratedTerminal({ taskId, isVisible }: Props) {
     return () => { cancelled = true; };
   }, []);
 
+  // Route a PTY's events to its tab; shared by spawned and reattached sessions
+  const ptyEventHandler = useCallback((tabId: string) => (event: PtyEvent) => {
+    switch (event.type) {
+      case "started":
+        tabPtyIds.current.set(tabId, event.id);
+        useProcessStore.getState().updateTerminalTab(tabId, { ptyId: event.id });
+        break;
+      case "output":
+        routePtyOutput(tabId, event.data);
+        break;
+      case "output_bytes":
+        routePtyOutput(tabId, decodePtyBytes(event.data));
+        break;
+      case "attention": {
+        const state = useProcessStore.getState();
+        const isCurrentlyVisible = state.activeView === "terminal" && useTaskStore.getState().activeTask?.id === taskId;
+        if (!isCurrentlyVisible && activeProject) {
+          sendNotification(
+            event.reason === "permission_prompt" ? "Terminal needs permission" : "Terminal is waiting for input",
+            task?.title ?? "Terminal needs attention",
+            "info",
+            { projectId: activeProject.id, taskId, openTerminal: true },
+          );
+        }
+        break;
+      }
+      case "exited": {
+        tabPtyIds.current.delete(tabId);
+        useProcessStore.getState().updateTerminalTab(tabId, { ptyId: null, status: "exited" });
+        const state = useProcessStore.getState();
+        const isCurrentlyVisible = state.activeView === "terminal" && useTaskStore.getState().activeTask?.id === taskId;
+        if (!isCurrentlyVisible && activeProject) {
+          sendNotification(
+            "Terminal session ended",
+            task?.title ?? "Terminal session ended",
+            "info",
+            { projectId: activeProject.id, taskId, openTerminal: true },
+          );
+        }
+        break;
+      }
+      case "error":
+        setError(event.message);
+        useProcessStore.getState().removeTerminalTab(taskId, tabId);
+        break;
+    }
+  }, [activeProject, task, taskId]);
+
+  // After a reload the store is empty but the task's PTYs are still running:
+  // give each one a tab again and reattach, replaying its scrollback.
+  useEffect(() => {
+    let cancelled = false;
+    listPtys()
+      .then((sessions) => {
+        if (cancelled) return;
+        const known = new Set(
+          Object.values(useProcessStore.getState().terminalTabs).map((t) => t.ptyId),
+        );
+        for (const session of sessions) {
+          if (session.taskId !== taskId || known.has(session.id) || reattachedPtys.has(session.id)) continue;
+          reattachedPtys.add(session.id);
+          const tabId = useProcessStore.getState().addTerminalTab(taskId, session.agent ?? session.command ?? "shell");
+          attachPty(session.id, ptyEventHandler(tabId)).catch((err) => {
+            reattachedPtys.delete(session.id);
+            useProcessStore.getState().removeTerminalTab(taskId, tabId);
+            logger.error("IntegratedTerminal reattach failed", err);
+          });
+        }
+      })
+      .catch((err) => logger.error("IntegratedTerminal listPtys failed", err));
+    return () => { cancelled = true; };
+  }, [taskId, ptyEventHandler]);
+
   const handleSpawn = useCallback(async (agent: string) => {
     if (!activeProject || !task) {
       logger.warn("IntegratedTerminal handleSpawn: no activeProject or task", { activeProject: !!activeProject, task: !!task });
@@ -105,6 +182,8 @@ export function IntegratedTerminal({ taskId, isVisible }: Props) {
 
       const workDir = getTaskWorkingDir(task, activeProject.path);
 
+      const envProfile = await repo.getEnvProfile(activeProject.id);
+
       // Create the tab in the store (status: running, ptyId: null until started event)
       const tabId = useProcessStore.getState().addTerminalTab(taskId, agent);
 
@@ -113,37 +192,13 @@ export function IntegratedTerminal({ taskId, isVisible }: Props) {
           agent: isRawShell ? undefined : agent,
           workingDirectory: workDir,
           appendSystemPrompt: systemPrompt,
+          shellIntegration: isRawShell,
+          taskId,
+          persistent: useSettingsStore.getState().persistentTerminals && useSettingsStore.getState().persistentTerminalsAvailable,
+          projectId: activeProject.id,
+          envProfile,
         },
-        (event: PtyEvent) => {
-          switch (event.type) {
-            case "started":
-              tabPtyIds.current.set(tabId, event.id);
-              useProcessStore.getState().updateTerminalTab(tabId, { ptyId: event.id });
-              break;
-            case "output":
-              routePtyOutput(tabId, event.data);
-              break;
-            case "exited": {
-              tabPtyIds.current.delete(tabId);
-              useProcessStore.getState().updateTerminalTab(tabId, { ptyId: null, status: "exited" });
-              const state = useProcessStore.getState();
-              const isCurrentlyVisible = state.activeView === "terminal" && useTaskStore.getState().activeTask?.id === taskId;
-              if (!isCurrentlyVisible && activeProject) {
-                sendNotification(
-                  "Terminal session ended",
-                  task?.title ?? "Terminal session ended",
-                  "info",
-                  { projectId: activeProject.id, taskId, openTerminal: true },
-                );
-              }
-              break;
-            }
-            case "error":
-              setError(event.message);
-              useProcessStore.getState().removeTerminalTab(taskId, tabId);
-              break;
-          }
-        },
+        ptyEventHandler(tabId),
       );
     } catch (err) {
       const msg = err instanceof Error ? err.message : String(err);
@@ -152,7 +207,7 @@ export function IntegratedTerminal({ taskId, isVisible }: Props) {
     } finally {
       setSpawning(false);
     }
-  }, [activeProject, task, taskId]);
+  }, [activeProject, task, taskId, ptyEventHandler]);
 
   const handleCloseTab = useCallback(async (tab: TerminalTab) => {
     // Kill PTY if running
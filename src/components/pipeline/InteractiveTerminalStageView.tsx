@@ -280,21 +280,44 @@ export function InteractiveTerminalStageView({ stage, taskId, isVisible }: Props
         systemPrompt = `${systemPrompt}\n\n${stageContext}`;
       }
 
-      // Build MCP config
-      let mcpHint = "";
+      // Build MCP config for stage context server (mirrors useStageExecution)
+      let mcpConfig: string | undefined;
       try {
         const mcpServerPath = await invoke<string>("get_mcp_server_path");
         const stagehandDir = await invoke<string>("get_stagehand_dir");
-        // Verify MCP server is available so we can hint the agent about it
-        void mcpServerPath;
-        void stagehandDir;
-        mcpHint =
-          "You have access to `list_completed_stages`, `get_stage_output`, and `get_task_description` tools to retrieve data from prior pipeline stages on demand.";
+        mcpConfig = JSON.stringify({
+          mcpServers: {
+            "stagehand-context": {
+              command: "node",
+              args: [mcpServerPath],
+              env: {
+                STAGEHAND_DB_PATH: `${stagehandDir}/data/${activeProject.id}.db`,
+                STAGEHAND_TASK_ID: task.id,
+              },
+            },
+          },
+        });
+        const mcpHint =
+          "You have access to `list_completed_stages`, `get_stage_output`, and `get_task_title` tools to retrieve data from prior pipeline stages on demand.";
         systemPrompt = `${systemPrompt}\n\n${mcpHint}`;
       } catch {
         // MCP unavailable — continue without it
       }
 
+      let allowedTools: string[] | undefined;
+      if (stage.allowed_tools) {
+        try {
+          allowedTools = JSON.parse(stage.allowed_tools);
+        } catch {
+          // ignore
+        }
+      }
+
+      // Continue the previous attempt's Claude session (headless or
+      // interactive) if there is one, so the agent keeps its context.
+      const resumeSessionId = prevAttempts[prevAttempts.length - 1]?.session_id ?? null;
+      const sessionId = resumeSessionId ?? crypto.randomUUID();
+
       // Create execution record
       await repo.createStageExecution(activeProject.id, {
         id: executionId,
@@ -307,7 +330,7 @@ export function InteractiveTerminalStageView({ stage, taskId, isVisible }: Props
         raw_output: null,
         parsed_output: null,
         user_decision: null,
-        session_id: null,
+        session_id: sessionId,
         error_message: null,
         thinking_output: null,
         stage_result: null,
@@ -343,6 +366,7 @@ export function InteractiveTerminalStageView({ stage, taskId, isVisible }: Props
       // Resolve effective agent + model for the PTY session
       const effectiveAgent = await repo.getEffectiveAgent(activeProject.id, stage.agent_override, stage.agent);
       const effectiveModel = await repo.getEffectiveModel(activeProject.id, stage.model_override, stage.persona_model);
+      const envProfile = await repo.getEnvProfile(activeProject.id);
       effectiveAgentRef.current = effectiveAgent;
       effectiveModelRef.current = effectiveModel;
 
@@ -354,6 +378,12 @@ export function InteractiveTerminalStageView({ stage, taskId, isVisible }: Props
           personaModel: effectiveModel,
           workingDirectory: workDir,
           appendSystemPrompt: systemPrompt,
+          mcpConfig,
+          allowedTools,
+          sessionId,
+          resume: resumeSessionId !== null,
+          projectId: activeProject.id,
+          envProfile,
         },
         (event: PtyEvent) => {
           switch (event.type) {
@@ -365,6 +395,20 @@ export function InteractiveTerminalStageView({ stage, taskId, isVisible }: Props
               xtermRef.current?.write(event.data);
               outputBufferRef.current += event.data;
               break;
+            case "attention": {
+              const isCurrentlyVisible =
+                useProcessStore.getState().activeView === "pipeline" &&
+                useTaskStore.getState().activeTask?.id === task.id;
+              if (!isCurrentlyVisible) {
+                sendNotification(
+                  event.reason === "permission_prompt" ? `${stage.name} needs permission` : `${stage.name} is waiting for input`,
+                  task.title,
+                  "info",
+                  { projectId: activeProject.id, taskId: task.id },
+                );
+              }
+              break;
+            }
             case "exited":
               ptyIdRef.current = null;
               // If we were still "running", the user didn't click Finish — Claude exited on its own
@@ -431,6 +475,8 @@ export function InteractiveTerminalStageView({ stage, taskId, isVisible }: Props
           noSessionPersistence: true,
           allowedTools: [],
           maxTurns: 1,
+          projectId: activeProject.id,
+          envProfile: await repo.getEnvProfile(activeProject.id),
         },
         (event: AgentStreamEvent) => {
           if (event.type === "stdout_line") {
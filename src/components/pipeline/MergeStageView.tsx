@@ -17,7 +17,9 @@ import {
   gitCurrentBranch,
   hasUncommittedChanges,
   gitDiffFileStats,
+  checkMergeConflicts,
   type DiffFileStat,
+  type MergeConflict,
 } from "../../lib/git";
 import { DiffFileList } from "./DiffFileList";
 import { logger } from "../../lib/logger";
@@ -38,6 +40,15 @@ import { useProcessStore, stageKey } from "../../stores/processStore";
 import type { MergeState } from "../../stores/processStore";
 import type { TaskStageInstance, AgentStreamEvent } from "../../lib/types";
 
+const CONFLICT_LABELS: Record<MergeConflict["kind"], string> = {
+  content: "content",
+  add_add: "added on both sides",
+  modify_delete: "modified and deleted",
+  rename_delete: "renamed and deleted",
+  rename_rename: "renamed differently",
+  mode: "file mode",
+};
+
 /**
  * MergeStageView intentionally bypasses the standard useStageExecution hook and
  * manages its own execution lifecycle (create, approve/fail, task completion,
@@ -93,6 +104,7 @@ export function MergeStageView({ stage }: MergeStageViewProps) {
   const [fixCommitting, setFixCommitting] = useState(false);
   const [fixCommitError, setFixCommitError] = useState<string | null>(null);
   const [targetIsDirty, setTargetIsDirty] = useState(false);
+  const [mergeConflicts, setMergeConflicts] = useState<MergeConflict[]>([]);
   const [dirtyStrategy, setDirtyStrategy] = useState<DirtyMergeStrategy>("stash_merge_pop");
 
   // Keep local targetBranch in sync with the GitHub store so changes
@@ -161,10 +173,13 @@ export function MergeStageView({ stage }: MergeStageViewProps) {
         }
 
         const diffBase = defaultBr;
-        const [files, stat, fStats] = await Promise.all([
+        const [files, stat, fStats, conflicts] = await Promise.all([
           gitDiffNameOnly(workDir, diffBase).catch(() => [] as string[]),
           gitDiffStatBranch(workDir, diffBase).catch(() => ""),
           gitDiffFileStats(workDir, diffBase).catch(() => [] as DiffFileStat[]),
+          checkMergeConflicts(activeProject.path, defaultBr, branchName)
+            .then((check) => check.conflicts)
+            .catch(() => [] as MergeConflict[]),
         ]);
 
         // Check if the target branch is checked out with dirty changes
@@ -180,6 +195,7 @@ export function MergeStageView({ stage }: MergeStageViewProps) {
           setChangedFiles(files);
           setDiffStat(stat);
           setFileStats(fStats);
+          setMergeConflicts(conflicts);
           setTargetIsDirty(dirty);
           // Only transition to preview on first load; keep persisted state otherwise
           if (mergeState === "loading") setMergeState("preview");
@@ -196,14 +212,20 @@ export function MergeStageView({ stage }: MergeStageViewProps) {
   useEffect(() => {
     if (!activeProject || !activeTask?.branch_name || mergeState !== "preview") return;
     const workDir = getTaskWorkingDir(activeTask, activeProject.path);
+    const projectPath = activeProject.path;
+    const branchName = activeTask.branch_name;
     const defaultBr = targetBranch;
     const refresh = () => {
       Promise.all([
         gitDiffNameOnly(workDir, defaultBr).catch(() => [] as string[]),
         gitDiffFileStats(workDir, defaultBr).catch(() => [] as DiffFileStat[]),
-      ]).then(([files, fStats]) => {
+        checkMergeConflicts(projectPath, defaultBr, branchName)
+          .then((check) => check.conflicts)
+          .catch(() => [] as MergeConflict[]),
+      ]).then(([files, fStats, conflicts]) => {
         setChangedFiles(files);
         setFileStats(fStats);
+        setMergeConflicts(conflicts);
       });
     };
     refreshRef.current = setInterval(refresh, 10_000);
@@ -612,6 +634,27 @@ Investigate and fix the issue (e.g. resolve merge conflicts, fix compatibility p
           </div>
         )}
 
+        {mergeConflicts.length > 0 && !error && (
+          <div className="mb-3 p-3 bg-amber-50 dark:bg-amber-500/10 border border-amber-200 dark:border-amber-500/20 rounded-lg">
+            <p className="text-xs font-medium text-amber-800 dark:text-amber-300 mb-1">
+              Merging will conflict in {mergeConflicts.length} file{mergeConflicts.length !== 1 ? "s" : ""}
+            </p>
+            <div className="max-h-32 overflow-y-auto text-xs font-mono text-amber-900 dark:text-amber-200">
+              {mergeConflicts.map((c) => (
+                <div key={`${c.kind}:${c.path}`}>
+                  {c.path}
+                  <span className="text-amber-700 dark:text-amber-400">
+                    {" "}({CONFLICT_LABELS[c.kind]}{c.hunks.length > 0 ? `, ${c.hunks.length} hunk${c.hunks.length !== 1 ? "s" : ""}` : ""})
+                  </span>
+                </div>
+              ))}
+            </div>
+            <p className="text-xs text-amber-800 dark:text-amber-300 mt-2">
+              Based on committed changes. Resolve them in the task branch first, or merge and resolve afterwards.
+            </p>
+          </div>
+        )}
+
         {error && (
           <div className="mb-3 space-y-2">
             <Alert variant="destructive">
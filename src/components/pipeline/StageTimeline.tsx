@@ -100,10 +100,13 @@ export function LiveStreamBubble({
   stageKey: sk,
   label,
   onStop,
+  onTakeOver,
 }: {
   stageKey: string;
   label: string;
   onStop: () => void;
+  /** Continue the run by hand in an interactive terminal. */
+  onTakeOver?: () => void;
 }) {
   const streamOutput = useProcessStore((s) => s.stages[sk]?.streamOutput ?? EMPTY_LINES);
   const isStopping = useProcessStore((s) => s.stages[sk]?.killed ?? false);
@@ -138,6 +141,11 @@ export function LiveStreamBubble({
               Stop
             </Button>
           )}
+          {!isStopping && onTakeOver && (
+            <Button variant="ghost" size="xs" onClick={onTakeOver}>
+              Take over
+            </Button>
+          )}
         </div>
         <div className="border border-border rounded-lg overflow-hidden bg-zinc-50 dark:bg-zinc-900">
           <div
@@ -55,7 +55,7 @@ export function StageView({ stage, taskId }: StageViewProps) {
   const pendingCommit = useProcessStore((s) => s.pendingCommit);
   const committedHash = useProcessStore((s) => s.committedStages[sid]);
   const noChangesToCommit = useProcessStore((s) => s.noChangesStageId === sid);
-  const { runStage, approveStage, suggestNextStage, chooseNextStage, redoStage, killCurrent } =
+  const { runStage, approveStage, suggestNextStage, chooseNextStage, redoStage, killCurrent, takeOverCurrent } =
     useStageExecution();
   useProcessHealthCheck(sid, taskId);
   const [userInput, setUserInput] = usePersistedState(`stage_input:${sk}`);
@@ -78,6 +78,16 @@ export function StageView({ stage, taskId }: StageViewProps) {
   const [nextSuggestionReason, setNextSuggestionReason] = useState<string | null>(initialSuggestion?.reason ?? null);
   const [selectedNextTemplateId, setSelectedNextTemplateId] = useState<string | null>(initialSuggestion?.suggestedTemplateId ?? null);
   const [stageError, setStageError] = useState<string | null>(null);
+  // Agent of the running headless run; only Claude runs can be taken over
+  const [runAgent, setRunAgent] = useState<string | null>(null);
+  useEffect(() => {
+    if (!isRunning || !activeProject) return;
+    let cancelled = false;
+    repo.getEffectiveAgent(activeProject.id, null, stage.agent)
+      .then((agent) => { if (!cancelled) setRunAgent(agent); })
+      .catch(() => {});
+    return () => { cancelled = true; };
+  }, [isRunning, activeProject, stage.agent]);
   const [commitPrepTimedOut, setCommitPrepTimedOut] = useState(false);
   // Terminal output formats whose next stage is fixed (not user-selectable)
   const TERMINAL_FORMATS = ["pr_preparation", "pr_review", "merge"] as const;
@@ -812,6 +822,7 @@ Investigate the error (read files, run checks) and fix the issue. Do NOT run git
               stageKey={sk}
               label={`${stage.name} working...`}
               onStop={() => killCurrent(task!.id, sid)}
+              onTakeOver={runAgent === "claude" && isRunning ? () => takeOverCurrent(task!.id, sid) : undefined}
             />
           )}
 
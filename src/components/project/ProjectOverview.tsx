@@ -17,6 +17,7 @@ import { ChevronDown } from "lucide-react";
 import { formatRelativeTime, formatTokenCount, formatDuration } from "../../lib/format";
 import { gitDiffShortStatBranch } from "../../lib/git";
 import { getTaskWorkingDir } from "../../lib/worktree";
+import { useTaskOverlaps } from "../../hooks/useTaskOverlaps";
 import type { Task } from "../../lib/types";
 
 export function ProjectOverview() {
@@ -70,6 +71,13 @@ export function ProjectOverview() {
     }
   }, [tasks.length, pausedTasks.length, archivedTasks.length]);
 
+  const { report: overlapReport, error: overlapError } = useTaskOverlaps(
+    activeProject?.id,
+    activeProject?.path,
+    defaultBranch,
+    tasks,
+  );
+
   const taskIds = tasks.map((t) => t.id).join(",");
 
   useEffect(() => {
@@ -121,6 +129,7 @@ export function ProjectOverview() {
 
   const awaitingTasks = tasks.filter((t) => taskExecStatuses[t.id] === "awaiting_user");
   const sortedTasks = [...tasks].sort((a, b) => new Date(b.updated_at).getTime() - new Date(a.updated_at).getTime());
+  const taskTitle = (id: string) => tasks.find((t) => t.id === id)?.title ?? id;
 
   return (
     <div className="p-6 max-w-4xl mx-auto space-y-6 min-h-full">
@@ -246,6 +255,47 @@ export function ProjectOverview() {
         </CardContent>
       </Card>
 
+      {/* Task Branch Overlaps */}
+      {(overlapError || (overlapReport && overlapReport.overlaps.length > 0)) && (
+        <Card>
+          <CardHeader className="pb-2">
+            <CardTitle className="text-base">Overlapping Changes</CardTitle>
+          </CardHeader>
+          <CardContent className="space-y-2">
+            {overlapError && (
+              <p className="text-sm text-destructive">{overlapError}</p>
+            )}
+            {overlapReport?.overlaps.map((overlap) => (
+              <div key={`${overlap.taskA}:${overlap.taskB}`} className="px-3 py-2 rounded-md border border-border">
+                <p className="text-sm truncate">
+                  {taskTitle(overlap.taskA)} <span className="text-muted-foreground">and</span> {taskTitle(overlap.taskB)}
+                </p>
+                {overlap.files.map((file) => (
+                  <div key={file.path} className="flex items-center gap-2 text-xs">
+                    <span className="font-mono truncate text-muted-foreground">{file.path}</span>
+                    {file.hunks.length > 0 && (
+                      <Badge variant="secondary" className="text-xs shrink-0 ml-auto">
+                        {file.hunks.length} {file.hunks.length === 1 ? "hunk" : "hunks"}
+                      </Badge>
+                    )}
+                  </div>
+                ))}
+                {!overlap.sameMergeBase && (
+                  <p className="text-xs text-muted-foreground mt-1">
+                    Branched from different commits of {defaultBranch}; only files are compared.
+                  </p>
+                )}
+              </div>
+            ))}
+            {overlapReport && overlapReport.overlaps.length > 0 && overlapReport.mergeOrder.length > 1 && (
+              <p className="text-xs text-muted-foreground">
+                Suggested merge order: {overlapReport.mergeOrder.map(taskTitle).join(" → ")}
+              </p>
+            )}
+          </CardContent>
+        </Card>
+      )}
+
       {/* Paused Tasks (Collapsible) */}
       {pausedTasks.length > 0 && (
         <Collapsible open={pausedOpen} onOpenChange={setPausedOpen}>
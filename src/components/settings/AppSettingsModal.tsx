@@ -179,12 +179,15 @@ function EditorSettings() {
   const editorSidebarPosition = useSettingsStore((s) => s.editorSidebarPosition);
   const editorFontSize = useSettingsStore((s) => s.editorFontSize);
   const terminalFontSize = useSettingsStore((s) => s.terminalFontSize);
+  const persistentTerminals = useSettingsStore((s) => s.persistentTerminals);
+  const persistentTerminalsAvailable = useSettingsStore((s) => s.persistentTerminalsAvailable);
   const diffViewMode = useSettingsStore((s) => s.diffViewMode);
   const externalEditor = useSettingsStore((s) => s.externalEditor);
   const externalEditorCommand = useSettingsStore((s) => s.externalEditorCommand);
   const setEditorSidebarPosition = useSettingsStore((s) => s.setEditorSidebarPosition);
   const setEditorFontSize = useSettingsStore((s) => s.setEditorFontSize);
   const setTerminalFontSize = useSettingsStore((s) => s.setTerminalFontSize);
+  const setPersistentTerminals = useSettingsStore((s) => s.setPersistentTerminals);
   const setDiffViewMode = useSettingsStore((s) => s.setDiffViewMode);
   const setExternalEditor = useSettingsStore((s) => s.setExternalEditor);
   const setExternalEditorCommand = useSettingsStore((s) => s.setExternalEditorCommand);
@@ -229,6 +232,28 @@ function EditorSettings() {
         )}
       </div>
 
+      <div>
+        <h3 className="text-sm font-medium text-foreground mb-1">Terminal Sessions</h3>
+        <p className="text-xs text-muted-foreground mb-4">
+          What happens to integrated terminal sessions when Stagehand quits.
+        </p>
+        {persistentTerminalsAvailable ? (
+          <RadioGroup
+            name="persistentTerminals"
+            value={persistentTerminals ? "persistent" : "app"}
+            options={[
+              { value: "app", label: "Close with Stagehand", description: "Sessions end when the app quits" },
+              { value: "persistent", label: "Keep running", description: "New sessions run in a background host and can be reattached after a restart or update" },
+            ]}
+            onChange={(v) => setPersistentTerminals(v === "persistent")}
+          />
+        ) : (
+          <p className="text-xs text-muted-foreground px-3 py-2.5 rounded-lg bg-accent/50">
+            Sessions end when the app quits. Keeping them running needs the stagehand-ptyd helper, which this build doesn't include.
+          </p>
+        )}
+      </div>
+
       {externalEditor === "builtin" && (
         <>
           <div>
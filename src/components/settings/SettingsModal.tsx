@@ -11,11 +11,13 @@ import { LinearSettingsContent } from "../linear/LinearSettings";
 import { GitHubSettingsContent } from "../github/GitHubSettings";
 import { GitHubConventionsContent } from "../github/GitHubConventions";
 import { AgentSettingsContent } from "../agents/AgentSettings";
+import { EnvironmentSettingsContent } from "../agents/EnvironmentSettings";
+import { HookSettingsContent } from "../agents/HookSettings";
 import { Dialog, DialogContent, DialogHeader, DialogTitle } from "@/components/ui/dialog";
 import { Button } from "@/components/ui/button";
 import { ScrollArea } from "@/components/ui/scroll-area";
 
-export type ProjectSettingsSection = "project" | "pipeline" | "linear" | "github" | "conventions" | "agents";
+export type ProjectSettingsSection = "project" | "pipeline" | "linear" | "github" | "conventions" | "agents" | "environment" | "hooks";
 type Section = ProjectSettingsSection;
 
 type NavItem =
@@ -40,6 +42,8 @@ export function SettingsModal({ onClose, initialSection }: SettingsModalProps) {
     { section: "github", label: "Git", projectRequired: true },
     { header: "WORKFLOW" },
     { section: "agents", label: "AI Agents", projectRequired: true },
+    { section: "environment", label: "Environment", projectRequired: true },
+    { section: "hooks", label: "Hooks", projectRequired: true },
     { section: "conventions", label: "Conventions", projectRequired: true },
   ];
 
@@ -150,6 +154,10 @@ function SectionContent({
       return <GitHubConventionsContent projectId={projectId!} />;
     case "agents":
       return <AgentSettingsContent projectId={projectId!} />;
+    case "environment":
+      return <EnvironmentSettingsContent projectId={projectId!} />;
+    case "hooks":
+      return <HookSettingsContent projectId={projectId!} />;
     default:
       return null;
   }
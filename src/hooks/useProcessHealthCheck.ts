@@ -48,10 +48,17 @@ export function useProcessHealthCheck(stageId: string | null, taskId?: string) {
       const processId = stageState?.processId;
 
       // Check 1: Is the process still tracked by the backend?
+      let resumedAt = 0;
       try {
         const detailedProcesses = await listProcessesDetailed();
         const runningIds = detailedProcesses.map((p) => p.processId);
 
+        // Paused processes are silent on purpose; the inactivity clock
+        // restarts when they resume.
+        const current = detailedProcesses.find((p) => p.processId === processId);
+        if (current?.paused) return;
+        resumedAt = current?.resumedAtMs ?? 0;
+
         if (processId && !runningIds.includes(processId)) {
           // Process is gone — mark as crashed
           await markStageCrashed(projectId, stageId, effectTaskId, "Process crashed unexpectedly");
@@ -91,7 +98,7 @@ export function useProcessHealthCheck(stageId: string | null, taskId?: string) {
 
       // Check 2: Inactivity timeout
       const lastOutput = stageState?.lastOutputAt;
-      if (lastOutput && Date.now() - lastOutput > PROCESS_INACTIVITY_TIMEOUT_MS) {
+      if (lastOutput && Date.now() - Math.max(lastOutput, resumedAt) > PROCESS_INACTIVITY_TIMEOUT_MS) {
         await markStageCrashed(
           projectId,
           stageId,
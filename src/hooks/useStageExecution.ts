@@ -4,18 +4,18 @@ import { useTaskStore } from "../stores/taskStore";
 import { useProcessStore, stageKey } from "../stores/processStore";
 import { useGitHubStore } from "../stores/githubStore";
 import { invoke } from "@tauri-apps/api/core";
-import { spawnAgent, killProcess, listProcessesDetailed } from "../lib/agent";
+import { spawnAgent, killProcess, listProcessesDetailed, takeoverProcess } from "../lib/agent";
+import { routePtyOutput, decodePtyBytes } from "../lib/ptyRouter";
 import { renderPrompt } from "../lib/prompt";
 import {
   hasUncommittedChanges,
   gitDiffStat,
   isGitRepo,
-  gitBranchExists,
   gitPush,
   gitDefaultBranch,
   ghCreatePr,
-  gitWorktreeAdd,
-  gitWorktreeRemove,
+  createWorktree,
+  removeWorktree,
 } from "../lib/git";
 import { getTaskWorkingDir, cleanupTaskWorktree } from "../lib/worktree";
 import { gitDiffShortStatBranch } from "../lib/git";
@@ -287,6 +287,8 @@ export function useStageExecution() {
           duration_ms?: number;
           num_turns?: number;
         } | null = null;
+        // PTY session continuing the run, once it has been taken over
+        let handedOverTo: string | null = null;
 
         // Capture task.id so completion handler uses the correct task even if activeTask changes
         const taskId = task.id;
@@ -387,8 +389,41 @@ export function useStageExecution() {
             case "stderr_line":
               appendOutput(sk, `[stderr] ${event.line}`);
               break;
+            case "hook_output":
+              appendOutput(sk, `[hook] ${event.line}`);
+              break;
+            case "hook_finished":
+              appendOutput(
+                sk,
+                `[Hook \`${event.command}\` exited with code: ${event.exit_code ?? "none"}]`,
+              );
+              break;
+            case "handed_over":
+              handedOverTo = event.pty_id;
+              appendOutput(sk, `[Taken over in interactive terminal: ${event.pty_id}]`);
+              break;
             case "completed":
               setStopped(sk);
+              if (handedOverTo) {
+                // The run was stopped for the takeover, so its exit code says
+                // nothing about the stage: keep what it did for review.
+                appendOutput(sk, `[Headless run stopped; continuing in terminal ${handedOverTo}]`);
+                repo.updateStageExecution(activeProject!.id, executionId!, {
+                  status: "awaiting_user",
+                  raw_output: rawOutput,
+                  parsed_output: resultText,
+                  thinking_output: thinkingText.trim() || null,
+                  completed_at: new Date().toISOString(),
+                  ...(usageData ?? {}),
+                }).then(() => {
+                  if (useTaskStore.getState().activeTask?.id === taskId) {
+                    loadExecutions(activeProject!.id, taskId);
+                  } else {
+                    useTaskStore.getState().refreshTaskExecStatuses(activeProject!.id);
+                  }
+                });
+                break;
+              }
               appendOutput(
                 sk,
                 `[Process completed with exit code: ${event.exit_code}]`,
@@ -452,6 +487,7 @@ export function useStageExecution() {
         // Resolve effective agent + model
         const effectiveAgent = await repo.getEffectiveAgent(activeProject.id, null, stage.agent);
         const effectiveModel = await repo.getEffectiveModel(activeProject.id, stage.model_override, stage.persona_model);
+        const envProfile = await repo.getEnvProfile(activeProject.id);
 
         // Build MCP config for stage context server
         let mcpConfig: string | undefined;
@@ -509,6 +545,9 @@ export function useStageExecution() {
             outputFormat: "stream-json",
             allowedTools: allowedTools,
             mcpConfig,
+            projectId: activeProject.id,
+            envProfile,
+            stageId: stage.stage_template_id,
             jsonSchema:
               stage.output_schema &&
               !(stage.output_format === "findings" && !!priorAttemptOutput)
@@ -1052,6 +1091,50 @@ Return strict JSON:
     }, 3000);
   }, [failStaleExecutions]);
 
+  /**
+   * Stop the stage's headless run and continue its session in a new tab of
+   * the task's integrated terminal, which is brought to the front.
+   */
+  const takeOverCurrent = useCallback(async (taskId: string, stageId: string) => {
+    const sk = stageKey(taskId, stageId);
+    const processId = useProcessStore.getState().stages[sk]?.processId;
+    if (!processId || processId === "spawning" || processId === "fixing") return;
+
+    const store = useProcessStore.getState();
+    const tabId = store.addTerminalTab(taskId, "claude");
+    try {
+      await takeoverProcess(
+        processId,
+        (event) => {
+          const state = useProcessStore.getState();
+          switch (event.type) {
+            case "started":
+              state.updateTerminalTab(tabId, { ptyId: event.id });
+              break;
+            case "output":
+              routePtyOutput(tabId, event.data);
+              break;
+            case "output_bytes":
+              routePtyOutput(tabId, decodePtyBytes(event.data));
+              break;
+            case "exited":
+              state.updateTerminalTab(tabId, { ptyId: null, status: "exited" });
+              break;
+            case "error":
+              state.appendOutput(sk, `[Terminal error] ${event.message}`);
+              break;
+          }
+        },
+        { taskId },
+      );
+      store.setActiveView("terminal");
+    } catch (err) {
+      useProcessStore.getState().removeTerminalTab(taskId, tabId);
+      useProcessStore.getState().appendOutput(sk, `[Takeover failed] ${err instanceof Error ? err.message : String(err)}`);
+      logger.error("Failed to take over process", err);
+    }
+  }, []);
+
   // Cleanup kill timeout on unmount
   useEffect(() => {
     return () => {
@@ -1061,7 +1144,7 @@ Return strict JSON:
     };
   }, []);
 
-  return { runStage, approveStage, suggestNextStage, chooseNextStage, redoStage, killCurrent };
+  return { runStage, approveStage, suggestNextStage, chooseNextStage, redoStage, killCurrent, takeOverCurrent };
 }
 
 /** Generate a fallback branch name slug from a task title. */
@@ -1092,17 +1175,21 @@ export async function createWorktreeForTask(
 
   const baseDir = await repo.getWorktreeBaseDir(projectId);
   const worktreePath = `${baseDir}/${projectId}/${branchName.replace(/\//g, "--")}--${task.id}`;
-  const exists = await gitBranchExists(projectPath, branchName);
 
-  // Clean up stale worktree if directory already exists
+  // Clean up a stale worktree if the directory already exists. One with
+  // uncommitted changes is kept, and creating the new one below fails.
   try {
-    await gitWorktreeRemove(projectPath, worktreePath);
+    await removeWorktree(projectPath, worktreePath);
   } catch {
     // Worktree may not exist — that's fine
   }
 
-  const startPoint = exists ? undefined : baseBranch;
-  await gitWorktreeAdd(projectPath, worktreePath, branchName, !exists, startPoint);
+  // Creates the branch from baseBranch if it doesn't exist yet
+  await createWorktree(projectPath, task.id, {
+    branch: branchName,
+    base: baseBranch,
+    baseDir: `${baseDir}/${projectId}`,
+  });
 
   // Update task in DB and store
   await useTaskStore.getState().updateTask(projectId, task.id, {
@@ -0,0 +1,97 @@
+import { useEffect, useRef, useState } from "react";
+import { watchTaskOverlaps, updateOverlapWatch, stopOverlapWatch } from "../lib/git";
+import type { OverlapEvent, OverlapReport, TaskBranch } from "../lib/git";
+import { sendNotification } from "../lib/notifications";
+import { useTaskStore } from "../stores/taskStore";
+import { logger } from "../lib/logger";
+import type { Task } from "../lib/types";
+
+/**
+ * Watch a project's task branches for changes to the same files, notifying
+ * when a new overlap shows up. The watch stops on unmount.
+ */
+export function useTaskOverlaps(
+  projectId: string | undefined,
+  projectPath: string | undefined,
+  base: string | null,
+  tasks: Task[],
+) {
+  const [report, setReport] = useState<OverlapReport | null>(null);
+  const [error, setError] = useState<string | null>(null);
+  const watchIdRef = useRef<string | null>(null);
+
+  const branches: TaskBranch[] = tasks
+    .filter((t) => t.branch_name)
+    .map((t) => ({ taskId: t.id, branch: t.branch_name! }));
+  const branchesKey = branches.map((b) => `${b.taskId}:${b.branch}`).join(",");
+  const branchesRef = useRef(branches);
+  branchesRef.current = branches;
+  const branchesKeyRef = useRef(branchesKey);
+  branchesKeyRef.current = branchesKey;
+
+  useEffect(() => {
+    setReport(null);
+    setError(null);
+    if (!projectId || !projectPath || !base) return;
+    let stopped = false;
+
+    const onEvent = (event: OverlapEvent) => {
+      if (stopped) return;
+      switch (event.type) {
+        case "report":
+          setReport(event.report);
+          setError(null);
+          break;
+        case "error":
+          setError(event.message);
+          break;
+        case "new_overlap": {
+          const known = useTaskStore.getState().tasks;
+          const title = (id: string) => known.find((t) => t.id === id)?.title ?? id;
+          sendNotification(
+            event.conflicting ? "Tasks edit the same lines" : "Tasks edit the same file",
+            `${title(event.task_a)} and ${title(event.task_b)} both change ${event.path}`,
+            "info",
+            { projectId, taskId: event.task_a },
+          );
+          break;
+        }
+      }
+    };
+
+    const startedKey = branchesKeyRef.current;
+    watchTaskOverlaps(projectPath, base, branchesRef.current, onEvent)
+      .then((watchId) => {
+        if (stopped) {
+          stopOverlapWatch(watchId).catch(() => {});
+          return;
+        }
+        watchIdRef.current = watchId;
+        // Tasks may have changed while the watch was starting.
+        if (branchesKeyRef.current !== startedKey) {
+          updateOverlapWatch(watchId, branchesRef.current).catch(() => {});
+        }
+      })
+      .catch((err) => {
+        logger.error("Failed to watch task overlaps", err);
+        if (!stopped) setError(String(err));
+      });
+
+    return () => {
+      stopped = true;
+      const watchId = watchIdRef.current;
+      watchIdRef.current = null;
+      if (watchId) stopOverlapWatch(watchId).catch(() => {});
+    };
+  }, [projectId, projectPath, base]);
+
+  useEffect(() => {
+    const watchId = watchIdRef.current;
+    if (!watchId) return;
+    updateOverlapWatch(watchId, branchesRef.current).catch((err) => {
+      logger.warn("Failed to update overlap watch", err);
+    });
+  }, [branchesKey]);
+
+  return { report, error };
+}
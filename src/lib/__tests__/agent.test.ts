@@ -0,0 +1,53 @@
+import { writeToPty } from "../agent";
+import { mockInvoke } from "../../test/mocks/tauri";
+
+// ─── writeToPty ──────────────────────────────────────────────────────────────
+
+describe("writeToPty", () => {
+  it("sends one write per session at a time, batching input typed meanwhile", async () => {
+    const sent: string[] = [];
+    const release: (() => void)[] = [];
+    mockInvoke("write_to_pty", (args) => {
+      sent.push((args as { data: string }).data);
+      return new Promise<void>((resolve) => release.push(resolve));
+    });
+
+    const first = writeToPty("pty-1", "a");
+    await vi.waitFor(() => expect(sent).toEqual(["a"]));
+    const second = writeToPty("pty-1", "b");
+    const third = writeToPty("pty-1", "c");
+    expect(second).toBe(third);
+
+    release.shift()!();
+    await first;
+    await vi.waitFor(() => expect(sent).toEqual(["a", "bc"]));
+    release.shift()!();
+    await second;
+  });
+
+  it("keeps sending after a failed write", async () => {
+    const sent: string[] = [];
+    mockInvoke("write_to_pty", async (args) => {
+      const { data } = args as { data: string };
+      sent.push(data);
+      if (data === "x") throw new Error("session gone");
+    });
+
+    await expect(writeToPty("pty-2", "x")).rejects.toThrow("session gone");
+    await writeToPty("pty-2", "y");
+    expect(sent).toEqual(["x", "y"]);
+  });
+
+  it("doesn't hold up other sessions", async () => {
+    const sent: string[] = [];
+    mockInvoke("write_to_pty", (args) => {
+      const { id, data } = args as { id: string; data: string };
+      sent.push(`${id}:${data}`);
+      return id === "slow" ? new Promise<void>(() => {}) : undefined;
+    });
+
+    writeToPty("slow", "1");
+    await writeToPty("fast", "2");
+    expect(sent).toEqual(["slow:1", "fast:2"]);
+  });
+});
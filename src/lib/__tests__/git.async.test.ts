@@ -7,6 +7,8 @@ import {
   gitAdd,
   gitAddFiles,
   getChangedFiles,
+  taskDiff,
+  taskOverlaps,
   gitCommit,
   gitCreateBranch,
   gitCheckoutBranch,
@@ -112,19 +114,65 @@ describe("gitAddFiles", () => {
 // ─── getChangedFiles ─────────────────────────────────────────────────────────
 
 describe("getChangedFiles", () => {
-  it("parses status output into file paths", async () => {
-    mockInvoke("run_git_command", () => "MM src/main.ts\n?? new-file.ts\n");
+  it("returns the paths from git_status", async () => {
+    mockInvoke("git_status", () => ({
+      branch: "main",
+      head: "abc1234",
+      upstream: null,
+      ahead: 0,
+      behind: 0,
+      files: [
+        { path: "src/main.ts", oldPath: null, staged: "modified", unstaged: "modified" },
+        { path: "new-file.ts", oldPath: null, staged: null, unstaged: "untracked" },
+      ],
+    }));
     const result = await getChangedFiles("/repo");
     expect(result).toEqual(["src/main.ts", "new-file.ts"]);
   });
 
   it("returns empty array for clean repo", async () => {
-    mockInvoke("run_git_command", () => "");
+    mockInvoke("git_status", () => ({ branch: "main", head: null, upstream: null, ahead: 0, behind: 0, files: [] }));
     const result = await getChangedFiles("/repo");
     expect(result).toEqual([]);
   });
 });
 
+// ─── taskDiff ────────────────────────────────────────────────────────────────
+
+describe("taskDiff", () => {
+  it("passes base, head and options to task_diff", async () => {
+    let received: Record<string, unknown> | undefined;
+    mockInvoke("task_diff", (args) => {
+      received = args as Record<string, unknown>;
+      return { mergeBase: "abc", head: null, files: [], additions: 0, deletions: 0 };
+    });
+    const result = await taskDiff("/repo", "main", undefined, { wordDiff: true, maxFileBytes: 1024 });
+    expect(received).toEqual({
+      workingDirectory: "/repo",
+      base: "main",
+      head: undefined,
+      options: { wordDiff: true, maxFileBytes: 1024 },
+    });
+    expect(result.mergeBase).toBe("abc");
+  });
+});
+
+// ─── taskOverlaps ────────────────────────────────────────────────────────────
+
+describe("taskOverlaps", () => {
+  it("passes the task branches to task_overlaps", async () => {
+    let received: Record<string, unknown> | undefined;
+    mockInvoke("task_overlaps", (args) => {
+      received = args as Record<string, unknown>;
+      return { overlaps: [], mergeOrder: ["t1", "t2"], skipped: [] };
+    });
+    const tasks = [{ taskId: "t1", branch: "feature/a" }, { taskId: "t2", branch: "feature/b" }];
+    const result = await taskOverlaps("/repo", "main", tasks);
+    expect(received).toEqual({ workingDirectory: "/repo", base: "main", tasks });
+    expect(result.mergeOrder).toEqual(["t1", "t2"]);
+  });
+});
+
 // ─── gitCommit ───────────────────────────────────────────────────────────────
 
 describe("gitCommit", () => {
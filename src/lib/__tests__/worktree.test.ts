@@ -0,0 +1,57 @@
+import { cleanupTaskWorktree } from "../worktree";
+import { mockInvoke } from "../../test/mocks/tauri";
+
+describe("cleanupTaskWorktree", () => {
+  it("removes the worktree and deletes the branch", async () => {
+    const removed: unknown[] = [];
+    const gitArgs: string[][] = [];
+    mockInvoke("remove_worktree", (args) => { removed.push(args); });
+    mockInvoke("run_git_command", (args) => {
+      gitArgs.push((args as { args: string[] }).args);
+      return "";
+    });
+
+    await cleanupTaskWorktree(
+      "/repo",
+      { worktree_path: "/repo/.stagehand-worktrees/feat--1", branch_name: "feat", ejected: 0 },
+      { deleteBranch: true },
+    );
+
+    expect(removed).toEqual([
+      { workingDirectory: "/repo", path: "/repo/.stagehand-worktrees/feat--1" },
+    ]);
+    expect(gitArgs.some((a) => a.includes("branch") && a.includes("feat"))).toBe(true);
+  });
+
+  it("prunes stale entries when the worktree can't be removed", async () => {
+    let pruned = false;
+    mockInvoke("remove_worktree", () => { throw new Error("No worktree at /gone"); });
+    mockInvoke("prune_worktrees", () => { pruned = true; return ["/gone"]; });
+
+    await cleanupTaskWorktree("/repo", { worktree_path: "/gone", branch_name: null, ejected: 0 });
+
+    expect(pruned).toBe(true);
+  });
+
+  it("keeps a worktree with uncommitted changes and its branch", async () => {
+    let pruned = false;
+    const gitArgs: string[][] = [];
+    mockInvoke("remove_worktree", () => {
+      throw new Error("Worktree /repo/wt has uncommitted changes");
+    });
+    mockInvoke("prune_worktrees", () => { pruned = true; return []; });
+    mockInvoke("run_git_command", (args) => {
+      gitArgs.push((args as { args: string[] }).args);
+      return "";
+    });
+
+    await cleanupTaskWorktree(
+      "/repo",
+      { worktree_path: "/repo/wt", branch_name: "feat", ejected: 0 },
+      { deleteBranch: true },
+    );
+
+    expect(pruned).toBe(false);
+    expect(gitArgs).toEqual([]);
+  });
+});
@@ -1,5 +1,5 @@
 import { invoke, Channel } from "@tauri-apps/api/core";
-import type { AgentStreamEvent, SpawnAgentArgs, PtyEvent, SpawnPtyArgs } from "./types";
+import type { AgentStreamEvent, SpawnAgentArgs, PtyEvent, PtyInfo, SpawnPtyArgs, RecordingInfo, TerminalProfile, PtySnapshot, AttentionMatcher, CommandHistoryEntry, PtySignal, PtyForeground, EnvProfile, ProjectHooks } from "./types";
 
 export async function spawnAgent(
   args: SpawnAgentArgs,
@@ -18,6 +18,22 @@ export async function killProcess(processId: string): Promise<void> {
   return invoke("kill_process", { processId });
 }
 
+export async function sendAgentMessage(processId: string, text: string): Promise<void> {
+  return invoke("send_agent_message", { processId, text });
+}
+
+export async function closeAgentInput(processId: string): Promise<void> {
+  return invoke("close_agent_input", { processId });
+}
+
+export async function pauseProcess(processId: string): Promise<void> {
+  return invoke("pause_process", { processId });
+}
+
+export async function resumeProcess(processId: string): Promise<void> {
+  return invoke("resume_process", { processId });
+}
+
 export async function listProcesses(): Promise<string[]> {
   return invoke<string[]>("list_processes");
 }
@@ -25,12 +41,35 @@ export async function listProcesses(): Promise<string[]> {
 export interface ProcessInfo {
   processId: string;
   stageExecutionId: string | null;
+  paused: boolean;
+  resumedAtMs: number | null;
+  /** PTY session continuing this run, while it is being taken over. */
+  handedOverTo: string | null;
 }
 
 export async function listProcessesDetailed(): Promise<ProcessInfo[]> {
   return invoke<ProcessInfo[]>("list_processes_detailed");
 }
 
+/**
+ * Stop a headless Claude run and continue its session in an interactive
+ * terminal. Resolves to the new PTY id.
+ */
+export async function takeoverProcess(
+  processId: string,
+  onEvent: (event: PtyEvent) => void,
+  args?: { taskId?: string; cols?: number; rows?: number },
+): Promise<string> {
+  const channel = new Channel<PtyEvent>();
+  channel.onmessage = onEvent;
+
+  return invoke<string>("takeover_process", {
+    processId,
+    args: args ?? null,
+    onEvent: channel,
+  });
+}
+
 export async function checkAgentAvailable(agent?: string): Promise<string> {
   return invoke<string>("check_agent_available", { agent: agent ?? null });
 }
@@ -50,14 +89,173 @@ export async function spawnPty(
   });
 }
 
-export async function writeToPty(id: string, data: string): Promise<void> {
-  return invoke("write_to_pty", { id, data });
+/** Per session: input typed while the previous write is still in flight,
+ *  and the promise of the last write sent. */
+const pendingInput = new Map<string, { data: string; sent: Promise<void> }>();
+const lastWrite = new Map<string, Promise<void>>();
+
+/** Send keyboard input to a session. Writes to one session go out one at a
+ *  time, in the order they were made; input typed in the meantime is sent
+ *  together in the next write. */
+export function writeToPty(id: string, data: string): Promise<void> {
+  const pending = pendingInput.get(id);
+  if (pending) {
+    pending.data += data;
+    return pending.sent;
+  }
+  const previous = lastWrite.get(id) ?? Promise.resolve();
+  const entry = { data, sent: Promise.resolve() };
+  entry.sent = previous
+    .catch(() => {})
+    .then(() => {
+      pendingInput.delete(id);
+      return invoke<void>("write_to_pty", { id, data: entry.data });
+    });
+  pendingInput.set(id, entry);
+  lastWrite.set(id, entry.sent);
+  entry.sent
+    .catch(() => {})
+    .then(() => {
+      if (lastWrite.get(id) === entry.sent) lastWrite.delete(id);
+    });
+  return entry.sent;
 }
 
 export async function resizePty(id: string, cols: number, rows: number): Promise<void> {
   return invoke("resize_pty", { id, cols, rows });
 }
 
+export async function pausePty(id: string): Promise<void> {
+  return invoke("pause_pty", { id });
+}
+
+export async function resumePty(id: string): Promise<void> {
+  return invoke("resume_pty", { id });
+}
+
 export async function killPty(id: string): Promise<void> {
   return invoke("kill_pty", { id });
 }
+
+/** Whether this build includes the stagehand-ptyd host that `persistent` sessions run in. */
+export async function persistentTerminalsAvailable(): Promise<boolean> {
+  return invoke<boolean>("persistent_terminals_available");
+}
+
+export async function listPtys(): Promise<PtyInfo[]> {
+  return invoke<PtyInfo[]>("list_ptys");
+}
+
+/** Subscribe to a running PTY. Its scrollback is replayed as one `output`
+ *  event before live events. Resolves to a subscriber id for `detachPty`. */
+export async function attachPty(
+  id: string,
+  onEvent: (event: PtyEvent) => void,
+): Promise<number> {
+  const channel = new Channel<PtyEvent>();
+  channel.onmessage = onEvent;
+
+  return invoke<number>("attach_pty", {
+    id,
+    onEvent: channel,
+  });
+}
+
+export async function detachPty(id: string, subscriberId: number): Promise<void> {
+  return invoke("detach_pty", { id, subscriberId });
+}
+
+/** Interrupt (SIGINT), suspend (SIGTSTP), hang up or terminate the command in the foreground. */
+export async function signalPty(id: string, signal: PtySignal): Promise<void> {
+  return invoke("signal_pty", { id, signal });
+}
+
+export async function ptyForeground(id: string): Promise<PtyForeground> {
+  return invoke<PtyForeground>("pty_foreground", { id });
+}
+
+export async function ptySnapshot(id: string): Promise<PtySnapshot> {
+  return invoke<PtySnapshot>("pty_snapshot", { id });
+}
+
+/** Commands run in the task's terminals, oldest first, keeping the last `limit`. */
+export async function listCommandHistory(taskId: string, limit?: number): Promise<CommandHistoryEntry[]> {
+  return invoke<CommandHistoryEntry[]>("list_command_history", { taskId, limit });
+}
+
+// === Attention Matchers ===
+
+export async function listAttentionMatchers(): Promise<AttentionMatcher[]> {
+  return invoke<AttentionMatcher[]>("list_attention_matchers");
+}
+
+/** Replace all matchers; running sessions pick them up immediately. */
+export async function saveAttentionMatchers(matchers: AttentionMatcher[]): Promise<void> {
+  return invoke("save_attention_matchers", { matchers });
+}
+
+// === Terminal Profiles ===
+
+export async function listTerminalProfiles(): Promise<TerminalProfile[]> {
+  return invoke<TerminalProfile[]>("list_terminal_profiles");
+}
+
+export async function saveTerminalProfile(profile: TerminalProfile): Promise<void> {
+  return invoke("save_terminal_profile", { profile });
+}
+
+export async function deleteTerminalProfile(name: string): Promise<void> {
+  return invoke("delete_terminal_profile", { name });
+}
+
+// === Session Recordings ===
+
+export async function listRecordings(): Promise<RecordingInfo[]> {
+  return invoke<RecordingInfo[]>("list_recordings");
+}
+
+/** Play a recorded session back as PTY events. `speed` multiplies real time;
+ *  pauses are capped at `idleLimitMs`. Resolves to an id for `stopPlayback`. */
+export async function playRecording(
+  id: string,
+  onEvent: (event: PtyEvent) => void,
+  options: { speed?: number; idleLimitMs?: number } = {},
+): Promise<string> {
+  const channel = new Channel<PtyEvent>();
+  channel.onmessage = onEvent;
+
+  return invoke<string>("play_recording", {
+    id,
+    speed: options.speed,
+    idleLimitMs: options.idleLimitMs,
+    onEvent: channel,
+  });
+}
+
+export async function stopPlayback(playbackId: string): Promise<void> {
+  return invoke("stop_playback", { playbackId });
+}
+
+// === Environment Profiles ===
+
+export async function listEnvProfiles(projectId: string): Promise<EnvProfile[]> {
+  return invoke<EnvProfile[]>("list_env_profiles", { projectId });
+}
+
+export async function saveEnvProfile(projectId: string, profile: EnvProfile): Promise<void> {
+  return invoke("save_env_profile", { projectId, profile });
+}
+
+export async function deleteEnvProfile(projectId: string, name: string): Promise<void> {
+  return invoke("delete_env_profile", { projectId, name });
+}
+
+// === Lifecycle Hooks ===
+
+export async function getProjectHooks(projectId: string): Promise<ProjectHooks> {
+  return invoke<ProjectHooks>("get_project_hooks", { projectId });
+}
+
+export async function saveProjectHooks(projectId: string, hooks: ProjectHooks): Promise<void> {
+  return invoke("save_project_hooks", { projectId, hooks });
+}
@@ -1,4 +1,4 @@
-import { invoke } from "@tauri-apps/api/core";
+import { invoke, Channel } from "@tauri-apps/api/core";
 import { GIT_LOG_DEFAULT_MAX, GIT_COMMITS_DEFAULT_MAX } from "./constants";
 import { withRetry } from "./retry";
 
@@ -9,6 +9,266 @@ export async function runGit(workingDir: string, ...args: string[]): Promise<str
   });
 }
 
+// === Native git (libgit2) ===
+
+export type GitFileChange =
+  | "added"
+  | "modified"
+  | "deleted"
+  | "renamed"
+  | "copied"
+  | "type_change"
+  | "untracked"
+  | "conflicted";
+
+export interface GitStatusEntry {
+  path: string;
+  /** Previous path of a staged rename. */
+  oldPath: string | null;
+  /** Change between HEAD and the index. */
+  staged: GitFileChange | null;
+  /** Change between the index and the working tree. */
+  unstaged: GitFileChange | null;
+}
+
+export interface GitRepoStatus {
+  /** null on a detached HEAD. */
+  branch: string | null;
+  /** null before the first commit. */
+  head: string | null;
+  upstream: string | null;
+  ahead: number;
+  behind: number;
+  files: GitStatusEntry[];
+}
+
+export interface GitDiffLine {
+  kind: "context" | "addition" | "deletion";
+  content: string;
+  oldLineno: number | null;
+  newLineno: number | null;
+  /** Changed and unchanged runs, present with `wordDiff` on paired lines. */
+  words?: { text: string; changed: boolean }[];
+}
+
+export interface GitDiffHunk {
+  header: string;
+  oldStart: number;
+  oldLines: number;
+  newStart: number;
+  newLines: number;
+  lines: GitDiffLine[];
+}
+
+export interface GitDiffFile {
+  path: string;
+  oldPath: string | null;
+  status: GitFileChange;
+  binary: boolean;
+  additions: number;
+  deletions: number;
+  /**
+   * The file is over `maxFileBytes` and its content wasn't read: no hunks,
+   * zero line counts, and `binary` is false whatever it holds.
+   */
+  tooLarge: boolean;
+  /** Empty when `statOnly` was set or the file is too large. */
+  hunks: GitDiffHunk[];
+}
+
+export interface GitDiffOptions {
+  /** Revision to diff from; HEAD if unset. */
+  base?: string;
+  /** Revision to diff to; the working tree if unset. */
+  head?: string;
+  paths?: string[];
+  /** Diff from where base and head diverged, like `base...head`. */
+  mergeBase?: boolean;
+  includeUntracked?: boolean;
+  statOnly?: boolean;
+}
+
+export interface TaskDiffOptions {
+  paths?: string[];
+  /** Include untracked files when diffing the working tree. */
+  includeUntracked?: boolean;
+  ignoreWhitespace?: boolean;
+  contextLines?: number;
+  wordDiff?: boolean;
+  /** Files larger than this keep their stats but lose their hunks. */
+  maxFileBytes?: number;
+  statOnly?: boolean;
+}
+
+export interface TaskDiff {
+  mergeBase: string;
+  /** null when the working tree was compared. */
+  head: string | null;
+  files: GitDiffFile[];
+  additions: number;
+  deletions: number;
+}
+
+export type MergeConflictKind =
+  | "content"
+  | "add_add"
+  | "modify_delete"
+  | "rename_delete"
+  | "rename_rename"
+  | "mode";
+
+export interface MergeConflictHunk {
+  /** Line where the conflict starts in the merged file, counting the ours side of earlier conflicts. */
+  startLine: number;
+  ours: string[];
+  base: string[];
+  theirs: string[];
+}
+
+export interface MergeConflict {
+  path: string;
+  kind: MergeConflictKind;
+  /** The file's path on each side; null where it doesn't exist. */
+  basePath: string | null;
+  ourPath: string | null;
+  theirPath: string | null;
+  binary: boolean;
+  /** Only content and mode conflicts between text files have hunks. */
+  hunks: MergeConflictHunk[];
+}
+
+export interface MergeCheck {
+  ours: string;
+  theirs: string;
+  mergeBase: string | null;
+  /** `theirs` is already merged into `ours`. */
+  upToDate: boolean;
+  conflicts: MergeConflict[];
+}
+
+export interface TaskBranch {
+  taskId: string;
+  branch: string;
+}
+
+/** Lines of the merge base a hunk replaces, inclusive. */
+export interface LineRange {
+  start: number;
+  end: number;
+}
+
+export interface TaskOverlap {
+  taskA: string;
+  taskB: string;
+  /** Both tasks diverged from the base at the same commit; otherwise only files are compared, not hunks. */
+  sameMergeBase: boolean;
+  files: {
+    path: string;
+    /** Hunk pairs touching the same lines; empty when the tasks edit different parts of the file or have different merge bases. */
+    hunks: { a: LineRange; b: LineRange }[];
+  }[];
+}
+
+export interface OverlapReport {
+  overlaps: TaskOverlap[];
+  /** Task ids, merging first the ones that overlap least with the rest. */
+  mergeOrder: string[];
+  /** Tasks whose branch couldn't be compared. */
+  skipped: { taskId: string; error: string }[];
+}
+
+export type OverlapEvent =
+  | { type: "report"; report: OverlapReport }
+  | { type: "new_overlap"; task_a: string; task_b: string; path: string; conflicting: boolean }
+  | { type: "error"; message: string };
+
+export interface GitCommitInfo {
+  hash: string;
+  shortHash: string;
+  subject: string;
+  body: string;
+  authorName: string;
+  authorEmail: string;
+  /** Seconds since the epoch. */
+  authoredAt: number;
+  parents: string[];
+}
+
+export interface GitBranchInfo {
+  /** e.g. `main` or `origin/main`. */
+  name: string;
+  remote: boolean;
+  current: boolean;
+  target: string | null;
+  upstream: string | null;
+  ahead: number | null;
+  behind: number | null;
+}
+
+export async function gitRepoStatus(workingDir: string): Promise<GitRepoStatus> {
+  return invoke<GitRepoStatus>("git_status", { workingDirectory: workingDir });
+}
+
+export async function gitDiffFiles(workingDir: string, options: GitDiffOptions = {}): Promise<GitDiffFile[]> {
+  return invoke<GitDiffFile[]>("git_diff", { workingDirectory: workingDir, ...options });
+}
+
+/** A task branch's changes since it diverged from `base`; the working tree if `head` is unset. */
+export async function taskDiff(
+  workingDir: string,
+  base: string,
+  head?: string,
+  options: TaskDiffOptions = {},
+): Promise<TaskDiff> {
+  return invoke<TaskDiff>("task_diff", { workingDirectory: workingDir, base, head, options });
+}
+
+/** Dry-run merging `theirs` into `ours` in memory; the working tree and index are untouched. */
+export async function checkMergeConflicts(workingDir: string, ours: string, theirs: string): Promise<MergeCheck> {
+  return invoke<MergeCheck>("check_merge_conflicts", { workingDirectory: workingDir, ours, theirs });
+}
+
+/** Files and hunks that active task branches both change, compared against their merge base with `base`. */
+export async function taskOverlaps(workingDir: string, base: string, tasks: TaskBranch[]): Promise<OverlapReport> {
+  return invoke<OverlapReport>("task_overlaps", { workingDirectory: workingDir, base, tasks });
+}
+
+/**
+ * Keep comparing task branches as they move. The first `report` event is the
+ * baseline; `new_overlap` events follow for overlaps that appear after it.
+ * Returns a watch id for `updateOverlapWatch` and `stopOverlapWatch`.
+ */
+export async function watchTaskOverlaps(
+  workingDir: string,
+  base: string,
+  tasks: TaskBranch[],
+  onEvent: (event: OverlapEvent) => void,
+): Promise<string> {
+  const channel = new Channel<OverlapEvent>();
+  channel.onmessage = onEvent;
+  return invoke<string>("watch_task_overlaps", { workingDirectory: workingDir, base, tasks, onEvent: channel });
+}
+
+export async function updateOverlapWatch(watchId: string, tasks: TaskBranch[]): Promise<void> {
+  return invoke("update_overlap_watch", { watchId, tasks });
+}
+
+export async function stopOverlapWatch(watchId: string): Promise<void> {
+  return invoke("stop_overlap_watch", { watchId });
+}
+
+/** Commits in `range` (a revision, `a..b` or `a...b`), newest first; HEAD's history if unset. */
+export async function gitLogRange(workingDir: string, range?: string, maxCount?: number): Promise<GitCommitInfo[]> {
+  return invoke<GitCommitInfo[]>("git_log", { workingDirectory: workingDir, range, maxCount });
+}
+
+/** Local then remote branches, sorted by name. */
+export async function gitBranches(workingDir: string): Promise<GitBranchInfo[]> {
+  return invoke<GitBranchInfo[]>("git_branches", { workingDirectory: workingDir });
+}
+
+// === Git CLI ===
+
 export async function gitStatus(workingDir: string): Promise<string> {
   return runGit(workingDir, "status", "--porcelain");
 }
@@ -31,21 +291,8 @@ export async function gitAddFiles(workingDir: string, files: string[]): Promise<
 }
 
 export async function getChangedFiles(workingDir: string): Promise<string[]> {
-  const status = await gitStatus(workingDir);
-  return status
-    .trim()
-    .split("\n")
-    .filter((line) => line.length > 0)
-    .map((line) => {
-      // Porcelain format: "XY path" where XY is 2-char status + space + path.
-      // Rust run_command trims stdout, so a leading space on the first line may
-      // be lost (e.g. " M src/foo" becomes "M src/foo"). Detect and re-pad:
-      if (line.length >= 2 && line[1] === " " && line[0] !== "?" && !line.startsWith("  ")) {
-        line = " " + line;
-      }
-      return line.slice(3).trim();
-    })
-    .filter((p) => p.length > 0);
+  const status = await gitRepoStatus(workingDir);
+  return status.files.map((f) => f.path);
 }
 
 export async function gitCommit(workingDir: string, message: string): Promise<string> {
@@ -78,12 +325,10 @@ export async function gitListBranches(workingDir: string, { fetch = false } = {}
     if (fetch) {
       try { await runGit(workingDir, "fetch", "--prune", "origin"); } catch { /* ok */ }
     }
-    const local = await runGit(workingDir, "branch", "--format=%(refname:short)");
-    const remote = await runGit(workingDir, "branch", "-r", "--format=%(refname:short)");
     const seen = new Set<string>();
     const branches: string[] = [];
-    for (const raw of [...local.split("\n"), ...remote.split("\n")]) {
-      const name = raw.trim().replace(/^origin\//, "");
+    for (const branch of await gitBranches(workingDir)) {
+      const name = branch.name.replace(/^origin\//, "");
       if (name && name !== "HEAD" && !seen.has(name)) {
         seen.add(name);
         branches.push(name);
@@ -154,16 +399,19 @@ export interface GitCommit {
   author: string;
 }
 
+function toGitCommit(commit: GitCommitInfo): GitCommit {
+  return {
+    hash: commit.hash,
+    message: commit.subject,
+    date: new Date(commit.authoredAt * 1000).toISOString(),
+    author: commit.authorName,
+  };
+}
+
 export async function gitLog(workingDir: string, maxCount: number = GIT_LOG_DEFAULT_MAX): Promise<GitCommit[]> {
   try {
-    const delimiter = "---END-COMMIT---";
-    const raw = await runGit(
-      workingDir,
-      "log",
-      `--max-count=${maxCount}`,
-      `--format=%H%n%s%n%aI%n%an%n${delimiter}`,
-    );
-    return parseGitLogOutput(raw, delimiter);
+    const commits = await gitLogRange(workingDir, undefined, maxCount);
+    return commits.map(toGitCommit);
   } catch {
     return [];
   }
@@ -179,32 +427,14 @@ export async function gitLogBranchDiff(
   maxCount: number = GIT_COMMITS_DEFAULT_MAX,
 ): Promise<GitCommit[]> {
   try {
-    const delimiter = "---END-COMMIT---";
-    const raw = await runGit(
-      workingDir,
-      "log",
-      `${baseBranch}..HEAD`,
-      `--max-count=${maxCount}`,
-      `--format=%H%n%s%n%aI%n%an%n${delimiter}`,
-    );
-    return parseGitLogOutput(raw, delimiter);
+    const commits = await gitLogRange(workingDir, `${baseBranch}..HEAD`, maxCount);
+    return commits.map(toGitCommit);
   } catch {
     // Fallback to regular log if the base branch doesn't exist locally
     return gitLog(workingDir, maxCount);
   }
 }
 
-function parseGitLogOutput(raw: string, delimiter: string): GitCommit[] {
-  return raw
-    .trim()
-    .split(`${delimiter}\n`)
-    .filter((block) => block.trim())
-    .map((block) => {
-      const [hash, message, date, author] = block.trim().split("\n");
-      return { hash, message, date, author };
-    });
-}
-
 export async function gitDefaultBranch(workingDir: string): Promise<string | null> {
   try {
     const ref = await runGit(workingDir, "symbolic-ref", "refs/remotes/origin/HEAD");
@@ -284,6 +514,66 @@ export async function gitWorktreeRemove(
   return runGit(workingDir, "worktree", "remove", worktreePath, "--force");
 }
 
+export interface WorktreeInfo {
+  /** git's name for the worktree, under `.git/worktrees`. */
+  name: string;
+  path: string;
+  /** null on a detached HEAD. */
+  branch: string | null;
+  head: string | null;
+  taskId: string | null;
+  locked: boolean;
+  lockReason: string | null;
+  /** Uncommitted changes, untracked files included. */
+  dirty: boolean;
+  /** The directory is gone; pruning drops it unless locked. */
+  missing: boolean;
+}
+
+export interface CreateWorktreeOptions {
+  /** Defaults to `stagehand/<taskId>`; created from `base` if missing. */
+  branch?: string;
+  /** Start point for a new branch; HEAD if unset. */
+  base?: string;
+  /** Directory to create the worktree in; `.stagehand-worktrees` in the repo if unset. */
+  baseDir?: string;
+}
+
+/** Create a worktree for a task, undoing any partial work if a step fails. */
+export async function createWorktree(
+  workingDir: string,
+  taskId: string,
+  options: CreateWorktreeOptions = {},
+): Promise<WorktreeInfo> {
+  return invoke<WorktreeInfo>("create_worktree", { workingDirectory: workingDir, taskId, ...options });
+}
+
+export async function listWorktrees(workingDir: string): Promise<WorktreeInfo[]> {
+  return invoke<WorktreeInfo[]>("list_worktrees", { workingDirectory: workingDir });
+}
+
+export async function lockWorktree(workingDir: string, path: string, reason?: string): Promise<void> {
+  return invoke("lock_worktree", { workingDirectory: workingDir, path, reason });
+}
+
+export async function unlockWorktree(workingDir: string, path: string): Promise<void> {
+  return invoke("unlock_worktree", { workingDirectory: workingDir, path });
+}
+
+/** Drop worktrees whose directory is gone. Returns their paths. */
+export async function pruneWorktrees(workingDir: string): Promise<string[]> {
+  return invoke<string[]>("prune_worktrees", { workingDirectory: workingDir });
+}
+
+/** Remove a worktree. Locked or dirty worktrees are refused unless `force` is set. */
+export async function removeWorktree(
+  workingDir: string,
+  path: string,
+  options: { force?: boolean; deleteBranch?: boolean } = {},
+): Promise<void> {
+  return invoke("remove_worktree", { workingDirectory: workingDir, path, ...options });
+}
+
 export async function gitWorktreeList(
   workingDir: string,
 ): Promise<string> {
@@ -626,9 +916,8 @@ export async function ghFindPrForBranch(
 }
 
 export async function gitDiffNameOnly(workingDir: string, base: string, head?: string): Promise<string[]> {
-  const ref = head ? `${base}...${head}` : `${base}...HEAD`;
-  const result = await runGit(workingDir, "diff", "--name-only", ref);
-  return result.trim().split("\n").filter((l) => l.length > 0);
+  const files = await gitDiffFiles(workingDir, { base, head: head ?? "HEAD", mergeBase: true, statOnly: true });
+  return files.map((f) => f.path);
 }
 
 export interface DiffFileStat {
@@ -638,70 +927,31 @@ export interface DiffFileStat {
   deletions: number;
 }
 
+const STATUS_LETTERS: Partial<Record<GitFileChange, DiffFileStat["status"]>> = {
+  added: "A",
+  deleted: "D",
+  renamed: "R",
+  copied: "C",
+  conflicted: "U",
+};
+
+function toFileStats(files: GitDiffFile[]): DiffFileStat[] {
+  return files.map((f) => ({
+    path: f.path,
+    status: STATUS_LETTERS[f.status] ?? "M",
+    additions: f.additions,
+    deletions: f.deletions,
+  }));
+}
+
 /** Returns per-file status + line counts for a branch diff. */
 export async function gitDiffFileStats(workingDir: string, base: string): Promise<DiffFileStat[]> {
-  const [nameStatus, numstat] = await Promise.all([
-    runGit(workingDir, "diff", "--name-status", `${base}...HEAD`),
-    runGit(workingDir, "diff", "--numstat", `${base}...HEAD`),
-  ]);
-
-  // Parse --name-status: "M\tpath" or "R100\told\tnew"
-  const statusMap = new Map<string, DiffFileStat["status"]>();
-  for (const line of nameStatus.trim().split("\n").filter(Boolean)) {
-    const parts = line.split("\t");
-    const code = parts[0][0] as DiffFileStat["status"];
-    const filePath = parts.length >= 3 ? parts[2] : parts[1]; // renamed: use new path
-    statusMap.set(filePath, code);
-  }
-
-  // Parse --numstat: "additions\tdeletions\tpath"
-  const results: DiffFileStat[] = [];
-  for (const line of numstat.trim().split("\n").filter(Boolean)) {
-    const parts = line.split("\t");
-    const adds = parts[0] === "-" ? 0 : parseInt(parts[0], 10);
-    const dels = parts[1] === "-" ? 0 : parseInt(parts[1], 10);
-    const filePath = parts.slice(2).join("\t"); // handle paths with tabs
-    results.push({
-      path: filePath,
-      status: statusMap.get(filePath) ?? "M",
-      additions: adds,
-      deletions: dels,
-    });
-  }
-
-  return results;
+  return toFileStats(await gitDiffFiles(workingDir, { base, head: "HEAD", mergeBase: true, statOnly: true }));
 }
 
 /** Returns per-file line counts for uncommitted changes (used in commit view). */
 export async function gitDiffFileStatsUnstaged(workingDir: string): Promise<DiffFileStat[]> {
-  const [nameStatus, numstat] = await Promise.all([
-    runGit(workingDir, "diff", "HEAD", "--name-status"),
-    runGit(workingDir, "diff", "HEAD", "--numstat"),
-  ]);
-
-  const statusMap = new Map<string, DiffFileStat["status"]>();
-  for (const line of nameStatus.trim().split("\n").filter(Boolean)) {
-    const parts = line.split("\t");
-    const code = parts[0][0] as DiffFileStat["status"];
-    const filePath = parts.length >= 3 ? parts[2] : parts[1];
-    statusMap.set(filePath, code);
-  }
-
-  const results: DiffFileStat[] = [];
-  for (const line of numstat.trim().split("\n").filter(Boolean)) {
-    const parts = line.split("\t");
-    const adds = parts[0] === "-" ? 0 : parseInt(parts[0], 10);
-    const dels = parts[1] === "-" ? 0 : parseInt(parts[1], 10);
-    const filePath = parts.slice(2).join("\t");
-    results.push({
-      path: filePath,
-      status: statusMap.get(filePath) ?? "M",
-      additions: adds,
-      deletions: dels,
-    });
-  }
-
-  return results;
+  return toFileStats(await gitDiffFiles(workingDir, { statOnly: true }));
 }
 
 export async function gitDiffStatBranch(workingDir: string, base: string): Promise<string> {
@@ -778,11 +1028,12 @@ export async function gitResetFile(workingDir: string, relativePath: string, ref
 
 export async function gitShowFile(workingDir: string, filePath: string, ref = "HEAD"): Promise<string> {
   try {
-    const content = await runGit(workingDir, "show", `${ref}:${filePath}`);
-    // runGit trims stdout, but file content typically ends with a newline.
-    // Restore it so the diff view doesn't show a phantom trailing newline.
-    if (content.length > 0) return content + "\n";
-    return content;
+    const content = await invoke<string | null>("git_show_file", {
+      workingDirectory: workingDir,
+      rev: ref,
+      path: filePath,
+    });
+    return content ?? "";
   } catch {
     return "";
   }
@@ -8,10 +8,12 @@
  * when a writer re-registers.
  */
 
-type PtyWriter = (data: string) => void;
+/** Decoded text, or exact bytes from a session spawned with `binary`. */
+type PtyChunk = string | Uint8Array;
+type PtyWriter = (data: PtyChunk) => void;
 
 const writers = new Map<string, PtyWriter>();
-const buffers = new Map<string, string>();
+const buffers = new Map<string, { chunks: PtyChunk[]; size: number }>();
 
 const MAX_BUFFER_SIZE = 512 * 1024; // 512KB per task
 
@@ -20,7 +22,7 @@ export function registerPtyWriter(taskId: string, writer: PtyWriter): void {
   writers.set(taskId, writer);
   const buffered = buffers.get(taskId);
   if (buffered) {
-    writer(buffered);
+    for (const chunk of buffered.chunks) writer(chunk);
     buffers.delete(taskId);
   }
 }
@@ -31,20 +33,27 @@ export function unregisterPtyWriter(taskId: string): void {
 }
 
 /** Route PTY output to the registered writer, or buffer it. */
-export function routePtyOutput(taskId: string, data: string): void {
+export function routePtyOutput(taskId: string, data: PtyChunk): void {
   const writer = writers.get(taskId);
   if (writer) {
     writer(data);
   } else {
-    let existing = buffers.get(taskId) ?? "";
-    existing += data;
-    if (existing.length > MAX_BUFFER_SIZE) {
-      existing = existing.slice(-MAX_BUFFER_SIZE);
+    const buffered = buffers.get(taskId) ?? { chunks: [], size: 0 };
+    buffered.chunks.push(data);
+    buffered.size += data.length;
+    // Drop whole chunks so binary output isn't cut mid-sequence
+    while (buffered.size > MAX_BUFFER_SIZE && buffered.chunks.length > 1) {
+      buffered.size -= buffered.chunks.shift()!.length;
     }
-    buffers.set(taskId, existing);
+    buffers.set(taskId, buffered);
   }
 }
 
+/** Decode the base64 payload of an `output_bytes` event. */
+export function decodePtyBytes(data: string): Uint8Array {
+  return Uint8Array.from(atob(data), (c) => c.charCodeAt(0));
+}
+
 /** Clear buffered output only (keeps writer registered). */
 export function clearPtyBuffer(taskId: string): void {
   buffers.delete(taskId);
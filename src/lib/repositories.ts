@@ -116,6 +116,11 @@ export async function getEffectiveModel(
   return setting ?? undefined;
 }
 
+/** Environment profile applied to the project's stage runs and terminals, if one is selected. */
+export async function getEnvProfile(projectId: string): Promise<string | undefined> {
+  return (await getProjectSetting(projectId, "env_profile")) ?? undefined;
+}
+
 export async function getCompletionStrategy(projectId: string): Promise<CompletionStrategy> {
   return (await getProjectSetting(projectId, "default_completion_strategy") ?? "pr") as CompletionStrategy;
 }
@@ -248,11 +248,28 @@ export type AgentStreamEvent =
   | { type: "started"; process_id: string; session_id: string | null }
   | { type: "stdout_line"; line: string }
   | { type: "stderr_line"; line: string }
+  | { type: "paused"; process_id: string }
+  | { type: "resumed"; process_id: string; paused_ms: number }
+  | { type: "retrying"; process_id: string; attempt: number; reason: AgentFailureKind; delay_ms: number }
+  | { type: "hook_output"; process_id: string; phase: HookPhase; command: string; stream: "stdout" | "stderr"; line: string }
+  | { type: "hook_finished"; process_id: string; phase: HookPhase; command: string; exit_code: number | null }
+  | { type: "handed_over"; process_id: string; pty_id: string }
   | { type: "completed"; process_id: string; exit_code: number | null }
   | { type: "error"; process_id: string; message: string };
 
 // === Spawn Args ===
 
+export type AgentFailureKind = "rate_limit" | "network" | "crash_no_output";
+
+export interface AgentRetryPolicy {
+  /** Total attempts including the first one. */
+  maxAttempts: number;
+  baseDelayMs?: number;
+  maxDelayMs?: number;
+  /** Defaults to every failure kind. */
+  retryOn?: AgentFailureKind[];
+}
+
 export interface SpawnAgentArgs {
   prompt: string;
   agent?: string;
@@ -267,6 +284,14 @@ export interface SpawnAgentArgs {
   allowedTools?: string[];
   maxTurns?: number;
   mcpConfig?: string;
+  streamInput?: boolean;
+  retry?: AgentRetryPolicy;
+  /** Defaults to "auto": argv for small prompts, stdin or a temp file for large ones. */
+  promptDelivery?: "auto" | "argv" | "stdin" | "file";
+  projectId?: string;
+  envProfile?: string;
+  /** Stage template id, selects stage-specific lifecycle hooks. */
+  stageId?: string;
 }
 
 // === PTY Types ===
@@ -274,14 +299,191 @@ export interface SpawnAgentArgs {
 export type PtyEvent =
   | { type: "started"; id: string }
   | { type: "output"; data: string }
+  /** Base64 raw output of a session spawned with `binary`. */
+  | { type: "output_bytes"; data: string }
+  /** The screen matched an attention matcher, e.g. `permission_prompt` or `turn_finished`. */
+  | { type: "attention"; id: string; reason: string }
+  /** From a shell's integration snippet, or the terminal's foreground process. */
+  | { type: "command_started"; id: string; command: string }
+  /** `exit_code` is null when the shell didn't report it. */
+  | { type: "command_finished"; id: string; command: string; exit_code: number | null; duration_ms: number }
+  | { type: "cwd_changed"; id: string; cwd: string }
+  | { type: "paused"; id: string }
+  | { type: "resumed"; id: string; paused_ms: number }
+  /** Size changes while playing back a recording. */
+  | { type: "resized"; id: string; cols: number; rows: number }
   | { type: "exited"; id: string; exit_code: number | null }
   | { type: "error"; id: string; message: string };
 
 export interface SpawnPtyArgs {
   agent?: string;
+  /** Program to run instead of an agent or the shell, e.g. `npm` or `psql`. */
+  command?: string;
+  args?: string[];
+  /** Variables set on top of the environment profile. */
+  env?: Record<string, string>;
+  /** Start a login shell, or run `command` through one. */
+  loginShell?: boolean;
+  /** Saved terminal profile supplying any of the above that aren't set. */
+  terminalProfile?: string;
+  /** Load the bash, zsh or fish integration so the shell reports commands, exit codes and its cwd. */
+  shellIntegration?: boolean;
   personaModel?: string;
   workingDirectory?: string;
   appendSystemPrompt?: string;
+  /** Claude-format MCP config JSON, as for `spawnAgent`. */
+  mcpConfig?: string;
+  /** Claude session to start, or to continue when `resume` is set. */
+  sessionId?: string;
+  /** Continue `sessionId` (e.g. a headless stage's conversation) instead of starting it. */
+  resume?: boolean;
+  allowedTools?: string[];
+  maxTurns?: number;
   cols?: number;
   rows?: number;
+  projectId?: string;
+  envProfile?: string;
+  /** Lets the session be found again with `listPtys` after a reload. */
+  taskId?: string;
+  /** Host the session in stagehand-ptyd so it survives app restarts. */
+  persistent?: boolean;
+  /** Receive exact output bytes as `output_bytes` events, e.g. for sixel or kitty graphics. */
+  binary?: boolean;
+  /** Record the session as an asciicast v2 file for later playback. */
+  record?: boolean;
+}
+
+export interface PtyInfo {
+  id: string;
+  agent: string | null;
+  taskId: string | null;
+  workingDirectory: string | null;
+  pid: number | null;
+  cols: number;
+  rows: number;
+  paused: boolean;
+  startedAtMs: number;
+  subscribers: number;
+  /** Hosted by stagehand-ptyd rather than the app. */
+  persistent: boolean;
+  binary: boolean;
+  command: string | null;
+  /** Headless run this session took over with `takeoverProcess`. */
+  takenOverFrom: string | null;
+  stageExecutionId: string | null;
+  /** Current directory of a shell session, if known. */
+  cwd: string | null;
+}
+
+/** Signals `signalPty` delivers to a session's foreground process group. */
+export type PtySignal = "SIGINT" | "SIGTSTP" | "SIGHUP" | "SIGTERM";
+
+/** The process in the foreground of a PTY session's terminal. */
+export interface PtyForeground {
+  pid: number;
+  /** null where the platform has no /proc. */
+  name: string | null;
+  argv: string[];
+}
+
+/** A command that finished in one of a task's terminals. */
+export interface CommandHistoryEntry {
+  sessionId: string;
+  command: string;
+  cwd: string | null;
+  exitCode: number | null;
+  startedAtMs: number;
+  durationMs: number;
+}
+
+/** The visible screen of a PTY session, as the backend emulates it. */
+export interface PtySnapshot {
+  cols: number;
+  rows: number;
+  /** One string per screen row, trailing blanks trimmed. */
+  lines: string[];
+  cursorRow: number;
+  cursorCol: number;
+  cursorVisible: boolean;
+  /** Full-screen programs such as editors use the alternate screen. */
+  alternateScreen: boolean;
+}
+
+/** A screen condition that raises an `attention` PTY event. */
+export interface AttentionMatcher {
+  reason: string;
+  /** Only match sessions running this agent. */
+  agent?: string | null;
+  /** Regex the screen text must match; `^` and `$` match at row boundaries. */
+  pattern?: string | null;
+  /** Text the screen must contain. */
+  contains?: string | null;
+  /** Regex the screen must not match. */
+  unless?: string | null;
+  /** Only look at the last this many non-blank rows. */
+  lastRows?: number | null;
+}
+
+export interface TerminalProfile {
+  name: string;
+  command?: string | null;
+  args?: string[];
+  env?: Record<string, string>;
+  loginShell?: boolean;
+  /** Value for TERM. */
+  term?: string | null;
+}
+
+export interface RecordingInfo {
+  /** The recorded session's id. */
+  id: string;
+  taskId: string | null;
+  agent: string | null;
+  workingDirectory: string | null;
+  startedAtMs: number;
+  cols: number;
+  rows: number;
+  durationMs: number;
+  sizeBytes: number;
+}
+
+// === Environment Profiles ===
+
+export interface EnvVar {
+  key: string;
+  /** Null for secrets when listed; send null to keep a stored secret. */
+  value: string | null;
+  secret?: boolean;
+}
+
+export interface EnvProfile {
+  name: string;
+  envFiles?: string[];
+  secretKeys?: string[];
+  vars?: EnvVar[];
+  clearEnv?: boolean;
+}
+
+// === Lifecycle Hooks ===
+
+export type HookPhase = "pre" | "post";
+
+export type HookFailurePolicy = "abort" | "warn" | "feedback";
+
+export interface HookCommand {
+  command: string;
+  onFailure?: HookFailurePolicy;
+  timeoutSecs?: number;
+}
+
+export interface HookSet {
+  pre?: HookCommand[];
+  post?: HookCommand[];
+}
+
+export interface ProjectHooks {
+  project?: HookSet;
+  /** Keyed by stage template id. */
+  stages?: Record<string, HookSet>;
+  maxFeedbackRounds?: number;
 }
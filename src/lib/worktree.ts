@@ -1,9 +1,12 @@
 import type { Task } from "./types";
 import {
-  gitWorktreeRemove,
+  removeWorktree,
+  pruneWorktrees,
   gitDeleteBranch,
   gitCheckoutBranch,
 } from "./git";
+import { logger } from "./logger";
+import { sendNotification } from "./notifications";
 
 /**
  * Returns the working directory for a task: the worktree path if set,
@@ -25,7 +28,9 @@ export interface CleanupWorktreeOptions {
  * For ejected tasks (no worktree), checks out the default branch first
  * so the task branch can be safely deleted.
  *
- * All operations are best-effort — failures are silently ignored.
+ * All operations are best-effort — failures are logged and skipped. A
+ * worktree with uncommitted changes, or a locked one, is kept along with
+ * its branch, and the user is told.
  */
 export async function cleanupTaskWorktree(
   projectPath: string,
@@ -39,17 +44,23 @@ export async function cleanupTaskWorktree(
   if (task.ejected && !task.worktree_path) {
     try {
       await gitCheckoutBranch(projectPath, defaultBranch ?? "main");
-    } catch {
-      // Non-critical
+    } catch (err) {
+      logger.warn("Failed to check out default branch during cleanup", err);
     }
   }
 
-  // Remove the worktree directory
   if (task.worktree_path) {
     try {
-      await gitWorktreeRemove(projectPath, task.worktree_path);
-    } catch {
-      // Non-critical — worktree may already be gone
+      await removeWorktree(projectPath, task.worktree_path);
+    } catch (err) {
+      const message = err instanceof Error ? err.message : String(err);
+      logger.warn("Failed to remove worktree", task.worktree_path, err);
+      if (/uncommitted changes|is locked/.test(message)) {
+        sendNotification("Worktree kept", message, "info");
+        return;
+      }
+      // The directory may already be gone; drop git's stale entry instead
+      try { await pruneWorktrees(projectPath); } catch { /* ok */ }
     }
   }
 
@@ -57,8 +68,8 @@ export async function cleanupTaskWorktree(
   if (deleteBranch && task.branch_name) {
     try {
       await gitDeleteBranch(projectPath, task.branch_name);
-    } catch {
-      // Non-critical — branch may already be gone
+    } catch (err) {
+      logger.warn("Failed to delete branch", task.branch_name, err);
     }
   }
 }
@@ -1,6 +1,7 @@
 import { create } from "zustand";
 import { getSetting, setSetting } from "../lib/repositories";
 import { DEFAULT_KEYBINDINGS, type KeyBindingAction } from "../lib/keybindings";
+import { persistentTerminalsAvailable } from "../lib/agent";
 
 export type TextSize = "xs" | "s" | "m" | "l" | "xl";
 
@@ -30,6 +31,9 @@ interface SettingsState {
   editorFontSize: number;
   diffViewMode: "inline" | "sideBySide";
   terminalFontSize: number;
+  persistentTerminals: boolean;
+  /** The stagehand-ptyd host is bundled; `persistentTerminals` is ignored without it. */
+  persistentTerminalsAvailable: boolean;
   externalEditor: ExternalEditor;
   externalEditorCommand: string;
   keybindings: Record<KeyBindingAction, string>;
@@ -42,6 +46,7 @@ interface SettingsState {
   setEditorFontSize: (v: number) => void;
   setDiffViewMode: (v: "inline" | "sideBySide") => void;
   setTerminalFontSize: (v: number) => void;
+  setPersistentTerminals: (v: boolean) => void;
   setExternalEditor: (v: ExternalEditor) => void;
   setExternalEditorCommand: (v: string) => void;
   setKeybinding: (action: KeyBindingAction, shortcut: string) => void;
@@ -60,22 +65,26 @@ export const useSettingsStore = create<SettingsState>((set, get) => ({
   editorFontSize: 13,
   diffViewMode: "inline",
   terminalFontSize: 13,
+  persistentTerminals: false,
+  persistentTerminalsAvailable: false,
   externalEditor: "builtin",
   externalEditorCommand: "",
   keybindings: { ...DEFAULT_KEYBINDINGS },
   loaded: false,
 
   async load() {
-    const [appTextSize, appSidebar, editorSidebar, editorFont, diffView, termFont, extEditor, extEditorCmd, keybindingsRaw] = await Promise.all([
+    const [appTextSize, appSidebar, editorSidebar, editorFont, diffView, termFont, persistentTerms, extEditor, extEditorCmd, keybindingsRaw, ptydAvailable] = await Promise.all([
       getSetting("appTextSize"),
       getSetting("appSidebarPosition"),
       getSetting("editorSidebarPosition"),
       getSetting("editorFontSize"),
       getSetting("diffViewMode"),
       getSetting("terminalFontSize"),
+      getSetting("persistentTerminals"),
       getSetting("externalEditor"),
       getSetting("externalEditorCommand"),
       getSetting("keybindings"),
+      persistentTerminalsAvailable().catch(() => false),
     ]);
     let keybindings = { ...DEFAULT_KEYBINDINGS };
     if (keybindingsRaw) {
@@ -91,6 +100,8 @@ export const useSettingsStore = create<SettingsState>((set, get) => ({
       editorFontSize: editorFont ? Number(editorFont) : 13,
       diffViewMode: diffView === "sideBySide" ? "sideBySide" : "inline",
       terminalFontSize: termFont ? Number(termFont) : 13,
+      persistentTerminals: persistentTerms === "true",
+      persistentTerminalsAvailable: ptydAvailable,
       externalEditor: extEditor && VALID_EDITORS.has(extEditor) ? extEditor as ExternalEditor : "builtin",
       externalEditorCommand: extEditorCmd ?? "",
       keybindings,
@@ -122,6 +133,10 @@ export const useSettingsStore = create<SettingsState>((set, get) => ({
     set({ terminalFontSize: v });
     setSetting("terminalFontSize", String(v));
   },
+  setPersistentTerminals(v) {
+    set({ persistentTerminals: v });
+    setSetting("persistentTerminals", String(v));
+  },
   setExternalEditor(v) {
     set({ externalEditor: v });
     setSetting("externalEditor", v);